@@ -1,9 +1,15 @@
 // Copyright 2023-2024 The Milton Hirsch Institute, B.V.
 // SPDX-License-Identifier: Apache-2.0
 
+use chrono::{DateTime, Utc};
 use cidr::IpCidr;
+use std::path::PathBuf;
+use subnet_garden_core::pool::SubnetPool;
+use subnet_garden_core::storage::Format;
 use subnet_garden_core::Bits;
 
+use crate::util::color::Painter;
+
 pub(crate) mod init {
     use cidr::IpCidr;
 
@@ -14,9 +20,21 @@ pub(crate) mod init {
         /// Force initialization even if the pool file already exists
         pub(crate) force: bool,
 
-        #[arg()]
+        #[arg(required_unless_present = "from", conflicts_with = "from")]
         /// Pool subnet CIDR
-        pub(crate) cidr: IpCidr,
+        pub(crate) cidr: Option<IpCidr>,
+
+        #[arg(long)]
+        /// Copy the subnets and names of an existing pool file
+        pub(crate) from: Option<String>,
+
+        #[arg(long, requires = "from")]
+        /// Renumber the copied pool into this CIDR
+        pub(crate) root: Option<IpCidr>,
+
+        #[arg(short, long)]
+        /// CIDR within the pool that is never to be allocated (repeatable)
+        pub(crate) exclude: Vec<IpCidr>,
     }
 }
 
@@ -24,9 +42,13 @@ pub(crate) mod init {
 /// Allocate subnet
 pub(crate) struct AllocateArgs {
     #[arg()]
-    /// Number of subnet bits
+    /// Number of subnet bits, or network prefix length with --prefix
     pub(crate) bits: Bits,
 
+    #[arg(long, visible_alias = "prefix-length")]
+    /// Give the subnet size as a network prefix length (e.g. 24 for a /24)
+    pub(crate) prefix: bool,
+
     #[arg()]
     /// Name or format of the subnet to allocate
     pub(crate) name_format: Option<String>,
@@ -34,6 +56,138 @@ pub(crate) struct AllocateArgs {
     #[arg()]
     /// Parameters for subnet name format
     pub(crate) param: Option<Vec<String>>,
+
+    #[arg(long, conflicts_with_all = ["name_format", "param"])]
+    /// Literal name of the subnet to allocate, used without formatting
+    pub(crate) name: Option<String>,
+
+    #[arg(long, conflicts_with_all = ["name_format", "param", "name"])]
+    /// Number of unnamed subnets to allocate
+    pub(crate) count: Option<usize>,
+
+    #[arg(short, long, conflicts_with = "count")]
+    /// Allocate only from free space within the given CIDR
+    pub(crate) within: Option<IpCidr>,
+
+    #[arg(long, conflicts_with_all = ["count", "param", "within"])]
+    /// Claim this CIDR if it is free, otherwise allocate the next free subnet
+    pub(crate) at: Option<IpCidr>,
+
+    #[arg(long, conflicts_with_all = ["count", "within", "at"])]
+    /// Allocate from the top of the pool downward
+    pub(crate) high: bool,
+
+    #[arg(long, requires = "count")]
+    /// Name subnets allocated with --count subnet-0, subnet-1, ...
+    pub(crate) auto_name: bool,
+
+    #[arg(short, long, value_enum, default_value_t = OutputFormat::Text)]
+    /// Print the allocated CIDRs one per line or the new records as JSON
+    pub(crate) output: OutputFormat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub(crate) enum OutputFormat {
+    Text,
+    Json,
+}
+
+#[derive(Debug, clap::Args)]
+/// Show a pool metadata value
+pub(crate) struct ConfigGetArgs {
+    #[arg()]
+    /// Metadata key
+    pub(crate) key: String,
+}
+
+#[derive(Debug, clap::Args)]
+/// List all pool metadata
+pub(crate) struct ConfigListArgs {
+    #[arg(short, long, value_enum, default_value_t = OutputFormat::Text)]
+    /// Print `key = value` lines or a JSON object
+    pub(crate) output: OutputFormat,
+}
+
+#[derive(Debug, clap::Args)]
+/// Set a pool metadata value
+pub(crate) struct ConfigSetArgs {
+    #[arg()]
+    /// Metadata key
+    pub(crate) key: String,
+
+    #[arg()]
+    /// Metadata value
+    pub(crate) value: String,
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub(crate) enum ConfigCommands {
+    Get(ConfigGetArgs),
+    List(ConfigListArgs),
+    Set(ConfigSetArgs),
+}
+
+#[derive(Debug, clap::Args)]
+/// Show and change pool-level metadata
+pub(crate) struct ConfigArgs {
+    #[command(subcommand)]
+    pub(crate) command: ConfigCommands,
+}
+
+#[derive(Debug, clap::Args)]
+/// Convert a pool file to another format
+pub(crate) struct ConvertArgs {
+    #[arg(long = "in")]
+    /// Pool file to convert
+    pub(crate) input: String,
+
+    #[arg(long = "out")]
+    /// Pool file to write
+    pub(crate) output: String,
+
+    #[arg(long)]
+    /// Format of the input instead of the one told by the file
+    pub(crate) input_format: Option<Format>,
+
+    #[arg(long)]
+    /// Format of the output instead of the one told by its extension
+    pub(crate) output_format: Option<Format>,
+}
+
+#[derive(Debug, clap::Args)]
+/// Write the pool to stdout for other programs
+pub(crate) struct ExportArgs {
+    #[arg(long, value_enum)]
+    /// Format to write the pool in
+    pub(crate) format: ExchangeFormat,
+
+    #[arg(long)]
+    /// Encode the output as base64, for binary formats
+    pub(crate) base64: bool,
+}
+
+#[derive(Debug, clap::Args)]
+/// Create the pool file from a pool read from stdin
+pub(crate) struct ImportArgs {
+    #[arg(long, value_enum)]
+    /// Format the pool is read in
+    pub(crate) format: ExchangeFormat,
+
+    #[arg(long)]
+    /// Decode the input from base64
+    pub(crate) base64: bool,
+
+    #[arg(short, long)]
+    /// Replace the pool if it already exists
+    pub(crate) force: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub(crate) enum ExchangeFormat {
+    Json,
+    Postcard,
+    Toml,
+    Yaml,
 }
 
 #[derive(Debug, clap::Args)]
@@ -50,6 +204,10 @@ pub(crate) struct FreeArgs {
     #[arg(short, long)]
     /// Ignore missing subnets
     pub(crate) ignore_missing: bool,
+
+    #[arg(short, long)]
+    /// Free subnets even if they are locked
+    pub(crate) force: bool,
 }
 
 #[derive(Debug, clap::Args)]
@@ -59,9 +217,45 @@ pub(crate) struct CidrsArgs {
     /// List CIDRs in long format
     pub(crate) long: bool,
 
+    #[arg(long)]
+    /// Omit the totals line from long format
+    pub(crate) no_header: bool,
+
     #[arg(short, long, default_value = None)]
     /// List CIDRs within the given CIDR
     pub(crate) within: Option<IpCidr>,
+
+    #[arg(long, requires = "long")]
+    /// Add the number of usable host addresses to long format
+    pub(crate) show_hosts: bool,
+
+    #[arg(long, value_parser = crate::util::time::parse_time)]
+    /// List CIDRs created before the given age (e.g. 30d), date or time
+    pub(crate) older_than: Option<DateTime<Utc>>,
+
+    #[arg(long, value_parser = crate::util::time::parse_time)]
+    /// List CIDRs created after the given age (e.g. 30d), date or time
+    pub(crate) newer_than: Option<DateTime<Utc>>,
+
+    #[arg(long)]
+    /// Include CIDRs without a creation time when filtering by time
+    pub(crate) include_untimed: bool,
+
+    #[arg(long, conflicts_with = "only_unnamed")]
+    /// List only named CIDRs
+    pub(crate) only_named: bool,
+
+    #[arg(long)]
+    /// List only unnamed CIDRs
+    pub(crate) only_unnamed: bool,
+
+    #[arg(long, conflicts_with = "long")]
+    /// List `cidr<TAB>name` lines in a stable format for scripts
+    pub(crate) porcelain: bool,
+
+    #[arg(short, long)]
+    /// List CIDRs in descending address order
+    pub(crate) reverse: bool,
 }
 
 #[derive(Debug, clap::Args)]
@@ -70,18 +264,66 @@ pub(crate) struct NamesArgs {
     #[arg(short)]
     /// List named CIDRs in long format
     pub(crate) long: bool,
+
+    #[arg(long)]
+    /// Omit the totals line from long format
+    pub(crate) no_header: bool,
+
+    #[arg(long, conflicts_with = "long")]
+    /// List `cidr<TAB>name` lines in a stable format for scripts
+    pub(crate) porcelain: bool,
+
+    #[arg(short, long)]
+    /// List names in descending order
+    pub(crate) reverse: bool,
+}
+
+#[derive(Debug, clap::Args)]
+/// Reconcile the pool with the subnets described in a pool file
+pub(crate) struct ApplyArgs {
+    #[arg()]
+    /// Pool file with the desired subnets and names
+    pub(crate) desired: String,
+
+    #[arg(long)]
+    /// Free allocated subnets that are not in the desired pool file
+    pub(crate) prune: bool,
+
+    #[arg(long)]
+    /// Print the actions without changing the pool
+    pub(crate) dry_run: bool,
 }
 
 #[derive(Debug, clap::Args)]
 /// Claim subnet
 pub(crate) struct ClaimArgs {
-    #[arg()]
+    #[arg(required_unless_present = "from")]
     /// CIDR subnet to claim
-    pub(crate) cidr: IpCidr,
+    pub(crate) cidr: Option<IpCidr>,
 
     #[arg()]
     /// Name of the subnet to claim
     pub(crate) name: Option<String>,
+
+    #[arg(long, conflicts_with_all = ["cidr", "name"])]
+    /// File listing one subnet to claim per line as CIDR and optional name
+    pub(crate) from: Option<PathBuf>,
+}
+
+#[derive(Debug, clap::Args)]
+/// Allocate subnets sized for numbers of hosts, largest first
+pub(crate) struct PlanArgs {
+    #[arg(required_unless_present = "from", value_parser = crate::subcommands::subnet::parse_requirement)]
+    /// Number of hosts of each subnet, named as HOSTS:NAME
+    pub(crate) requirements: Vec<(u128, Option<String>)>,
+
+    #[arg(long, conflicts_with = "requirements")]
+    /// File listing the number of hosts and optional name of one subnet per line
+    pub(crate) from: Option<PathBuf>,
+
+    #[arg(long)]
+    /// Print the plan without changing the pool
+    pub(crate) dry_run: bool,
 }
 
 #[derive(Debug, clap::Args)]
@@ -94,22 +336,182 @@ pub(crate) struct RenameArgs {
     #[arg()]
     /// New name of the subnet or omit to remove the name
     pub(crate) name: Option<String>,
+
+    #[arg(short, long)]
+    /// Rename the subnet even if it is locked
+    pub(crate) force: bool,
+}
+
+#[derive(Debug, clap::Args)]
+/// Protect a subnet from being freed or renamed
+pub(crate) struct LockArgs {
+    #[arg()]
+    /// Name or CIDR of the subnet to lock
+    pub(crate) identifier: String,
+}
+
+#[derive(Debug, clap::Args)]
+/// Allow a locked subnet to be freed and renamed again
+pub(crate) struct UnlockArgs {
+    #[arg()]
+    /// Name or CIDR of the subnet to unlock
+    pub(crate) identifier: String,
+}
+
+#[derive(Debug, clap::Args)]
+/// Move all subnets to a new pool CIDR of the same size
+pub(crate) struct RenumberArgs {
+    #[arg()]
+    /// New pool subnet CIDR
+    pub(crate) cidr: IpCidr,
+}
+
+#[derive(Debug, clap::Args)]
+/// Split a block into equal sized subnets
+pub(crate) struct SplitArgs {
+    #[arg()]
+    /// CIDR of the block to split
+    pub(crate) cidr: IpCidr,
+
+    #[arg()]
+    /// Number of subnet bits of each child subnet
+    pub(crate) bits: Bits,
+
+    #[arg()]
+    /// Name format of the child subnets, given the index of each child
+    pub(crate) name_format: Option<String>,
+}
+
+#[derive(Debug, clap::Args)]
+/// Search subnet names with a regular expression
+pub(crate) struct GrepArgs {
+    #[arg(allow_hyphen_values = true)]
+    /// Regular expression matched against subnet names
+    pub(crate) pattern: String,
+
+    #[arg(short)]
+    /// Match case-insensitively
+    pub(crate) ignore_case: bool,
+
+    #[arg(long)]
+    /// Show named subnets that do not match
+    pub(crate) invert: bool,
+}
+
+#[derive(Debug, clap::Args)]
+/// Render a template for every subnet
+pub(crate) struct RenderArgs {
+    #[arg()]
+    /// Template with {cidr}, {name}, {first} and {last} placeholders
+    pub(crate) template: String,
 }
 
 #[derive(Debug, clap::Args)]
 /// Largest available subnet (by bits)
-pub(crate) struct MaxAvailableArgs {}
+pub(crate) struct MaxAvailableArgs {
+    #[arg(long)]
+    /// List how many subnets of every size could be allocated
+    pub(crate) all: bool,
+
+    #[arg(long, visible_alias = "prefix-length")]
+    /// Show subnet sizes as network prefix lengths instead of host bits
+    pub(crate) prefix: bool,
+}
+
+#[derive(Debug, clap::Args)]
+/// Summarize how much of the pool is in use
+pub(crate) struct StatsArgs {
+    #[arg(short, long, value_enum, default_value_t = OutputFormat::Text)]
+    /// Print `key = value` lines or a JSON object
+    pub(crate) output: OutputFormat,
+}
+
+#[derive(Debug, clap::Args)]
+/// Add a pool to the pool file
+pub(crate) struct SpaceNewArgs {
+    #[arg()]
+    /// Name of the new pool
+    pub(crate) name: String,
+
+    #[arg()]
+    /// Pool subnet CIDR
+    pub(crate) cidr: IpCidr,
+}
+
+#[derive(Debug, clap::Args)]
+/// Remove a pool and all its subnets from the pool file
+pub(crate) struct SpaceDeleteArgs {
+    #[arg()]
+    /// Name of the pool to remove
+    pub(crate) name: String,
+}
+
+#[derive(Debug, clap::Args)]
+/// List the names of the pools in the pool file
+pub(crate) struct SpaceListArgs {}
+
+#[derive(Debug, clap::Subcommand)]
+pub(crate) enum SpaceCommands {
+    Delete(SpaceDeleteArgs),
+    List(SpaceListArgs),
+    New(SpaceNewArgs),
+}
+
+#[derive(Debug, clap::Args)]
+/// Manage the named pools of a file holding several pools
+pub(crate) struct SpaceArgs {
+    #[command(subcommand)]
+    pub(crate) command: SpaceCommands,
+}
+
+#[derive(Debug, clap::Args)]
+/// Show how the pool is divided into subnets and free blocks
+pub(crate) struct TreeArgs {}
+
+#[derive(Debug, clap::Args)]
+/// Check the pool for inconsistencies
+pub(crate) struct ValidateArgs {}
+
+#[derive(Debug, clap::Subcommand)]
+pub(crate) enum WatchCommands {
+    Cidrs(CidrsArgs),
+    Names(NamesArgs),
+}
+
+#[derive(Debug, clap::Args)]
+/// Re-print a listing whenever the pool file changes
+pub(crate) struct WatchArgs {
+    #[command(subcommand)]
+    pub(crate) command: WatchCommands,
+}
 
 #[derive(Debug, clap::Subcommand)]
 pub(crate) enum SubgCommands {
     Allocate(AllocateArgs),
+    Apply(ApplyArgs),
     Cidrs(CidrsArgs),
     Claim(ClaimArgs),
+    Config(ConfigArgs),
+    Convert(ConvertArgs),
+    Export(ExportArgs),
     Free(FreeArgs),
+    Grep(GrepArgs),
+    Import(ImportArgs),
     Init(init::InitArgs),
+    Lock(LockArgs),
     MaxAvailable(MaxAvailableArgs),
     Names(NamesArgs),
+    Plan(PlanArgs),
     Rename(RenameArgs),
+    Render(RenderArgs),
+    Renumber(RenumberArgs),
+    Space(SpaceArgs),
+    Split(SplitArgs),
+    Stats(StatsArgs),
+    Tree(TreeArgs),
+    Unlock(UnlockArgs),
+    Validate(ValidateArgs),
+    Watch(WatchArgs),
 }
 
 #[derive(Debug, clap::Args)]
@@ -117,6 +519,77 @@ pub(crate) enum SubgCommands {
 pub(crate) struct SubgArgs {
     #[arg(short = 'p', long, default_value = subg::DEFAULT_STORAGE_PATH, env = "SUBG_POOL_PATH")]
     pub(crate) pool_path: String,
+
+    #[arg(long, env = "SUBG_POOL_NAME")]
+    /// Use the named pool of a file holding several pools
+    pub(crate) pool_name: Option<String>,
+
+    #[arg(long)]
+    /// Abort unless the loaded pool has this CIDR
+    pub(crate) pool_cidr: Option<IpCidr>,
+
+    #[arg(long)]
+    /// Write JSON pool files on a single line instead of pretty printed
+    pub(crate) compact: bool,
+
+    #[arg(short, long, conflicts_with = "verbose")]
+    /// Do not print what commands changing the pool did
+    pub(crate) quiet: bool,
+
+    #[arg(short, long)]
+    /// Report the free blocks subnets come from and the pool totals
+    pub(crate) verbose: bool,
+
+    #[arg(long, value_enum, default_value_t = ColorChoice::Auto)]
+    /// Highlight long listings; by default only when stdout is a terminal
+    pub(crate) color: ColorChoice,
+
+    #[arg(long, overrides_with = "lenient")]
+    /// Fail to load a pool file with overlapping subnets (the default)
+    pub(crate) strict: bool,
+
+    #[arg(long, overrides_with = "strict")]
+    /// Skip subnets overlapping an earlier subnet of the pool file, with a
+    /// warning, instead of failing
+    pub(crate) lenient: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub(crate) enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+impl SubgArgs {
+    /// Load the pool selected by the pool path and name, checking it is the
+    /// pool given by --pool-cidr.
+    pub(crate) fn load_pool(&self) -> SubnetPool {
+        let pool = match self.lenient && !self.strict {
+            true => subg::load_pool_lenient(&self.pool_path, self.pool_name.as_deref()),
+            false => subg::load_pool_named(&self.pool_path, self.pool_name.as_deref()),
+        };
+        if let Some(pool_cidr) = self.pool_cidr {
+            if *pool.cidr() != pool_cidr {
+                eprintln!("Pool CIDR is {}, expected {pool_cidr}", pool.cidr());
+                std::process::exit(exitcode::USAGE);
+            }
+        }
+        pool
+    }
+
+    pub(crate) fn painter(&self) -> Painter {
+        Painter::new(self.color)
+    }
+
+    pub(crate) fn store_pool(&self, pool: &SubnetPool) {
+        subg::store_pool_named(
+            &self.pool_path,
+            self.pool_name.as_deref(),
+            pool,
+            self.compact,
+        );
+    }
 }
 
 #[derive(Debug, clap::Parser)]
@@ -4,9 +4,13 @@
 use crate::args::{Subg, SubgCommands};
 
 use clap::Parser;
+use subcommands::config;
 use subcommands::init;
+use subcommands::interchange;
+use subcommands::space;
 use subcommands::subnet;
 use subcommands::subnet::listing;
+use subcommands::watch;
 
 mod args;
 mod param_str;
@@ -23,6 +27,18 @@ fn main() {
         SubgCommands::Allocate(args) => {
             subnet::allocate(&subg.args, &args);
         }
+        SubgCommands::Apply(args) => {
+            subnet::apply(&subg.args, &args);
+        }
+        SubgCommands::Convert(args) => {
+            interchange::convert(&subg.args, &args);
+        }
+        SubgCommands::Export(args) => {
+            interchange::export(&subg.args, &args);
+        }
+        SubgCommands::Import(args) => {
+            interchange::import(&subg.args, &args);
+        }
         SubgCommands::Free(args) => {
             subnet::free(&subg.args, &args);
         }
@@ -32,14 +48,53 @@ fn main() {
         SubgCommands::Names(args) => {
             listing::names(&subg.args, &args);
         }
+        SubgCommands::Grep(args) => {
+            listing::grep(&subg.args, &args);
+        }
+        SubgCommands::Render(args) => {
+            listing::render(&subg.args, &args);
+        }
         SubgCommands::Claim(args) => {
             subnet::claim(&subg.args, &args);
         }
+        SubgCommands::Config(args) => {
+            config::config(&subg.args, &args);
+        }
+        SubgCommands::Lock(args) => {
+            subnet::lock(&subg.args, &args);
+        }
+        SubgCommands::Unlock(args) => {
+            subnet::unlock(&subg.args, &args);
+        }
         SubgCommands::Rename(args) => {
             subnet::rename(&subg.args, &args);
         }
-        SubgCommands::MaxAvailable(_) => {
-            subnet::max_bits(&subg.args);
+        SubgCommands::MaxAvailable(args) => {
+            subnet::max_bits(&subg.args, &args);
+        }
+        SubgCommands::Renumber(args) => {
+            subnet::renumber(&subg.args, &args);
+        }
+        SubgCommands::Space(args) => {
+            space::space(&subg.args, &args);
+        }
+        SubgCommands::Split(args) => {
+            subnet::split(&subg.args, &args);
+        }
+        SubgCommands::Stats(args) => {
+            subnet::stats(&subg.args, &args);
+        }
+        SubgCommands::Tree(args) => {
+            listing::tree(&subg.args, &args);
+        }
+        SubgCommands::Plan(args) => {
+            subnet::plan(&subg.args, &args);
+        }
+        SubgCommands::Validate(args) => {
+            subnet::validate(&subg.args, &args);
+        }
+        SubgCommands::Watch(args) => {
+            watch::watch(&subg.args, &args);
         }
     }
 }
@@ -83,7 +83,7 @@ type FormatState = State<BuildFormat, char, ParseError>;
 type FormatResult = ParseResult<BuildFormat, char, ParseError>;
 type FormatTermination = Termination<BuildFormat, char, ParseError>;
 
-static TEXT_STATE: FormatState = state(|b, c| -> FormatResult {
+static TEXT_STATE: FormatState = state("text", |b, c| -> FormatResult {
     match c {
         '\\' => Ok(ESCAPE_STATE),
         '{' => {
@@ -97,12 +97,12 @@ static TEXT_STATE: FormatState = state(|b, c| -> FormatResult {
     }
 });
 
-static ESCAPE_STATE: FormatState = state(|b, c| -> FormatResult {
+static ESCAPE_STATE: FormatState = state("escape", |b, c| -> FormatResult {
     b.add_text(c);
     Ok(TEXT_STATE)
 });
 
-static PADDING_STATE: FormatState = state(|b, c| -> FormatResult {
+static PADDING_STATE: FormatState = state("padding", |b, c| -> FormatResult {
     match c {
         '0'..='9' => {
             b.padding = b.padding * 10 + c.to_digit(10).unwrap() as usize;
@@ -115,7 +115,7 @@ static PADDING_STATE: FormatState = state(|b, c| -> FormatResult {
     }
 });
 
-static START_PADDING_STATE: FormatState = state(|b, c| -> FormatResult {
+static START_PADDING_STATE: FormatState = state("start_padding", |b, c| -> FormatResult {
     match c {
         '0' => {
             b.pad_char = '0';
@@ -126,7 +126,7 @@ static START_PADDING_STATE: FormatState = state(|b, c| -> FormatResult {
     }
 });
 
-static VARIABLE_STATE: FormatState = state(|b, c| -> FormatResult {
+static VARIABLE_STATE: FormatState = state("variable", |b, c| -> FormatResult {
     match c {
         '}' => {
             b.add_variable();
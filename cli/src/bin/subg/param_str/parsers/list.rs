@@ -39,7 +39,7 @@ type ListResult = ParseResult<BuildList, char, ParseError>;
 type ListTermination = Termination<BuildList, char, ParseError>;
 type ListStateMachine = state_machine::StateMachine<BuildList, char, ParseError>;
 
-static TEXT_STATE: ListState = state(|b, c| -> ListResult {
+static TEXT_STATE: ListState = state("text", |b, c| -> ListResult {
     match c {
         ',' => {
             b.add_list_item()?;
@@ -51,7 +51,7 @@ static TEXT_STATE: ListState = state(|b, c| -> ListResult {
         }
     }
 });
-static DELIMIT_STATE: ListState = state(|b, c| -> ListResult {
+static DELIMIT_STATE: ListState = state("delimit", |b, c| -> ListResult {
     b.add_text(c);
     Ok(TEXT_STATE)
 });
@@ -43,7 +43,7 @@ type RangeResult = ParseResult<BuildRange, char, ParseError>;
 type RangeTermination = Termination<BuildRange, char, ParseError>;
 type RangeStateMachine = state_machine::StateMachine<BuildRange, char, ParseError>;
 
-static INIT_STATE: RangeState = state(|_b, c| -> RangeResult {
+static INIT_STATE: RangeState = state("init", |_b, c| -> RangeResult {
     match c {
         '%' => Ok(START_STATE),
         _ => Err(ParseError::InvalidValue(
@@ -52,7 +52,7 @@ static INIT_STATE: RangeState = state(|_b, c| -> RangeResult {
     }
 });
 
-static START_STATE: RangeState = state(|b, c| -> RangeResult {
+static START_STATE: RangeState = state("start", |b, c| -> RangeResult {
     match c {
         '0'..='9' => {
             b.add_text(c);
@@ -75,7 +75,7 @@ static START_STATE: RangeState = state(|b, c| -> RangeResult {
     }
 });
 
-static END_STATE: RangeState = state(|b, c| -> RangeResult {
+static END_STATE: RangeState = state("end", |b, c| -> RangeResult {
     match c {
         '0'..='9' => {
             b.add_text(c);
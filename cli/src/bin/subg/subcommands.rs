@@ -1,5 +1,9 @@
 // Copyright 2023 The Milton Hirsch Institute, B.V.
 // SPDX-License-Identifier: Apache-2.0
 
+pub(crate) mod config;
 pub(crate) mod init;
+pub(crate) mod interchange;
+pub(crate) mod space;
 pub(crate) mod subnet;
+pub(crate) mod watch;
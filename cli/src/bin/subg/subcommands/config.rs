@@ -0,0 +1,52 @@
+// Copyright 2024 The Milton Hirsch Institute, B.V.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::args::{
+    ConfigArgs, ConfigCommands, ConfigGetArgs, ConfigListArgs, ConfigSetArgs, OutputFormat,
+    SubgArgs,
+};
+use std::process::exit;
+
+fn get(subg: &SubgArgs, args: &ConfigGetArgs) {
+    let pool = subg.load_pool();
+    match pool.metadata().get(&args.key) {
+        Some(value) => println!("{value}"),
+        None => {
+            eprintln!("No such key: {}", args.key);
+            exit(exitcode::DATAERR);
+        }
+    }
+}
+
+fn list(subg: &SubgArgs, args: &ConfigListArgs) {
+    let pool = subg.load_pool();
+    match args.output {
+        OutputFormat::Text => {
+            for (key, value) in pool.metadata() {
+                println!("{key} = {value}");
+            }
+        }
+        OutputFormat::Json => {
+            let json = subg::result(
+                serde_json::to_string_pretty(pool.metadata()),
+                exitcode::SOFTWARE,
+                "Could not write metadata",
+            );
+            println!("{json}");
+        }
+    }
+}
+
+fn set(subg: &SubgArgs, args: &ConfigSetArgs) {
+    let mut pool = subg.load_pool();
+    pool.set_metadata(&args.key, &args.value);
+    subg.store_pool(&pool);
+}
+
+pub(crate) fn config(subg: &SubgArgs, args: &ConfigArgs) {
+    match &args.command {
+        ConfigCommands::Get(args) => get(subg, args),
+        ConfigCommands::List(args) => list(subg, args),
+        ConfigCommands::Set(args) => set(subg, args),
+    }
+}
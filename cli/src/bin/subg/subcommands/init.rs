@@ -7,17 +7,64 @@ use std::path::Path;
 use std::process::exit;
 use subnet_garden_core::pool;
 
-pub(crate) fn init(subg: &SubgArgs, args: &InitArgs) {
+/// Tag of the allocations that keep excluded ranges from being allocated.
+pub(crate) const EXCLUDED_TAG: &str = "excluded";
+
+/// Exit unless a new pool may be written to the pool path: the file must not
+/// exist yet, or with a pool name must not hold that pool yet, unless
+/// `force` is set.
+pub(crate) fn check_new_pool(subg: &SubgArgs, force: bool) {
     let path = Path::new(&subg.pool_path);
     if path.exists() {
-        if !args.force {
-            eprintln!("Pool file already exists at {}", path.display());
-            exit(exitcode::CANTCREAT);
-        }
         if !path.is_file() {
             eprintln!("Path is not a file at {}", path.display());
             exit(exitcode::CANTCREAT);
         }
+        match &subg.pool_name {
+            // Adding a pool to a pool set file leaves the other pools be.
+            Some(pool_name) => {
+                if !force
+                    && subg::load_pool_set(&subg.pool_path)
+                        .get(pool_name)
+                        .is_some()
+                {
+                    eprintln!("Pool {pool_name} already exists in {}", path.display());
+                    exit(exitcode::CANTCREAT);
+                }
+            }
+            None => {
+                if !force {
+                    eprintln!("Pool file already exists at {}", path.display());
+                    exit(exitcode::CANTCREAT);
+                }
+            }
+        }
+    }
+}
+
+pub(crate) fn init(subg: &SubgArgs, args: &InitArgs) {
+    check_new_pool(subg, args.force);
+    let mut pool = match (&args.from, args.cidr) {
+        (Some(from), _) => {
+            let source = subg::load_pool(from);
+            match args.root {
+                Some(root) => subg::result(
+                    source.renumber(root),
+                    exitcode::SOFTWARE,
+                    "Could not renumber pool",
+                ),
+                None => source,
+            }
+        }
+        (None, Some(cidr)) => pool::SubnetPool::new(cidr),
+        (None, None) => unreachable!("clap requires cidr unless from is present"),
+    };
+    for cidr in &args.exclude {
+        subg::result(
+            pool.claim_full(cidr, None, None, &[EXCLUDED_TAG]),
+            exitcode::USAGE,
+            format!("Could not exclude {cidr}").as_str(),
+        );
     }
-    subg::store_pool(&subg.pool_path, &pool::SubnetPool::new(args.cidr));
+    subg.store_pool(&pool);
 }
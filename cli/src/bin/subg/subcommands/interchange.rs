@@ -0,0 +1,89 @@
+// Copyright 2024 The Milton Hirsch Institute, B.V.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::args::{ConvertArgs, ExchangeFormat, ExportArgs, ImportArgs, SubgArgs};
+use crate::subcommands::init;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use std::io::{stdin, stdout, Read, Write};
+use subnet_garden_core::pool::SubnetPool;
+use subnet_garden_core::storage::{self, Format};
+
+/// Pool file format of the text exchange formats, or `None` for postcard.
+fn storage_format(format: ExchangeFormat) -> Option<Format> {
+    match format {
+        ExchangeFormat::Json => Some(Format::Json),
+        ExchangeFormat::Postcard => None,
+        ExchangeFormat::Toml => Some(Format::Toml),
+        ExchangeFormat::Yaml => Some(Format::Yaml),
+    }
+}
+
+fn encode(pool: &SubnetPool, format: ExchangeFormat) -> Vec<u8> {
+    let message = "Could not export pool";
+    let Some(format) = storage_format(format) else {
+        return subg::result(
+            postcard::to_extend(pool, Vec::new()),
+            exitcode::SOFTWARE,
+            message,
+        );
+    };
+    let mut content = Vec::new();
+    subg::result(
+        storage::write(&mut content, pool, format, false),
+        exitcode::SOFTWARE,
+        message,
+    );
+    content
+}
+
+fn decode(content: &[u8], format: ExchangeFormat) -> SubnetPool {
+    let message = "Could not import pool";
+    match storage_format(format) {
+        Some(format) => subg::result(storage::read(content, format), exitcode::DATAERR, message),
+        None => subg::result(postcard::from_bytes(content), exitcode::DATAERR, message),
+    }
+}
+
+pub(crate) fn convert(subg: &SubgArgs, args: &ConvertArgs) {
+    let pool = subg::load_pool_with_format(&args.input, args.input_format);
+    subg::store_pool_with_format(&args.output, &pool, args.output_format, subg.compact);
+}
+
+pub(crate) fn export(subg: &SubgArgs, args: &ExportArgs) {
+    let pool = subg.load_pool();
+    let content = encode(&pool, args.format);
+    if args.base64 {
+        println!("{}", STANDARD.encode(&content));
+        return;
+    }
+    subg::result(
+        stdout().write_all(&content),
+        exitcode::IOERR,
+        "Could not write pool",
+    );
+}
+
+pub(crate) fn import(subg: &SubgArgs, args: &ImportArgs) {
+    init::check_new_pool(subg, args.force);
+    let mut content = Vec::new();
+    subg::result(
+        stdin().read_to_end(&mut content),
+        exitcode::IOERR,
+        "Could not read pool",
+    );
+    if args.base64 {
+        let encoded = subg::result(
+            String::from_utf8(content),
+            exitcode::DATAERR,
+            "Could not import pool",
+        );
+        content = subg::result(
+            STANDARD.decode(encoded.split_whitespace().collect::<String>()),
+            exitcode::DATAERR,
+            "Could not import pool",
+        );
+    }
+    let pool = decode(&content, args.format);
+    subg.store_pool(&pool);
+}
@@ -0,0 +1,47 @@
+// Copyright 2024 The Milton Hirsch Institute, B.V.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::args::{
+    SpaceArgs, SpaceCommands, SpaceDeleteArgs, SpaceListArgs, SpaceNewArgs, SubgArgs,
+};
+use std::path::Path;
+use subnet_garden_core::pool;
+use subnet_garden_core::pool_set::PoolSet;
+
+fn new(subg: &SubgArgs, args: &SpaceNewArgs) {
+    let mut pool_set = match Path::new(&subg.pool_path).exists() {
+        true => subg::load_pool_set(&subg.pool_path),
+        false => PoolSet::new(),
+    };
+    subg::result(
+        pool_set.create(&args.name, pool::SubnetPool::new(args.cidr)),
+        exitcode::USAGE,
+        format!("Could not create space {}", args.name).as_str(),
+    );
+    subg::store_pool_set(&subg.pool_path, &pool_set, subg.compact);
+}
+
+fn delete(subg: &SubgArgs, args: &SpaceDeleteArgs) {
+    let mut pool_set = subg::load_pool_set(&subg.pool_path);
+    subg::result(
+        pool_set.remove(&args.name),
+        exitcode::USAGE,
+        format!("Could not delete space {}", args.name).as_str(),
+    );
+    subg::store_pool_set(&subg.pool_path, &pool_set, subg.compact);
+}
+
+fn list(subg: &SubgArgs, _args: &SpaceListArgs) {
+    let pool_set = subg::load_pool_set(&subg.pool_path);
+    for name in pool_set.names() {
+        println!("{name}");
+    }
+}
+
+pub(crate) fn space(subg: &SubgArgs, args: &SpaceArgs) {
+    match &args.command {
+        SpaceCommands::Delete(args) => delete(subg, args),
+        SpaceCommands::List(args) => list(subg, args),
+        SpaceCommands::New(args) => new(subg, args),
+    }
+}
@@ -3,43 +3,189 @@
 
 pub(crate) mod listing;
 
-use crate::args::{AllocateArgs, ClaimArgs, FreeArgs, RenameArgs, SubgArgs};
+use crate::args::{
+    AllocateArgs, ApplyArgs, ClaimArgs, FreeArgs, LockArgs, MaxAvailableArgs, OutputFormat,
+    PlanArgs, RenameArgs, RenumberArgs, SplitArgs, StatsArgs, SubgArgs, UnlockArgs, ValidateArgs,
+};
 use crate::param_str;
+use crate::util::progress::Progress;
 use cidr::IpCidr;
+use std::fmt;
+use std::fs;
+use std::path::Path;
 use std::process::exit;
+use subnet_garden_core::errors::AllocateError;
+use subnet_garden_core::pool::{AllocStrategy, SubnetPool};
+use subnet_garden_core::{util, AllocateResult, Bits, CidrRecord};
+
+/// Host bits of a subnet of `size`, which is a network prefix length when
+/// `prefix` is set and a number of host bits otherwise.
+fn host_bits(pool: &SubnetPool, size: Bits, prefix: bool) -> Bits {
+    if !prefix {
+        return size;
+    }
+    let max_bits = util::max_bits(pool.cidr());
+    let pool_prefix = pool.cidr().network_length();
+    if size < pool_prefix || size > max_bits {
+        eprintln!("Prefix length {size} is not between {pool_prefix} and {max_bits}");
+        exit(exitcode::USAGE);
+    }
+    max_bits - size
+}
+
+fn allocate_subnet(
+    pool: &mut SubnetPool,
+    bits: Bits,
+    name: Option<&str>,
+    args: &AllocateArgs,
+) -> AllocateResult<IpCidr> {
+    let strategy = match args.high {
+        true => AllocStrategy::HighFirst,
+        false => AllocStrategy::LowFirst,
+    };
+    match &args.within {
+        Some(parent) => pool.allocate_within(bits, name, parent),
+        None => pool.allocate_with(bits, name, strategy),
+    }
+}
+
+/// Claim `at` if it is free, otherwise allocate the next free subnet of the
+/// same size. Which of the two happened is told on stderr.
+fn allocate_preferred(
+    pool: &mut SubnetPool,
+    bits: Bits,
+    name: Option<&str>,
+    at: &IpCidr,
+) -> IpCidr {
+    if util::host_length(at) != bits {
+        eprintln!("Subnet {at} is not of the requested size");
+        exit(exitcode::USAGE);
+    }
+    match pool.claim(at, name) {
+        Ok(()) => {
+            eprintln!("Claimed {at}");
+            *at
+        }
+        Err(AllocateError::NoSpaceAvailable) => {
+            let cidr = subg::pool_result(pool.allocate(bits, name), "Could not allocate subnet");
+            eprintln!("Subnet {at} is not free, allocated {cidr}");
+            cidr
+        }
+        Err(err) => subg::pool_result(Err(err), "Could not allocate subnet"),
+    }
+}
+
+/// With --verbose, report the totals of the pool after a change.
+fn report_totals(subg: &SubgArgs, pool: &SubnetPool) {
+    if subg.verbose {
+        eprintln!(
+            "{} subnets allocated, {} addresses free",
+            pool.allocated_count(),
+            pool.free_addresses()
+        );
+    }
+}
 
 pub(crate) fn allocate(subg: &SubgArgs, args: &AllocateArgs) {
-    let mut pool = subg::load_pool(&subg.pool_path);
-    match &args.param {
-        None => {
-            subg::result(
-                pool.allocate(args.bits, args.name_format.as_deref()),
-                exitcode::SOFTWARE,
-                "Could not allocate subnet",
+    let mut pool = subg.load_pool();
+    let bits = host_bits(&pool, args.bits, args.prefix);
+    let free_blocks: Vec<IpCidr> = match subg.verbose {
+        true => pool.free_blocks().copied().collect(),
+        false => Vec::new(),
+    };
+    let (cidrs, progress) = match (args.count, &args.param) {
+        (Some(count), _) => {
+            let names: Vec<Option<String>> = (0..count)
+                .map(|index| args.auto_name.then(|| format!("subnet-{index}")))
+                .collect();
+            let names: Vec<Option<&str>> = names.iter().map(|name| name.as_deref()).collect();
+            let cidrs = subg::pool_result(
+                pool.allocate_many(bits, &names),
+                "Could not allocate subnets",
             );
+            let mut progress = Progress::new("Allocated", count);
+            progress.succeeded(cidrs.len());
+            (cidrs, Some(progress))
+        }
+        (None, None) => {
+            let name = args.name.as_deref().or(args.name_format.as_deref());
+            if let Some(name) = name {
+                if let Some(holder) = pool.contains_name_conflict(name) {
+                    eprintln!("Could not allocate subnet");
+                    eprintln!("Name {name} is already used by {holder}");
+                    exit(subg::CONFLICT);
+                }
+            }
+            let cidr = match &args.at {
+                Some(at) => allocate_preferred(&mut pool, bits, name, at),
+                None => subg::pool_result(
+                    allocate_subnet(&mut pool, bits, name, args),
+                    "Could not allocate subnet",
+                ),
+            };
+            (vec![cidr], None)
         }
-        Some(params) => {
+        (None, Some(params)) => {
             let format = args.name_format.as_deref().unwrap();
             let param_strs: param_str::format::Args = params.iter().map(|s| s.as_str()).collect();
             let names = subg::result(
                 param_str::format::format_strings(format, &param_strs),
-                exitcode::SOFTWARE,
+                exitcode::USAGE,
                 "Could not format subnet names",
             );
-            for name in names {
-                subg::result(
-                    pool.allocate(args.bits, Some(name.to_string().as_str())),
+            let mut progress = Progress::new("Allocated", names.len());
+            let cidrs = names
+                .iter()
+                .map(|name| {
+                    let cidr = subg::pool_result(
+                        allocate_subnet(&mut pool, bits, Some(name.to_string().as_str()), args),
+                        format!("Could not allocate subnet {name}").as_str(),
+                    );
+                    progress.succeeded(1);
+                    cidr
+                })
+                .collect();
+            (cidrs, Some(progress))
+        }
+    };
+    subg.store_pool(&pool);
+    for cidr in &cidrs {
+        if let Some(block) = free_blocks
+            .iter()
+            .find(|block| util::cidr_contains(block, cidr))
+        {
+            eprintln!("Allocated {cidr} from free block {block}");
+        }
+    }
+    if !subg.quiet {
+        match args.output {
+            OutputFormat::Text => {
+                for cidr in cidrs {
+                    println!("{cidr}");
+                }
+            }
+            OutputFormat::Json => {
+                let records: Vec<&CidrRecord> = cidrs
+                    .iter()
+                    .filter_map(|cidr| pool.records_within(cidr).next())
+                    .collect();
+                let json = subg::result(
+                    serde_json::to_string(&records),
                     exitcode::SOFTWARE,
-                    format!("Could not allocate subnet {name}").as_str(),
+                    "Could not write allocated subnets",
                 );
+                println!("{json}");
             }
         }
-    };
-    subg::store_pool(&subg.pool_path, &pool);
+    }
+    if let Some(progress) = progress {
+        progress.finish();
+    }
+    report_totals(subg, &pool);
 }
 
 pub(crate) fn free(subg: &SubgArgs, args: &FreeArgs) {
-    let mut pool = subg::load_pool(&subg.pool_path);
+    let mut pool = subg.load_pool();
     let identifier_list = match args.param {
         None => vec![args.identifier_format.clone()],
         Some(ref params) => {
@@ -47,7 +193,7 @@ pub(crate) fn free(subg: &SubgArgs, args: &FreeArgs) {
             let param_strs: param_str::format::Args = params.iter().map(|s| s.as_str()).collect();
             subg::result(
                 param_str::format::format_strings(format, &param_strs),
-                exitcode::SOFTWARE,
+                exitcode::USAGE,
                 "Could not format subnet names",
             )
         }
@@ -67,27 +213,269 @@ pub(crate) fn free(subg: &SubgArgs, args: &FreeArgs) {
                 )
             }
         };
-        if !pool.free(&cidr) && !args.ignore_missing {
-            eprintln!("Could not free subnet {cidr}");
-            exit(exitcode::SOFTWARE);
+        let result = match args.force {
+            true => pool.force_free(&cidr),
+            false => pool.free(&cidr),
+        };
+        match result {
+            Ok(()) => {}
+            Err(AllocateError::NoSuchObject | AllocateError::FamilyMismatch)
+                if args.ignore_missing => {}
+            Err(AllocateError::NoSuchObject) => {
+                eprintln!("Could not free subnet {cidr}");
+                exit(subg::NOT_FOUND);
+            }
+            Err(err) => {
+                subg::pool_result(Err(err), format!("Could not free subnet {cidr}").as_str())
+            }
+        }
+    }
+    subg.store_pool(&pool);
+    report_totals(subg, &pool);
+}
+
+fn already_claimed(pool: &SubnetPool, cidr: &IpCidr, name: Option<&str>) -> bool {
+    pool.contains(cidr)
+        && pool
+            .records_within(cidr)
+            .next()
+            .is_some_and(|record| record.name.as_deref() == name)
+}
+
+/// Claim every subnet listed in `path`, one `cidr [name]` per line. Blank
+/// lines and lines starting with `#` are ignored, as are subnets that are
+/// already claimed with the same name. Lines that cannot be claimed are
+/// reported together once the rest have been claimed.
+fn claim_from(pool: &mut SubnetPool, path: &Path) -> (Vec<IpCidr>, Vec<String>, Progress) {
+    let content = subg::result(
+        fs::read_to_string(path),
+        exitcode::NOINPUT,
+        format!("Could not read {}", path.display()).as_str(),
+    );
+    let lines: Vec<(usize, &str)> = content
+        .lines()
+        .map(str::trim)
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .collect();
+    let mut progress = Progress::new("Claimed", lines.len());
+    let mut claimed = Vec::new();
+    let mut errors = Vec::new();
+    for (index, line) in lines {
+        let (cidr, name) = match line.split_once(char::is_whitespace) {
+            Some((cidr, name)) => (cidr, Some(name.trim_start())),
+            None => (line, None),
+        };
+        let result = match cidr.parse::<IpCidr>() {
+            Ok(cidr) if already_claimed(pool, &cidr, name) => {
+                progress.skipped();
+                continue;
+            }
+            Ok(cidr) => pool
+                .claim(&cidr, name)
+                .map(|_| cidr)
+                .map_err(|err| err.to_string()),
+            Err(err) => Err(err.to_string()),
+        };
+        match result {
+            Ok(cidr) => {
+                claimed.push(cidr);
+                progress.succeeded(1);
+            }
+            Err(err) => {
+                errors.push(format!("Line {}: {line}: {err}", index + 1));
+                progress.failed();
+            }
         }
     }
-    subg::store_pool(&subg.pool_path, &pool);
+    (claimed, errors, progress)
 }
 
 pub(crate) fn claim(subg: &SubgArgs, args: &ClaimArgs) {
-    let mut pool = subg::load_pool(&subg.pool_path);
-    subg::result(
-        pool.claim(&args.cidr, args.name.as_deref()),
-        exitcode::SOFTWARE,
-        "Could not claim subnet",
+    let mut pool = subg.load_pool();
+    let (claimed, errors, progress) = match (&args.from, &args.cidr) {
+        (Some(path), _) => {
+            let (claimed, errors, progress) = claim_from(&mut pool, path);
+            (claimed, errors, Some(progress))
+        }
+        (None, Some(cidr)) => {
+            let result = pool.claim(cidr, args.name.as_deref());
+            if result == Err(AllocateError::NoSpaceAvailable) {
+                if let Some(other) = pool.overlapping(cidr) {
+                    eprintln!("Could not claim subnet");
+                    match pool.next_free(cidr) {
+                        Some(free) => eprintln!("{cidr} overlaps {}; try {free}", other.cidr),
+                        None => eprintln!("{cidr} overlaps {}", other.cidr),
+                    }
+                    exit(subg::CONFLICT);
+                }
+            }
+            subg::pool_result(result, "Could not claim subnet");
+            (vec![*cidr], vec![], None)
+        }
+        (None, None) => unreachable!("clap requires cidr unless from is present"),
+    };
+    subg.store_pool(&pool);
+    if !subg.quiet {
+        for cidr in claimed {
+            println!("{cidr}");
+        }
+    }
+    report_totals(subg, &pool);
+    if let Some(progress) = progress {
+        progress.finish();
+    }
+    if !errors.is_empty() {
+        for error in errors {
+            eprintln!("{error}");
+        }
+        exit(exitcode::DATAERR);
+    }
+}
+
+/// Print an action taken, unless --quiet.
+fn print_action(subg: &SubgArgs, action: fmt::Arguments) {
+    if !subg.quiet {
+        println!("{action}");
+    }
+}
+
+fn name_or_dash(name: Option<&str>) -> &str {
+    name.unwrap_or("-")
+}
+
+/// Make the pool hold the subnets of the desired pool file under the same
+/// names: claim missing subnets, rename subnets whose names differ and, with
+/// --prune, free subnets that are not desired. Each action is printed as it
+/// is taken. Nothing is stored if any action fails.
+pub(crate) fn apply(subg: &SubgArgs, args: &ApplyArgs) {
+    let mut pool = subg.load_pool();
+    let desired = subg::load_pool(&args.desired);
+    let diff = pool.diff(&desired);
+
+    if args.prune {
+        for record in &diff.removed {
+            subg::pool_result(
+                pool.free(&record.cidr),
+                format!("Could not free subnet {}", record.cidr).as_str(),
+            );
+            print_action(
+                subg,
+                format_args!(
+                    "free {} {}",
+                    record.cidr,
+                    name_or_dash(record.name.as_deref())
+                ),
+            );
+        }
+    }
+
+    // Names are cleared before any is given out again, so subnets can
+    // exchange names.
+    for (cidr, _, _) in &diff.renamed {
+        subg::pool_result(
+            pool.rename(cidr, None),
+            format!("Could not rename {cidr}").as_str(),
+        );
+    }
+    for (cidr, old, new) in &diff.renamed {
+        subg::pool_result(
+            pool.rename(cidr, new.as_deref()),
+            format!("Could not rename {cidr}").as_str(),
+        );
+        print_action(
+            subg,
+            format_args!(
+                "rename {cidr} {} -> {}",
+                name_or_dash(old.as_deref()),
+                name_or_dash(new.as_deref())
+            ),
+        );
+    }
+
+    for record in &diff.added {
+        subg::pool_result(
+            pool.claim_idempotent(&record.cidr, record.name.as_deref()),
+            format!("Could not claim {}", record.cidr).as_str(),
+        );
+        print_action(
+            subg,
+            format_args!(
+                "claim {} {}",
+                record.cidr,
+                name_or_dash(record.name.as_deref())
+            ),
+        );
+    }
+
+    if !args.dry_run {
+        subg.store_pool(&pool);
+        report_totals(subg, &pool);
+    }
+}
+
+/// Parse a subnet requirement given as `HOSTS` or `HOSTS:NAME`.
+pub(crate) fn parse_requirement(arg: &str) -> Result<(u128, Option<String>), String> {
+    let (hosts, name) = match arg.split_once(':') {
+        Some((hosts, name)) => (hosts, Some(name.to_string())),
+        None => (arg, None),
+    };
+    match hosts.parse::<u128>() {
+        Ok(hosts) => Ok((hosts, name)),
+        Err(_) => Err(format!("Invalid number of hosts: {hosts}")),
+    }
+}
+
+/// Read subnet requirements from `path`, one `hosts [name]` per line. Blank
+/// lines and lines starting with `#` are ignored.
+fn read_requirements(path: &Path) -> Vec<(u128, Option<String>)> {
+    let content = subg::result(
+        fs::read_to_string(path),
+        exitcode::NOINPUT,
+        format!("Could not read {}", path.display()).as_str(),
     );
-    subg::store_pool(&subg.pool_path, &pool);
+    content
+        .lines()
+        .map(str::trim)
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(index, line)| {
+            let requirement = match line.split_once(char::is_whitespace) {
+                Some((hosts, name)) => format!("{hosts}:{}", name.trim_start()),
+                None => line.to_string(),
+            };
+            parse_requirement(&requirement).unwrap_or_else(|err| {
+                eprintln!("Line {}: {line}: {err}", index + 1);
+                exit(exitcode::DATAERR);
+            })
+        })
+        .collect()
+}
+
+/// Allocate a subnet for each requirement with `plan_vlsm` and print the
+/// subnets in the order of the requirements.
+pub(crate) fn plan(subg: &SubgArgs, args: &PlanArgs) {
+    let mut pool = subg.load_pool();
+    let requirements = match &args.from {
+        Some(path) => read_requirements(path),
+        None => args.requirements.clone(),
+    };
+    let cidrs = subg::pool_result(pool.plan_vlsm(&requirements), "Could not plan subnets");
+    for (cidr, (_, name)) in cidrs.iter().zip(&requirements) {
+        print_action(
+            subg,
+            format_args!("{cidr} {}", name_or_dash(name.as_deref())),
+        );
+    }
+    if !args.dry_run {
+        subg.store_pool(&pool);
+        report_totals(subg, &pool);
+    }
 }
 
 pub(crate) fn rename(subg: &SubgArgs, args: &RenameArgs) {
-    let mut pool = subg::load_pool(&subg.pool_path);
-    let cidr = match pool.find_by_name(args.identifier.as_str()) {
+    let mut pool = subg.load_pool();
+    let cidr = match pool.cidr_for_name(args.identifier.as_str()) {
         Some(cidr) => cidr,
         None => subg::result(
             args.identifier.parse::<IpCidr>(),
@@ -95,16 +483,125 @@ pub(crate) fn rename(subg: &SubgArgs, args: &RenameArgs) {
             "Could not parse arg IDENTIFIER",
         ),
     };
-    subg::result(
-        pool.rename(&cidr, args.name.as_deref()),
-        exitcode::SOFTWARE,
-        "Could not rename subnet",
+    if let Some(name) = &args.name {
+        if let Some(holder) = pool.contains_name_conflict(name) {
+            if holder != cidr {
+                eprintln!("Could not rename subnet");
+                eprintln!("Name {name} is already used by {holder}");
+                exit(subg::CONFLICT);
+            }
+        }
+    }
+    let result = match args.force {
+        true => pool.force_rename(&cidr, args.name.as_deref()),
+        false => pool.rename(&cidr, args.name.as_deref()),
+    };
+    subg::pool_result(result, "Could not rename subnet");
+    subg.store_pool(&pool);
+}
+
+pub(crate) fn lock(subg: &SubgArgs, args: &LockArgs) {
+    set_locked(subg, &args.identifier, true);
+}
+
+pub(crate) fn unlock(subg: &SubgArgs, args: &UnlockArgs) {
+    set_locked(subg, &args.identifier, false);
+}
+
+fn set_locked(subg: &SubgArgs, identifier: &str, locked: bool) {
+    let mut pool = subg.load_pool();
+    let cidr = match pool.cidr_for_name(identifier) {
+        Some(cidr) => cidr,
+        None => subg::result(
+            identifier.parse::<IpCidr>(),
+            exitcode::USAGE,
+            "Could not parse arg IDENTIFIER",
+        ),
+    };
+    let action = match locked {
+        true => "lock",
+        false => "unlock",
+    };
+    subg::pool_result(
+        pool.set_locked(&cidr, locked),
+        format!("Could not {action} subnet {cidr}").as_str(),
     );
-    subg::store_pool(&subg.pool_path, &pool);
+    subg.store_pool(&pool);
+}
+
+pub(crate) fn renumber(subg: &SubgArgs, args: &RenumberArgs) {
+    let pool = subg.load_pool();
+    let renumbered = subg::pool_result(pool.renumber(args.cidr), "Could not renumber pool");
+    subg.store_pool(&renumbered);
+}
+
+pub(crate) fn split(subg: &SubgArgs, args: &SplitArgs) {
+    let mut pool = subg.load_pool();
+    let children = subg::pool_result(pool.split(&args.cidr, args.bits), "Could not split subnet");
+    if let Some(format) = &args.name_format {
+        let range = format!("%0-{}", children.len() - 1);
+        let names = subg::result(
+            param_str::format::format_strings(format, &vec![range.as_str()]),
+            exitcode::USAGE,
+            "Could not format subnet names",
+        );
+        for (child, name) in children.iter().zip(names) {
+            subg::pool_result(
+                pool.rename(child, Some(name.as_str())),
+                format!("Could not name subnet {child}").as_str(),
+            );
+        }
+    }
+    subg.store_pool(&pool);
 }
 
-pub(crate) fn max_bits(subg: &SubgArgs) {
-    let pool = subg::load_pool(&subg.pool_path);
+pub(crate) fn max_bits(subg: &SubgArgs, args: &MaxAvailableArgs) {
+    let pool = subg.load_pool();
     let largest = pool.max_available_bits();
-    println!("{largest}");
+    let max_bits = util::max_bits(pool.cidr());
+    let size = |bits: Bits| match args.prefix {
+        true => max_bits - bits,
+        false => bits,
+    };
+    if args.all {
+        for bits in (0..=largest).rev() {
+            println!("{}: {}", size(bits), pool.capacity_for(bits));
+        }
+    } else {
+        println!("{}", size(largest));
+    }
+}
+
+pub(crate) fn stats(subg: &SubgArgs, args: &StatsArgs) {
+    let pool = subg.load_pool();
+    let summary = pool.summary();
+    match args.output {
+        OutputFormat::Text => {
+            println!("cidr = {}", summary.cidr);
+            println!("allocated_count = {}", summary.allocated_count);
+            println!("named_count = {}", summary.named_count);
+            println!("utilization = {}", summary.utilization);
+            println!("max_available_bits = {}", summary.max_available_bits);
+            println!("free_block_count = {}", summary.free_block_count);
+        }
+        OutputFormat::Json => {
+            let json = subg::result(
+                serde_json::to_string_pretty(&summary),
+                exitcode::SOFTWARE,
+                "Could not write summary",
+            );
+            println!("{json}");
+        }
+    }
+}
+
+pub(crate) fn validate(subg: &SubgArgs, _args: &ValidateArgs) {
+    let pool = subg.load_pool();
+    if let Err(errors) = pool.validate() {
+        eprintln!("Pool is inconsistent");
+        for error in errors {
+            eprintln!("{error}");
+        }
+        exit(exitcode::DATAERR);
+    }
 }
@@ -1,14 +1,30 @@
 // Copyright 2024 The Milton Hirsch Institute, B.V.
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::args::{CidrsArgs, NamesArgs, SubgArgs};
+use chrono::DateTime;
+use cidr::IpCidr;
+use regex::RegexBuilder;
+use subnet_garden_core::format_str;
+use subnet_garden_core::util as core_util;
+
+use crate::args::{CidrsArgs, GrepArgs, NamesArgs, RenderArgs, SubgArgs, TreeArgs};
 use crate::util;
+use crate::util::color::Style;
+
+/// Print a subnet in the porcelain format of the listings: the CIDR and
+/// the name, or `-` when unnamed, separated by a tab. Scripts rely on this
+/// format, so it must not change.
+fn print_porcelain(cidr: &IpCidr, name: Option<&str>) {
+    println!("{cidr}\t{}", name.unwrap_or("-"));
+}
 
 pub(crate) fn cidrs(subg: &SubgArgs, args: &CidrsArgs) {
-    let pool = subg::load_pool(&subg.pool_path);
+    let pool = subg.load_pool();
+    let painter = subg.painter();
 
-    if args.long {
-        println!("total {}", pool.allocated_count());
+    if args.long && !args.no_header {
+        let total = format!("total {}", pool.allocated_count());
+        println!("{}", painter.paint(&total, Style::Total));
     }
 
     let start_cidr = match args.within {
@@ -16,20 +32,75 @@ pub(crate) fn cidrs(subg: &SubgArgs, args: &CidrsArgs) {
         None => *pool.cidr(),
     };
 
+    let filtered = args.older_than.is_some() || args.newer_than.is_some();
+    let mut records: Vec<_> = pool
+        .records_within(&start_cidr)
+        .filter(|record| match record.name {
+            Some(_) => !args.only_unnamed,
+            None => !args.only_named,
+        })
+        .filter(|record| {
+            if !filtered {
+                return true;
+            }
+            let created_at = record
+                .created_at
+                .as_deref()
+                .and_then(|time| DateTime::parse_from_rfc3339(time).ok());
+            match created_at {
+                Some(created_at) => {
+                    args.older_than.map_or(true, |time| created_at < time)
+                        && args.newer_than.map_or(true, |time| created_at > time)
+                }
+                None => args.include_untimed,
+            }
+        })
+        .collect();
+    if args.reverse {
+        records.reverse();
+    }
+
+    if args.porcelain {
+        for record in records {
+            print_porcelain(&record.cidr, record.name.as_deref());
+        }
+        return;
+    }
+
     let max_cidr_width = match args.long {
-        true => pool
-            .records_within(&start_cidr)
-            .map(|r| r.cidr.to_string().len())
+        true => records
+            .iter()
+            .map(|r| util::display_width(&r.cidr.to_string()))
             .max()
             .unwrap_or(0),
         false => 0,
     };
-    for entry in pool.records_within(&start_cidr) {
+    let max_name_width = match args.show_hosts {
+        true => records
+            .iter()
+            .map(|r| util::display_width(r.name.as_deref().unwrap_or("-")))
+            .max()
+            .unwrap_or(0),
+        false => 0,
+    };
+    for entry in records {
         let mut cidr = entry.cidr.to_string();
         if args.long {
             util::right_pad(&mut cidr, max_cidr_width);
-            let name = entry.name.clone().unwrap_or("-".to_string());
-            println!("{cidr}  {name}");
+            let cidr = painter.paint(&cidr, Style::Cidr);
+            let mut name = entry.name.clone().unwrap_or("-".to_string());
+            if args.show_hosts {
+                util::right_pad(&mut name, max_name_width);
+            }
+            if entry.name.is_none() {
+                name = painter.paint(&name, Style::Unnamed);
+            }
+            if args.show_hosts {
+                let hosts = core_util::usable_hosts(&entry.cidr);
+                println!("{cidr}  {name}  {hosts}");
+            } else {
+                println!("{cidr}  {name}");
+            }
         } else {
             println!("{cidr}");
         }
@@ -37,27 +108,94 @@ pub(crate) fn cidrs(subg: &SubgArgs, args: &CidrsArgs) {
 }
 
 pub(crate) fn names(subg: &SubgArgs, args: &NamesArgs) {
-    let pool = subg::load_pool(&subg.pool_path);
+    let pool = subg.load_pool();
+    let painter = subg.painter();
 
-    if args.long {
-        println!("total {} of {}", pool.named_count(), pool.allocated_count());
+    if args.long && !args.no_header {
+        let total = format!("total {} of {}", pool.named_count(), pool.allocated_count());
+        println!("{}", painter.paint(&total, Style::Total));
+    }
+
+    let mut names = pool.names_with_cidrs();
+    if args.reverse {
+        names.reverse();
+    }
+    if args.porcelain {
+        for (name, cidr) in names {
+            print_porcelain(&cidr, Some(&name));
+        }
+        return;
     }
 
     let max_name_width = match args.long {
-        true => pool.names().map(|n| n.len()).max().unwrap_or(0),
+        true => names
+            .iter()
+            .map(|(name, _)| util::display_width(name))
+            .max()
+            .unwrap_or(0),
         false => 0,
     };
 
-    let mut names: Vec<String> = pool.names().collect();
-    names.sort();
-    for mut name in names {
+    for (mut name, cidr) in names {
         if args.long {
-            let cidr = pool.find_by_name(&name).unwrap();
-            let cidr_string = cidr.to_string();
             util::right_pad(&mut name, max_name_width);
-            println!("{name}  {cidr_string}");
+            println!("{name}  {}", painter.paint(&cidr.to_string(), Style::Cidr));
         } else {
             println!("{name}");
         }
     }
 }
+
+pub(crate) fn grep(subg: &SubgArgs, args: &GrepArgs) {
+    let regex = subg::result(
+        RegexBuilder::new(&args.pattern)
+            .case_insensitive(args.ignore_case)
+            .build(),
+        exitcode::USAGE,
+        format!("Invalid regular expression: {}", args.pattern).as_str(),
+    );
+    let pool = subg.load_pool();
+
+    let records: Vec<_> = pool
+        .records()
+        .filter(|record| match &record.name {
+            Some(name) => regex.is_match(name) != args.invert,
+            None => false,
+        })
+        .collect();
+
+    let max_cidr_width = records
+        .iter()
+        .map(|r| util::display_width(&r.cidr.to_string()))
+        .max()
+        .unwrap_or(0);
+    for record in records {
+        let mut cidr = record.cidr.to_string();
+        util::right_pad(&mut cidr, max_cidr_width);
+        let name = record.name.as_deref().unwrap_or_default();
+        println!("{cidr}  {name}");
+    }
+}
+
+pub(crate) fn render(subg: &SubgArgs, args: &RenderArgs) {
+    let template = subg::result(
+        format_str::parse(&args.template),
+        exitcode::USAGE,
+        "Could not parse template",
+    );
+    let pool = subg.load_pool();
+
+    for record in pool.records() {
+        let rendered = subg::result(
+            format_str::format(&template, &format_str::record_fields(record)),
+            exitcode::USAGE,
+            "Could not render template",
+        );
+        println!("{rendered}");
+    }
+}
+
+pub(crate) fn tree(subg: &SubgArgs, _args: &TreeArgs) {
+    let pool = subg.load_pool();
+    print!("{}", pool.pretty_tree_string());
+}
@@ -0,0 +1,76 @@
+// Copyright 2024 The Milton Hirsch Institute, B.V.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::args::{SubgArgs, WatchArgs, WatchCommands};
+use crate::subcommands::subnet::listing;
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use std::ffi::OsStr;
+use std::path::Path;
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// Changes arriving within this time of each other are rendered once, so a
+/// pool file written in several steps is not listed half-written.
+const SETTLE_TIME: Duration = Duration::from_millis(100);
+
+fn render(subg: &SubgArgs, args: &WatchArgs) {
+    match &args.command {
+        WatchCommands::Cidrs(args) => listing::cidrs(subg, args),
+        WatchCommands::Names(args) => listing::names(subg, args),
+    }
+}
+
+fn changes_file(event: &notify::Result<Event>, file_name: &OsStr) -> bool {
+    match event {
+        Ok(event) => {
+            matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_))
+                && event
+                    .paths
+                    .iter()
+                    .any(|path| path.file_name() == Some(file_name))
+        }
+        Err(_) => false,
+    }
+}
+
+/// Print the listing, then print it again each time the pool file changes,
+/// until interrupted.
+///
+/// The directory holding the pool file is watched rather than the file, so
+/// the watch carries on when the file is atomically replaced by a rename.
+pub(crate) fn watch(subg: &SubgArgs, args: &WatchArgs) {
+    let pool_path = Path::new(&subg.pool_path);
+    let file_name = match pool_path.file_name() {
+        Some(file_name) => file_name,
+        None => {
+            eprintln!("Pool path is not a file: {}", pool_path.display());
+            std::process::exit(exitcode::USAGE);
+        }
+    };
+    let directory = match pool_path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = subg::result(
+        notify::recommended_watcher(sender),
+        exitcode::OSERR,
+        "Could not watch pool file",
+    );
+    subg::result(
+        watcher.watch(directory, RecursiveMode::NonRecursive),
+        exitcode::OSERR,
+        format!("Could not watch {}", directory.display()).as_str(),
+    );
+
+    render(subg, args);
+    while let Ok(event) = receiver.recv() {
+        if !changes_file(&event, file_name) {
+            continue;
+        }
+        while receiver.recv_timeout(SETTLE_TIME).is_ok() {}
+        println!();
+        render(subg, args);
+    }
+}
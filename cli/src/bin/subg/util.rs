@@ -1,11 +1,20 @@
 // Copyright 2024 The Milton Hirsch Institute, B.V.
 // SPDX-License-Identifier: Apache-2.0
 
+pub(crate) mod color;
 pub(crate) mod iter;
+pub(crate) mod progress;
 pub(crate) mod state_machine;
+pub(crate) mod time;
+
+use unicode_width::UnicodeWidthStr;
+
+pub(crate) fn display_width(s: &str) -> usize {
+    s.width()
+}
 
 pub(crate) fn right_pad(s: &mut String, width: usize) {
-    while s.len() < width {
+    for _ in display_width(s)..width {
         s.push(' ');
     }
 }
@@ -21,6 +30,23 @@ mod tests {
         assert_eq!(s, "foo  ");
     }
 
+    #[test]
+    fn test_right_pad_multibyte() {
+        let mut s = String::from("café");
+        right_pad(&mut s, 6);
+        assert_eq!(s, "café  ");
+    }
+
+    #[test]
+    fn test_right_pad_wide_characters() {
+        let mut ascii = String::from("lab");
+        let mut cjk = String::from("实验");
+        right_pad(&mut ascii, 8);
+        right_pad(&mut cjk, 8);
+        assert_eq!(cjk, "实验    ");
+        assert_eq!(display_width(&ascii), display_width(&cjk));
+    }
+
     #[test]
     fn test_right_pad_too_wide() {
         let mut s = String::from("foo-bar");
@@ -0,0 +1,67 @@
+// Copyright 2024 The Milton Hirsch Institute, B.V.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::io::{stdout, IsTerminal};
+
+use crate::args::ColorChoice;
+
+const RESET: &str = "\x1b[0m";
+
+/// How a part of a listing is highlighted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Style {
+    Cidr,
+    Unnamed,
+    Total,
+}
+
+impl Style {
+    fn code(self) -> &'static str {
+        match self {
+            Style::Cidr => "\x1b[36m",
+            Style::Unnamed => "\x1b[2m",
+            Style::Total => "\x1b[1m",
+        }
+    }
+}
+
+/// Highlights listing output, or leaves it as is when color is off.
+pub(crate) struct Painter {
+    enabled: bool,
+}
+
+impl Painter {
+    /// Color is used when asked for, or by default when stdout is a terminal.
+    pub(crate) fn new(choice: ColorChoice) -> Self {
+        let enabled = match choice {
+            ColorChoice::Auto => stdout().is_terminal(),
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        };
+        Painter { enabled }
+    }
+
+    pub(crate) fn paint(&self, text: &str, style: Style) -> String {
+        match self.enabled {
+            true => format!("{}{text}{RESET}", style.code()),
+            false => text.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paint() {
+        assert_eq!(
+            Painter::new(ColorChoice::Always).paint("10.0.0.0/8", Style::Cidr),
+            "\x1b[36m10.0.0.0/8\x1b[0m"
+        );
+        assert_eq!(
+            Painter::new(ColorChoice::Never).paint("10.0.0.0/8", Style::Cidr),
+            "10.0.0.0/8"
+        );
+    }
+}
@@ -0,0 +1,103 @@
+// Copyright 2024 The Milton Hirsch Institute, B.V.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::io::{stderr, IsTerminal};
+
+/// Number of subnets between progress updates.
+const PROGRESS_INTERVAL: usize = 100;
+
+/// Counts the outcome of a bulk operation, reporting progress and a summary
+/// on stderr so that stdout is left to the subnets themselves.
+pub(crate) struct Progress {
+    verb: &'static str,
+    total: usize,
+    succeeded: usize,
+    skipped: usize,
+    failed: usize,
+    show_progress: bool,
+}
+
+impl Progress {
+    /// Track `total` subnets, summarized as `{verb} N subnets`. Progress is
+    /// only shown when stderr is a terminal.
+    pub(crate) fn new(verb: &'static str, total: usize) -> Self {
+        Progress {
+            verb,
+            total,
+            succeeded: 0,
+            skipped: 0,
+            failed: 0,
+            show_progress: stderr().is_terminal(),
+        }
+    }
+
+    pub(crate) fn succeeded(&mut self, count: usize) {
+        self.succeeded += count;
+        self.update();
+    }
+
+    pub(crate) fn skipped(&mut self) {
+        self.skipped += 1;
+        self.update();
+    }
+
+    pub(crate) fn failed(&mut self) {
+        self.failed += 1;
+        self.update();
+    }
+
+    fn processed(&self) -> usize {
+        self.succeeded + self.skipped + self.failed
+    }
+
+    fn update(&self) {
+        if self.show_progress && self.processed() % PROGRESS_INTERVAL == 0 {
+            eprint!("\r{} of {}", self.processed(), self.total);
+        }
+    }
+
+    pub(crate) fn summary(&self) -> String {
+        let noun = match self.succeeded {
+            1 => "subnet",
+            _ => "subnets",
+        };
+        format!(
+            "{} {} {noun} ({} skipped, {} failed)",
+            self.verb, self.succeeded, self.skipped, self.failed
+        )
+    }
+
+    /// Replace the progress counter with the summary.
+    pub(crate) fn finish(&self) {
+        if self.show_progress && self.processed() >= PROGRESS_INTERVAL {
+            eprint!("\r\x1b[K");
+        }
+        eprintln!("{}", self.summary());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summary() {
+        let mut progress = Progress::new("Allocated", 4);
+        assert_eq!(
+            progress.summary(),
+            "Allocated 0 subnets (0 skipped, 0 failed)"
+        );
+        progress.succeeded(1);
+        assert_eq!(
+            progress.summary(),
+            "Allocated 1 subnet (0 skipped, 0 failed)"
+        );
+        progress.succeeded(1);
+        progress.skipped();
+        progress.failed();
+        assert_eq!(
+            progress.summary(),
+            "Allocated 2 subnets (1 skipped, 1 failed)"
+        );
+    }
+}
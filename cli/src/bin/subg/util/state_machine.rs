@@ -3,8 +3,10 @@
 
 use std::slice::Iter;
 
-#[derive(Debug, PartialEq)]
+/// States compare by name, as function pointers have no stable identity.
+#[derive(Debug)]
 pub(crate) struct State<B, L, E> {
+    name: &'static str,
     transition: Transition<B, L, E>,
 }
 
@@ -28,8 +30,17 @@ impl<B, L, E> Clone for State<B, L, E> {
 
 impl<B, L, E> Copy for State<B, L, E> {}
 
-pub(crate) const fn state<B, L, E>(transition: Transition<B, L, E>) -> State<B, L, E> {
-    State { transition }
+impl<B, L, E> PartialEq for State<B, L, E> {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+pub(crate) const fn state<B, L, E>(
+    name: &'static str,
+    transition: Transition<B, L, E>,
+) -> State<B, L, E> {
+    State { name, transition }
 }
 
 pub(crate) struct StateMachine<B, L, E> {
@@ -0,0 +1,88 @@
+// Copyright 2024 The Milton Hirsch Institute, B.V.
+// SPDX-License-Identifier: Apache-2.0
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+
+fn parse_duration(text: &str) -> Option<Duration> {
+    let split = text.len().checked_sub(1)?;
+    if !text.is_char_boundary(split) {
+        return None;
+    }
+    let (count, unit) = text.split_at(split);
+    let count = count.parse::<u64>().ok()?;
+    let unit_seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return None,
+    };
+    let seconds = count.checked_mul(unit_seconds)?;
+    Duration::from_std(std::time::Duration::from_secs(seconds)).ok()
+}
+
+/// Parse a point in time given as an age relative to now (`30d`), a date
+/// (`2024-01-01`, midnight UTC) or an RFC 3339 time.
+pub(crate) fn parse_time(text: &str) -> Result<DateTime<Utc>, String> {
+    let time = if let Some(duration) = parse_duration(text) {
+        Utc::now().checked_sub_signed(duration)
+    } else if let Ok(date) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        date.and_hms_opt(0, 0, 0).map(|time| time.and_utc())
+    } else {
+        DateTime::parse_from_rfc3339(text)
+            .ok()
+            .map(|time| time.with_timezone(&Utc))
+    };
+    time.ok_or_else(|| format!("Expected age (e.g. 30d), date or RFC 3339 time, found {text}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duration() {
+        let before = Utc::now() - Duration::days(30);
+        let parsed = parse_time("30d").unwrap();
+        let after = Utc::now() - Duration::days(30);
+        assert!(before <= parsed && parsed <= after);
+    }
+
+    #[test]
+    fn date() {
+        assert_eq!(
+            parse_time("2024-01-02").unwrap().to_rfc3339(),
+            "2024-01-02T00:00:00+00:00"
+        );
+    }
+
+    #[test]
+    fn rfc3339() {
+        assert_eq!(
+            parse_time("2024-01-02T03:04:05+01:00")
+                .unwrap()
+                .to_rfc3339(),
+            "2024-01-02T02:04:05+00:00"
+        );
+    }
+
+    #[test]
+    fn unknown_unit() {
+        assert_eq!(
+            parse_time("30y"),
+            Err("Expected age (e.g. 30d), date or RFC 3339 time, found 30y".to_string())
+        );
+    }
+
+    #[test]
+    fn overflow() {
+        assert!(parse_time("99999999999999999w").is_err());
+        assert!(parse_time("100000000w").is_err());
+    }
+
+    #[test]
+    fn multibyte() {
+        assert!(parse_time("3é").is_err());
+    }
+}
@@ -2,18 +2,33 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use exitcode::ExitCode;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 use std::error::Error;
-use std::fmt;
-use std::fmt::{Display, Formatter};
+use std::fs;
 use std::fs::File;
 use std::path::Path;
 use std::process::exit;
+use subnet_garden_core::errors::{AllocateError, RenameError};
 use subnet_garden_core::pool;
+use subnet_garden_core::pool::LenientPool;
+use subnet_garden_core::pool_set::{LenientPoolSet, PoolSet};
+use subnet_garden_core::storage::{self, Format};
 
 pub const DEFAULT_STORAGE_PATH: &str = "subnet-garden-pool.yaml";
 
 pub const SUBG_COMMAND: &str = "subg";
 
+/// Exit code when the subnet acted on is not allocated. No other failure
+/// exits with it, so it is never confused with a missing pool file.
+pub const NOT_FOUND: ExitCode = 3;
+
+/// Exit code when an action conflicts with the pool: the name is taken, the
+/// subnet is locked or overlaps an allocated one, or there is no room left.
+/// No other failure exits with it, so it is never confused with a pool file
+/// that cannot be written.
+pub const CONFLICT: ExitCode = 4;
+
 fn show_error(err: impl Error, message: &str, exit_code: ExitCode) -> ! {
     eprintln!("{message}");
     eprintln!("{err}");
@@ -32,52 +47,71 @@ where
     }
 }
 
-#[derive(Debug)]
-enum PoolFormat {
-    Json,
-    Yaml,
+/// Class of a failed pool operation, deciding the exit code it causes.
+pub trait FailureClass {
+    fn exit_code(&self) -> ExitCode;
+}
+
+impl FailureClass for AllocateError {
+    fn exit_code(&self) -> ExitCode {
+        match self {
+            AllocateError::NoSuchObject => NOT_FOUND,
+            AllocateError::DuplicateName
+            | AllocateError::Locked
+            | AllocateError::NoSpaceAvailable => CONFLICT,
+            AllocateError::FamilyMismatch
+            | AllocateError::NotSiblings
+            | AllocateError::PrefixTooLong
+            | AllocateError::PrefixTooShort
+            | AllocateError::SizeMismatch => exitcode::USAGE,
+        }
+    }
 }
 
-impl Display for PoolFormat {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        let s = format!("{self:?}");
-        write!(f, "{}", s.to_lowercase())
+impl FailureClass for RenameError {
+    fn exit_code(&self) -> ExitCode {
+        match self {
+            RenameError::NoSuchObject => NOT_FOUND,
+            RenameError::DuplicateName | RenameError::Locked => CONFLICT,
+            RenameError::FamilyMismatch => exitcode::USAGE,
+        }
+    }
+}
+
+/// Like `result`, exiting with the code of the class of the failure.
+pub fn pool_result<T, E>(result: Result<T, E>, message: &str) -> T
+where
+    E: Error + FailureClass,
+{
+    match result {
+        Ok(value) => value,
+        Err(err) => {
+            let exit_code = err.exit_code();
+            show_error(err, message, exit_code);
+        }
     }
 }
 
-fn parse_pool_path(pool_path: &str) -> (&Path, crate::PoolFormat) {
+/// Format of the pool file according to its extension, or the reason it
+/// cannot be told from the extension.
+fn parse_pool_path(pool_path: &str) -> (&Path, Result<Format, String>) {
     let path = Path::new(pool_path);
     let format = match path
         .extension()
         .map(|v| v.to_str().expect("str because path created from str"))
     {
-        Some("json") => PoolFormat::Json,
-        Some("yaml" | "yml") => PoolFormat::Yaml,
         Some(ext) => {
-            eprintln!("Unknown pool file extension: {ext}");
-            exit(exitcode::USAGE);
-        }
-        None => {
-            eprintln!("Pool file has no extension: {}", path.display());
-            exit(exitcode::USAGE);
+            Format::try_from(ext).map_err(|_| format!("Unknown pool file extension: {ext}"))
         }
+        None => Err(format!("Pool file has no extension: {}", path.display())),
     };
     (path, format)
 }
 
-pub fn load_pool(pool_path: &str) -> pool::SubnetPool {
-    fn from_reader<'a, E: Error>(
-        reader: &'a File,
-        from_reader: fn(&'a File) -> Result<pool::SubnetPool, E>,
-    ) -> pool::SubnetPool {
-        result(
-            from_reader(reader),
-            exitcode::DATAERR,
-            "Unable to load subnet pool file",
-        )
-    }
-
-    let (path, pool_format) = parse_pool_path(pool_path);
+/// Read and parse the pool file at `pool_path`, exiting on failure. The
+/// format is `pool_format` if given, otherwise told by the file.
+fn read_pool_file<T: DeserializeOwned>(pool_path: &str, pool_format: Option<Format>) -> T {
+    let (path, path_format) = parse_pool_path(pool_path);
     if !path.exists() {
         eprintln!("Subnet pool file does not exist at {}", path.display());
         exit(exitcode::NOINPUT);
@@ -86,28 +120,150 @@ pub fn load_pool(pool_path: &str) -> pool::SubnetPool {
         eprintln!("Path is not a file at {}", path.display());
         exit(exitcode::NOINPUT);
     }
-    let pool_file = File::open(path).unwrap();
+    let content = result(
+        fs::read(path),
+        exitcode::NOINPUT,
+        &format!("Could not read pool file at {}", path.display()),
+    );
+
+    let pool_format = pool_format
+        .or(path_format.ok())
+        .unwrap_or_else(|| Format::sniff(&content));
+    result(
+        storage::read(content.as_slice(), pool_format),
+        exitcode::DATAERR,
+        "Unable to load subnet pool file",
+    )
+}
 
-    match pool_format {
-        PoolFormat::Json => from_reader(&pool_file, serde_json::from_reader),
-        PoolFormat::Yaml => from_reader(&pool_file, serde_yaml::from_reader),
+pub fn load_pool(pool_path: &str) -> pool::SubnetPool {
+    read_pool_file(pool_path, None)
+}
+
+/// Load a pool as `load_pool` does, reading it in `pool_format` when given
+/// instead of the format told by the file.
+pub fn load_pool_with_format(pool_path: &str, pool_format: Option<Format>) -> pool::SubnetPool {
+    read_pool_file(pool_path, pool_format)
+}
+
+/// Load a file holding several named pools.
+pub fn load_pool_set(pool_path: &str) -> PoolSet {
+    read_pool_file(pool_path, None)
+}
+
+/// Load the pool named `pool_name` from a pool set file, or the single
+/// pool in the file when no name is given.
+pub fn load_pool_named(pool_path: &str, pool_name: Option<&str>) -> pool::SubnetPool {
+    let Some(pool_name) = pool_name else {
+        return load_pool(pool_path);
+    };
+    match load_pool_set(pool_path).remove(pool_name) {
+        Ok(pool) => pool,
+        Err(_) => {
+            eprintln!("No pool named {pool_name} in {pool_path}");
+            exit(exitcode::DATAERR);
+        }
     }
 }
 
-pub fn store_pool(pool_path: &str, pool: &pool::SubnetPool) {
-    fn to_writer<'a, E: Error>(
-        writer: &'a File,
-        to_writer: fn(&'a File, &pool::SubnetPool) -> Result<(), E>,
-        subnet_pool: &pool::SubnetPool,
-    ) {
-        result(
-            to_writer(writer, subnet_pool),
-            exitcode::CANTCREAT,
-            "Could not store pool file",
+/// Load a pool as `load_pool_named` does, skipping subnets that overlap an
+/// earlier subnet of the file instead of failing. Each skipped subnet is
+/// reported on stderr.
+pub fn load_pool_lenient(pool_path: &str, pool_name: Option<&str>) -> pool::SubnetPool {
+    let loaded: LenientPool = match pool_name {
+        None => read_pool_file(pool_path, None),
+        Some(pool_name) => {
+            let mut pool_set: LenientPoolSet = read_pool_file(pool_path, None);
+            match pool_set.pools.remove(pool_name) {
+                Some(loaded) => loaded,
+                None => {
+                    eprintln!("No pool named {pool_name} in {pool_path}");
+                    exit(exitcode::DATAERR);
+                }
+            }
+        }
+    };
+    for record in &loaded.skipped {
+        eprintln!(
+            "Skipped subnet {} overlapping an earlier subnet",
+            record.cidr
         );
     }
+    loaded.pool
+}
+
+/// Write the pool to `pool_path`. The same pool always produces the same
+/// bytes: fields are written in a fixed order and subnets are sorted by
+/// address.
+///
+/// A file whose extension does not name a format keeps the format of its
+/// current content. A new file must have a known extension.
+pub fn store_pool(pool_path: &str, pool: &pool::SubnetPool) {
+    store_pool_as(pool_path, pool, false);
+}
 
-    let (path, pool_format) = parse_pool_path(pool_path);
+/// Write the pool as `store_pool` does, writing JSON on a single line
+/// instead of pretty printed when `compact` is set.
+pub fn store_pool_as(pool_path: &str, pool: &pool::SubnetPool, compact: bool) {
+    write_pool_file(pool_path, pool, None, compact);
+}
+
+/// Write the pool as `store_pool_as` does, in `pool_format` when given
+/// instead of the format told by the file.
+pub fn store_pool_with_format(
+    pool_path: &str,
+    pool: &pool::SubnetPool,
+    pool_format: Option<Format>,
+    compact: bool,
+) {
+    write_pool_file(pool_path, pool, pool_format, compact);
+}
+
+/// Write a file holding several named pools, as `store_pool_as` does.
+pub fn store_pool_set(pool_path: &str, pool_set: &PoolSet, compact: bool) {
+    write_pool_file(pool_path, pool_set, None, compact);
+}
+
+/// Write the pool under `pool_name` in the pool set file at `pool_path`,
+/// leaving the other pools in the file as they are. The file is created if
+/// it does not exist yet. Without a name the file holds just the pool.
+pub fn store_pool_named(
+    pool_path: &str,
+    pool_name: Option<&str>,
+    pool: &pool::SubnetPool,
+    compact: bool,
+) {
+    let Some(pool_name) = pool_name else {
+        return store_pool_as(pool_path, pool, compact);
+    };
+    let mut pool_set = match Path::new(pool_path).exists() {
+        true => load_pool_set(pool_path),
+        false => PoolSet::new(),
+    };
+    pool_set.replace(pool_name, pool.clone());
+    store_pool_set(pool_path, &pool_set, compact);
+}
+
+fn write_pool_file<T: Serialize>(
+    pool_path: &str,
+    value: &T,
+    pool_format: Option<Format>,
+    compact: bool,
+) {
+    let (path, path_format) = parse_pool_path(pool_path);
+    let content = fs::read(path).ok();
+    let pool_format = match pool_format {
+        Some(pool_format) => Ok(pool_format),
+        None => path_format,
+    };
+    let pool_format = match (pool_format, &content) {
+        (Ok(pool_format), _) => pool_format,
+        (Err(_), Some(content)) => Format::sniff(content),
+        (Err(message), None) => {
+            eprintln!("{message}");
+            exit(exitcode::USAGE);
+        }
+    };
 
     let pool_file = result(
         File::create(path),
@@ -115,8 +271,9 @@ pub fn store_pool(pool_path: &str, pool: &pool::SubnetPool) {
         &format!("Could not create pool file at {}", path.display()),
     );
 
-    match pool_format {
-        PoolFormat::Json => to_writer(&pool_file, serde_json::to_writer_pretty, pool),
-        PoolFormat::Yaml => to_writer(&pool_file, serde_yaml::to_writer, pool),
-    }
+    result(
+        storage::write(&pool_file, value, pool_format, compact),
+        exitcode::CANTCREAT,
+        "Could not store pool file",
+    );
 }
@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 mod fixture;
+mod storage;
 mod subcommands;
 
 #[test]
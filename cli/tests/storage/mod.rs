@@ -0,0 +1,238 @@
+// Copyright 2024 The Milton Hirsch Institute, B.V.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::fixture;
+use crate::fixture::Test;
+use std::fs;
+
+const TEST_TIME: &str = "2024-01-26T12:00:00Z";
+
+fn new_storage_test(path: &str) -> Test {
+    let mut test = fixture::new_test_with_path(path);
+    test.pool
+        .allocate_at_time(8, Some("b-name"), TEST_TIME)
+        .unwrap();
+    test.pool
+        .allocate_at_time(4, Some("a-name"), TEST_TIME)
+        .unwrap();
+    test.pool.allocate_at_time(8, None, TEST_TIME).unwrap();
+    test
+}
+
+#[test]
+fn yaml_is_stable() {
+    let test = new_storage_test("pool.yaml");
+    test.store();
+    let first = fs::read(&test.pool_path).unwrap();
+    test.store();
+    let second = fs::read(&test.pool_path).unwrap();
+    assert_eq!(first, second);
+    assert_eq!(
+        String::from_utf8(first).unwrap(),
+        "cidr: 10.10.0.0/16\n\
+         subnets:\n\
+         - cidr: 10.10.0.0/24\n  \
+           name: b-name\n  \
+           created_at: 2024-01-26T12:00:00Z\n\
+         - cidr: 10.10.1.0/28\n  \
+           name: a-name\n  \
+           created_at: 2024-01-26T12:00:00Z\n\
+         - cidr: 10.10.2.0/24\n  \
+           created_at: 2024-01-26T12:00:00Z\n"
+    );
+}
+
+#[test]
+fn json_is_stable() {
+    let test = new_storage_test("pool.json");
+    test.store();
+    let first = fs::read(&test.pool_path).unwrap();
+    test.store();
+    let second = fs::read(&test.pool_path).unwrap();
+    assert_eq!(first, second);
+}
+
+#[test]
+fn json_omits_unnamed() {
+    let mut test = fixture::new_test_with_path("pool.json");
+    test.pool.allocate_at_time(8, None, TEST_TIME).unwrap();
+    subg::store_pool_as(test.pool_path.to_str().unwrap(), &test.pool, true);
+    assert_eq!(
+        fs::read_to_string(&test.pool_path).unwrap(),
+        "{\"cidr\":\"10.10.0.0/16\",\"subnets\":[\
+         {\"cidr\":\"10.10.0.0/24\",\"created_at\":\"2024-01-26T12:00:00Z\"}]}"
+    );
+
+    let expected = test.pool.clone();
+    test.load();
+    assert_eq!(test.pool, expected);
+}
+
+#[test]
+fn loads_null_name() {
+    let mut test = fixture::new_test_with_path("pool.json");
+    fs::write(
+        &test.pool_path,
+        "{\"cidr\":\"10.10.0.0/16\",\"subnets\":[{\"cidr\":\"10.10.0.0/24\",\"name\":null}]}",
+    )
+    .unwrap();
+    test.load();
+    let record = test.pool.records().next().unwrap();
+    assert_eq!(record.name, None);
+    assert_eq!(test.pool.named_count(), 0);
+}
+
+#[test]
+fn rejects_duplicate_cidr() {
+    let mut test = fixture::new_test_with_path("pool.yaml");
+    fs::write(
+        &test.pool_path,
+        "cidr: 10.10.0.0/16\n\
+         subnets:\n\
+         - cidr: 10.10.0.0/24\n\
+         - cidr: 10.10.1.0/24\n\
+         - {cidr: 10.10.0.0/24, name: web}\n",
+    )
+    .unwrap();
+    test.subg.arg("cidrs");
+    test.subg
+        .assert()
+        .failure()
+        .code(exitcode::DATAERR)
+        .stdout("")
+        .stderr(predicates::str::starts_with(
+            "Unable to load subnet pool file\nDuplicate CIDR: 10.10.0.0/24",
+        ));
+}
+
+fn new_overlapping_test(args: &[&str]) -> Test {
+    let mut test = fixture::new_test_with_path("pool.yaml");
+    fs::write(
+        &test.pool_path,
+        "cidr: 10.10.0.0/16\n\
+         subnets:\n\
+         - {cidr: 10.10.0.0/24, name: web}\n\
+         - {cidr: 10.10.0.0/25, name: db}\n\
+         - {cidr: 10.10.1.0/24}\n",
+    )
+    .unwrap();
+    test.subg.args(args).arg("cidrs");
+    test
+}
+
+#[test]
+fn strict_rejects_overlapping() {
+    for args in [&[][..], &["--strict"], &["--lenient", "--strict"]] {
+        let mut test = new_overlapping_test(args);
+        test.subg
+            .assert()
+            .failure()
+            .code(exitcode::DATAERR)
+            .stdout("")
+            .stderr(predicates::str::starts_with(
+                "Unable to load subnet pool file\nNo space available",
+            ));
+    }
+}
+
+#[test]
+fn lenient_skips_overlapping() {
+    let mut test = new_overlapping_test(&["--lenient"]);
+    test.subg
+        .assert()
+        .success()
+        .stdout("10.10.0.0/24\n10.10.1.0/24\n")
+        .stderr("Skipped subnet 10.10.0.0/25 overlapping an earlier subnet\n");
+}
+
+#[test]
+fn json_compact_only_when_asked() {
+    let mut test = new_storage_test("pool.json");
+    test.pool.set_metadata("owner", "alice");
+    subg::store_pool_as(test.pool_path.to_str().unwrap(), &test.pool, true);
+    let content = fs::read_to_string(&test.pool_path).unwrap();
+    assert!(content.starts_with("{\"cidr\":\"10.10.0.0/16\","));
+    assert!(!content.contains('\n'));
+
+    let expected = subg::load_pool(test.pool_path.to_str().unwrap());
+    test.pool.allocate(4, None).unwrap();
+    test.store();
+    assert!(fs::read_to_string(&test.pool_path).unwrap().contains('\n'));
+    test.load();
+    assert_eq!(test.pool.metadata(), expected.metadata());
+    assert_eq!(test.pool.allocated_count(), expected.allocated_count() + 1);
+}
+
+#[test]
+fn json_pretty_by_default() {
+    let test = new_storage_test("pool.json");
+    test.store();
+    let content = fs::read_to_string(&test.pool_path).unwrap();
+    assert!(content.starts_with("{\n  \"cidr\": \"10.10.0.0/16\","));
+}
+
+fn new_sniff_test(content: &str) -> Test {
+    let test = fixture::new_test_with_path("pool.conf");
+    fs::write(&test.pool_path, content).unwrap();
+    test
+}
+
+#[test]
+fn sniffs_json() {
+    let mut test = new_sniff_test(
+        "\n  {\"cidr\": \"10.10.0.0/16\", \"subnets\": [{\"cidr\": \"10.10.0.0/24\"}]}",
+    );
+    test.load();
+    assert!(test.pool.contains(&"10.10.0.0/24".parse().unwrap()));
+
+    test.pool.allocate(8, None).unwrap();
+    test.store();
+    assert!(fs::read_to_string(&test.pool_path)
+        .unwrap()
+        .starts_with('{'));
+}
+
+#[test]
+fn sniffs_yaml() {
+    let mut test = new_sniff_test("cidr: 10.10.0.0/16\nsubnets:\n- cidr: 10.10.0.0/24\n");
+    test.load();
+    assert!(test.pool.contains(&"10.10.0.0/24".parse().unwrap()));
+
+    test.pool.allocate(8, None).unwrap();
+    test.store();
+    assert!(fs::read_to_string(&test.pool_path)
+        .unwrap()
+        .starts_with("cidr: "));
+}
+
+#[test]
+fn toml() {
+    let mut test = new_storage_test("pool.toml");
+    test.pool.set_metadata("owner", "alice");
+    test.store();
+    assert_eq!(
+        fs::read_to_string(&test.pool_path).unwrap(),
+        "cidr = \"10.10.0.0/16\"\n\
+         \n\
+         [[subnets]]\n\
+         cidr = \"10.10.0.0/24\"\n\
+         created_at = \"2024-01-26T12:00:00Z\"\n\
+         name = \"b-name\"\n\
+         \n\
+         [[subnets]]\n\
+         cidr = \"10.10.1.0/28\"\n\
+         created_at = \"2024-01-26T12:00:00Z\"\n\
+         name = \"a-name\"\n\
+         \n\
+         [[subnets]]\n\
+         cidr = \"10.10.2.0/24\"\n\
+         created_at = \"2024-01-26T12:00:00Z\"\n\
+         \n\
+         [metadata]\n\
+         owner = \"alice\"\n"
+    );
+
+    let expected = test.pool.clone();
+    test.load();
+    assert_eq!(test.pool, expected);
+}
@@ -0,0 +1,76 @@
+// Copyright 2024 The Milton Hirsch Institute, B.V.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::fixture;
+
+fn new_config_test(args: &[&str]) -> fixture::Test {
+    let mut test = fixture::new_test();
+    test.pool.set_metadata("owner", "alice");
+    test.pool.set_metadata("region", "eu-west");
+    test.store();
+    test.subg.arg("config").args(args);
+    test
+}
+
+#[test]
+fn set() {
+    let mut test = new_config_test(&["set", "owner", "bob"]);
+    test.subg.assert().success().stdout("").stderr("");
+    test.load();
+    assert_eq!(test.pool.metadata()["owner"], "bob");
+    assert_eq!(test.pool.metadata()["region"], "eu-west");
+}
+
+#[test]
+fn set_new_key() {
+    let mut test = new_config_test(&["set", "team", "garden"]);
+    test.subg.assert().success().stdout("").stderr("");
+    test.load();
+    assert_eq!(test.pool.metadata().len(), 3);
+    assert_eq!(test.pool.metadata()["team"], "garden");
+}
+
+#[test]
+fn get() {
+    let mut test = new_config_test(&["get", "owner"]);
+    test.subg.assert().success().stdout("alice\n").stderr("");
+}
+
+#[test]
+fn get_missing() {
+    let mut test = new_config_test(&["get", "team"]);
+    test.subg
+        .assert()
+        .failure()
+        .code(exitcode::DATAERR)
+        .stdout("")
+        .stderr("No such key: team\n");
+}
+
+#[test]
+fn list() {
+    let mut test = new_config_test(&["list"]);
+    test.subg
+        .assert()
+        .success()
+        .stdout("owner = alice\nregion = eu-west\n")
+        .stderr("");
+}
+
+#[test]
+fn list_json() {
+    let mut test = new_config_test(&["list", "-o", "json"]);
+    test.subg
+        .assert()
+        .success()
+        .stdout("{\n  \"owner\": \"alice\",\n  \"region\": \"eu-west\"\n}\n")
+        .stderr("");
+}
+
+#[test]
+fn list_empty() {
+    let mut test = fixture::new_test();
+    test.store();
+    test.subg.args(["config", "list"]);
+    test.subg.assert().success().stdout("").stderr("");
+}
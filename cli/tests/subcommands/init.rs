@@ -26,6 +26,74 @@ fn unforced() {
     assert_eq!(test.pool.allocated_count(), 0);
 }
 
+#[test]
+fn compact() {
+    let mut test = fixture::new_test_with_path("pool.json");
+    test.subg.args(["--compact", "init", fixture::TEST_CIDR]);
+    test.subg.assert().success().stdout("").stderr("");
+    assert_eq!(
+        std::fs::read_to_string(&test.pool_path).unwrap(),
+        "{\"cidr\":\"10.10.0.0/16\",\"subnets\":[]}"
+    );
+    test.load();
+    assert_eq!(
+        test.pool,
+        pool::SubnetPool::new(fixture::TEST_CIDR.parse().unwrap())
+    );
+}
+
+#[test]
+fn exclude() {
+    let mut test = new_init_test(fixture::TEST_CIDR);
+    test.subg
+        .args(["--exclude", "10.10.0.0/17", "-e", "10.10.128.0/24"]);
+    test.subg.assert().success().stdout("").stderr("");
+
+    test.load();
+    assert_eq!(test.pool.allocated_count(), 2);
+    assert!(test
+        .pool
+        .records()
+        .all(|record| record.tags == ["excluded"]));
+    assert_eq!(test.pool.max_available_bits(), 14);
+    let excluded: cidr::IpCidr = "10.10.0.0/17".parse().unwrap();
+    while let Ok(cidr) = test.pool.allocate(8, None) {
+        assert!(!excluded.contains(&cidr.first_address()));
+        assert_ne!(cidr, "10.10.128.0/24".parse().unwrap());
+    }
+    assert_eq!(test.pool.allocated_count(), 2 + 127);
+}
+
+#[test]
+fn exclude_max_available() {
+    let mut test = new_init_test(fixture::TEST_CIDR);
+    test.subg.args(["--exclude", "10.10.0.0/17"]);
+    test.subg.assert().success();
+
+    let mut max_available = assert_cmd::Command::cargo_bin(subg::SUBG_COMMAND).unwrap();
+    max_available
+        .args(["--pool-path", test.pool_path.to_str().unwrap()])
+        .arg("max-available")
+        .assert()
+        .success()
+        .stdout("15\n")
+        .stderr("");
+}
+
+#[test]
+fn exclude_outside_pool() {
+    let mut test = new_init_test(fixture::TEST_CIDR);
+    test.subg.args(["--exclude", "10.20.0.0/24"]);
+    test.subg
+        .assert()
+        .failure()
+        .code(exitcode::USAGE)
+        .stdout("")
+        .stderr("Could not exclude 10.20.0.0/24\nNo space available\n");
+
+    test.pool_path.assert(predicates::path::missing());
+}
+
 #[test]
 fn already_exists() {
     let mut test = new_init_test(fixture::TEST_CIDR);
@@ -39,6 +107,7 @@ fn already_exists() {
             "Pool file already exists at {}",
             test.pool_path.display()
         )));
+    assert_ne!(exitcode::CANTCREAT, subg::CONFLICT);
 
     test.pool_path.assert("");
 }
@@ -114,3 +183,82 @@ fn bad_pool_file() {
             test.pool_path.display()
         ));
 }
+
+fn new_source_pool() -> (assert_fs::TempDir, std::path::PathBuf, pool::SubnetPool) {
+    let dir = assert_fs::TempDir::new().unwrap();
+    let path = dir.path().join("source.yaml");
+    let mut source = pool::SubnetPool::new("10.20.0.0/16".parse().unwrap());
+    source.allocate(4, Some("test0")).unwrap();
+    source.allocate(8, Some("test1")).unwrap();
+    source.allocate(4, None).unwrap();
+    subg::store_pool(path.to_str().unwrap(), &source);
+    (dir, path, source)
+}
+
+#[test]
+fn from_existing() {
+    let (_dir, source_path, source) = new_source_pool();
+    let mut test = fixture::new_test();
+    test.subg
+        .args(["init", "--from", source_path.to_str().unwrap()]);
+    test.subg.assert().success().stdout("").stderr("");
+
+    test.load();
+    assert_eq!(test.pool, source);
+}
+
+#[test]
+fn from_existing_with_root() {
+    let (_dir, source_path, source) = new_source_pool();
+    let mut test = fixture::new_test();
+    test.subg.args([
+        "init",
+        "--from",
+        source_path.to_str().unwrap(),
+        "--root",
+        fixture::TEST_CIDR,
+    ]);
+    test.subg.assert().success().stdout("").stderr("");
+
+    test.load();
+    assert_eq!(
+        test.pool,
+        source
+            .renumber(fixture::TEST_CIDR.parse().unwrap())
+            .unwrap()
+    );
+    assert_eq!(
+        test.pool.find_by_name("test1"),
+        Some("10.10.1.0/24".parse().unwrap())
+    );
+}
+
+#[test]
+fn from_existing_already_exists() {
+    let (_dir, source_path, _) = new_source_pool();
+    let mut test = fixture::new_test();
+    test.pool_path.touch().unwrap();
+    test.subg
+        .args(["init", "--from", source_path.to_str().unwrap()]);
+    test.subg
+        .assert()
+        .failure()
+        .code(exitcode::CANTCREAT)
+        .stdout("")
+        .stderr(predicates::str::starts_with(format!(
+            "Pool file already exists at {}",
+            test.pool_path.display()
+        )));
+
+    test.pool_path.assert("");
+}
+
+#[test]
+fn from_with_cidr() {
+    let (_dir, source_path, _) = new_source_pool();
+    let mut test = new_init_test(fixture::TEST_CIDR);
+    test.subg.args(["--from", source_path.to_str().unwrap()]);
+    test.subg.assert().failure().code(fixture::HELP_EXIT_CODE);
+
+    test.pool_path.assert(predicates::path::missing());
+}
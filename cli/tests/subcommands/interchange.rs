@@ -0,0 +1,198 @@
+// Copyright 2024 The Milton Hirsch Institute, B.V.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::fixture;
+use crate::fixture::Test;
+use assert_fs::assert::PathAssert;
+use assert_fs::fixture::FileTouch;
+
+const TEST_TIME: &str = "2024-01-26T12:00:00Z";
+
+fn new_export_test(args: &[&str]) -> Test {
+    let mut test = fixture::new_test();
+    test.pool.set_description(Some("Lab networks"));
+    test.pool
+        .allocate_at_time(8, Some("test1"), TEST_TIME)
+        .unwrap();
+    test.pool.allocate_at_time(4, None, TEST_TIME).unwrap();
+    test.store();
+    test.subg.arg("export").args(args);
+    test
+}
+
+fn import(content: Vec<u8>, args: &[&str]) -> Test {
+    let mut test = fixture::new_test();
+    test.subg.arg("import").args(args).write_stdin(content);
+    test
+}
+
+#[test]
+fn postcard_round_trip() {
+    let mut export = new_export_test(&["--format", "postcard"]);
+    let output = export.subg.assert().success().stderr("");
+    let content = output.get_output().stdout.clone();
+    assert_eq!(
+        postcard::from_bytes::<subnet_garden_core::pool::SubnetPool>(&content).unwrap(),
+        export.pool
+    );
+
+    let mut import = import(content, &["--format", "postcard"]);
+    import.subg.assert().success().stdout("").stderr("");
+    import.load();
+    assert_eq!(import.pool, export.pool);
+}
+
+#[test]
+fn base64_round_trip() {
+    let mut export = new_export_test(&["--format", "postcard", "--base64"]);
+    let output = export.subg.assert().success().stderr("");
+    let content = output.get_output().stdout.clone();
+    assert!(content.ends_with(b"\n"));
+    assert!(content.iter().all(u8::is_ascii));
+
+    let mut import = import(content, &["--format", "postcard", "--base64"]);
+    import.subg.assert().success().stdout("").stderr("");
+    import.load();
+    assert_eq!(import.pool, export.pool);
+}
+
+#[test]
+fn json_round_trip() {
+    let mut export = new_export_test(&["--format", "json"]);
+    let output = export.subg.assert().success().stderr("");
+    let content = output.get_output().stdout.clone();
+    assert!(content.starts_with(b"{\n  \"cidr\": \"10.10.0.0/16\","));
+
+    let mut import = import(content, &["--format", "json"]);
+    import.subg.assert().success().stdout("").stderr("");
+    import.load();
+    assert_eq!(import.pool, export.pool);
+}
+
+#[test]
+fn import_already_exists() {
+    let mut test = import(vec![], &["--format", "postcard"]);
+    test.pool_path.touch().unwrap();
+    test.subg
+        .assert()
+        .failure()
+        .code(exitcode::CANTCREAT)
+        .stdout("")
+        .stderr(format!(
+            "Pool file already exists at {}\n",
+            test.pool_path.display()
+        ));
+    test.pool_path.assert("");
+}
+
+#[test]
+fn import_invalid_base64() {
+    let mut test = import(
+        b"not base64!".to_vec(),
+        &["--format", "postcard", "--base64"],
+    );
+    test.subg
+        .assert()
+        .failure()
+        .code(exitcode::DATAERR)
+        .stdout("")
+        .stderr("Could not import pool\nInvalid symbol 33, offset 9.\n");
+    test.pool_path.assert(predicates::path::missing());
+}
+
+#[test]
+fn import_invalid_postcard() {
+    let mut test = import(vec![0xff], &["--format", "postcard"]);
+    test.subg
+        .assert()
+        .failure()
+        .code(exitcode::DATAERR)
+        .stdout("");
+    test.pool_path.assert(predicates::path::missing());
+}
+
+fn convert(test: &Test, args: &[&str]) {
+    assert_cmd::Command::cargo_bin(subg::SUBG_COMMAND)
+        .unwrap()
+        .arg("convert")
+        .args(args)
+        .current_dir(test.pool_path.parent().unwrap())
+        .assert()
+        .success()
+        .stdout("")
+        .stderr("");
+}
+
+#[test]
+fn convert_round_trip() {
+    let mut test = fixture::new_test_with_path("pool.json");
+    test.pool.set_description(Some("Lab networks"));
+    test.pool.set_metadata("owner", "alice");
+    test.pool
+        .allocate_at_time(8, Some("test1"), TEST_TIME)
+        .unwrap();
+    test.pool.allocate_at_time(4, None, TEST_TIME).unwrap();
+    test.store();
+
+    convert(&test, &["--in", "pool.json", "--out", "pool.yaml"]);
+    convert(&test, &["--in", "pool.yaml", "--out", "pool.toml"]);
+    let dir = test.pool_path.parent().unwrap();
+    assert!(std::fs::read_to_string(dir.join("pool.yaml"))
+        .unwrap()
+        .starts_with("cidr: 10.10.0.0/16\n"));
+    let converted = subg::load_pool(dir.join("pool.toml").to_str().unwrap());
+    assert_eq!(converted, test.pool);
+}
+
+#[test]
+fn convert_format_override() {
+    let mut test = fixture::new_test_with_path("pool.json");
+    test.pool.allocate(4, Some("test1")).unwrap();
+    test.store();
+
+    convert(
+        &test,
+        &[
+            "--in",
+            "pool.json",
+            "--out",
+            "pool.out",
+            "--output-format",
+            "toml",
+        ],
+    );
+    let dir = test.pool_path.parent().unwrap();
+    assert!(std::fs::read_to_string(dir.join("pool.out"))
+        .unwrap()
+        .starts_with("cidr = \"10.10.0.0/16\"\n"));
+    convert(
+        &test,
+        &[
+            "--in",
+            "pool.out",
+            "--input-format",
+            "toml",
+            "--out",
+            "back.json",
+        ],
+    );
+    assert_eq!(
+        subg::load_pool(dir.join("back.json").to_str().unwrap()),
+        test.pool
+    );
+}
+
+#[test]
+fn convert_unknown_format() {
+    let test = fixture::new_test_with_path("pool.json");
+    test.store();
+    assert_cmd::Command::cargo_bin(subg::SUBG_COMMAND)
+        .unwrap()
+        .args(["convert", "--in", test.pool_path.to_str().unwrap()])
+        .args(["--out", "pool.bin", "--output-format", "bin"])
+        .assert()
+        .failure()
+        .code(fixture::HELP_EXIT_CODE)
+        .stdout("")
+        .stderr(predicates::str::contains("Unknown pool format: bin"));
+}
@@ -3,6 +3,8 @@
 
 use crate::fixture;
 use crate::fixture::Test;
+use assert_fs::prelude::*;
+use predicates::prelude::*;
 mod cidrs {
     use super::*;
     fn new_cidrs_test() -> Test {
@@ -31,6 +33,127 @@ mod cidrs {
             .stderr("");
     }
 
+    #[test]
+    fn porcelain() {
+        let mut test = new_cidrs_test();
+        test.subg.arg("--porcelain");
+        test.pool.allocate(4, Some("test1")).unwrap();
+        test.pool.allocate(6, None).unwrap();
+        test.pool.allocate(6, Some("test two")).unwrap();
+        test.store();
+        test.subg
+            .assert()
+            .success()
+            .stdout(
+                "10.10.0.0/28\ttest1\n\
+                 10.10.0.64/26\t-\n\
+                 10.10.0.128/26\ttest two\n",
+            )
+            .stderr("");
+    }
+
+    #[test]
+    fn reverse() {
+        let mut test = new_cidrs_test();
+        test.subg.arg("--reverse");
+        test.pool.allocate(4, Some("test1")).unwrap();
+        test.pool.allocate(6, None).unwrap();
+        test.pool.allocate(5, Some("test2")).unwrap();
+        test.store();
+        test.subg
+            .assert()
+            .success()
+            .stdout("10.10.0.64/26\n10.10.0.32/27\n10.10.0.0/28\n")
+            .stderr("");
+    }
+
+    #[test]
+    fn reverse_long() {
+        let mut test = new_cidrs_test();
+        test.subg.args(["-l", "-r"]);
+        test.pool.allocate(4, Some("test1")).unwrap();
+        test.pool.allocate(6, None).unwrap();
+        test.store();
+        test.subg
+            .assert()
+            .success()
+            .stdout(
+                "total 2\n\
+                 10.10.0.64/26  -\n\
+                 10.10.0.0/28   test1\n",
+            )
+            .stderr("");
+    }
+
+    fn new_mixed_cidrs_test() -> Test {
+        let mut test = new_cidrs_test();
+        test.pool.allocate(4, Some("test1")).unwrap();
+        test.pool.allocate(4, None).unwrap();
+        test.pool.allocate(6, Some("test2")).unwrap();
+        test.pool.allocate(6, None).unwrap();
+        test.store();
+        test
+    }
+
+    #[test]
+    fn only_named() {
+        let mut test = new_mixed_cidrs_test();
+        test.subg.arg("--only-named");
+        test.subg
+            .assert()
+            .success()
+            .stdout("10.10.0.0/28\n10.10.0.64/26\n")
+            .stderr("");
+    }
+
+    #[test]
+    fn only_unnamed() {
+        let mut test = new_mixed_cidrs_test();
+        test.subg.arg("--only-unnamed");
+        test.subg
+            .assert()
+            .success()
+            .stdout("10.10.0.16/28\n10.10.0.128/26\n")
+            .stderr("");
+    }
+
+    #[test]
+    fn only_unnamed_long() {
+        let mut test = new_mixed_cidrs_test();
+        test.subg.args(["-l", "--only-unnamed"]);
+        test.subg
+            .assert()
+            .success()
+            .stdout(
+                "total 4\n\
+                 10.10.0.16/28   -\n\
+                 10.10.0.128/26  -\n",
+            )
+            .stderr("");
+    }
+
+    #[test]
+    fn only_named_conflicts_with_only_unnamed() {
+        let mut test = new_mixed_cidrs_test();
+        test.subg.args(["--only-named", "--only-unnamed"]);
+        test.subg
+            .assert()
+            .failure()
+            .code(fixture::HELP_EXIT_CODE)
+            .stdout("");
+    }
+
+    #[test]
+    fn porcelain_conflicts_with_long() {
+        let mut test = new_cidrs_test();
+        test.subg.args(["--porcelain", "-l"]);
+        test.subg
+            .assert()
+            .failure()
+            .code(fixture::HELP_EXIT_CODE)
+            .stdout("");
+    }
+
     #[test]
     fn has_cidrs_long() {
         let mut test = new_cidrs_test();
@@ -51,6 +174,49 @@ mod cidrs {
             .stderr("");
     }
 
+    #[test]
+    fn has_cidrs_long_show_hosts() {
+        let mut test = new_cidrs_test();
+        test.subg.args(["-l", "--show-hosts"]);
+        test.pool.allocate(8, Some("web-servers")).unwrap();
+        test.pool.allocate(6, None).unwrap();
+        test.store();
+        test.subg
+            .assert()
+            .success()
+            .stdout(
+                "total 2\n\
+                 10.10.0.0/24  web-servers  254\n\
+                 10.10.1.0/26  -            62\n",
+            )
+            .stderr("");
+    }
+
+    #[test]
+    fn show_hosts_requires_long() {
+        let mut test = new_cidrs_test();
+        test.subg.arg("--show-hosts");
+        test.store();
+        test.subg.assert().failure().code(fixture::HELP_EXIT_CODE);
+    }
+
+    #[test]
+    fn has_cidrs_long_no_header() {
+        let mut test = new_cidrs_test();
+        test.subg.arg("-l").arg("--no-header");
+        test.pool.allocate(4, Some("test1")).unwrap();
+        test.pool.allocate(6, None).unwrap();
+        test.store();
+        test.subg
+            .assert()
+            .success()
+            .stdout(
+                "10.10.0.0/28   test1\n\
+                 10.10.0.64/26  -\n",
+            )
+            .stderr("");
+    }
+
     #[test]
     fn subset_cidrs() {
         let mut test = new_cidrs_test();
@@ -65,6 +231,167 @@ mod cidrs {
             .stdout("10.10.0.0/28\n10.10.0.16/28\n")
             .stderr("");
     }
+
+    fn new_timed_cidrs_test() -> Test {
+        let mut test = new_cidrs_test();
+        test.pool
+            .allocate_at_time(4, Some("old"), "2023-06-01T00:00:00Z")
+            .unwrap();
+        test.pool
+            .allocate_at_time(4, Some("new"), "2024-06-01T00:00:00Z")
+            .unwrap();
+        test.store();
+        test
+    }
+
+    #[test]
+    fn older_than() {
+        let mut test = new_timed_cidrs_test();
+        test.subg.arg("--older-than").arg("2024-01-01");
+        test.subg
+            .assert()
+            .success()
+            .stdout("10.10.0.0/28\n")
+            .stderr("");
+    }
+
+    #[test]
+    fn newer_than() {
+        let mut test = new_timed_cidrs_test();
+        test.subg.arg("--newer-than").arg("2024-01-01T00:00:00Z");
+        test.subg
+            .assert()
+            .success()
+            .stdout("10.10.0.16/28\n")
+            .stderr("");
+    }
+
+    #[test]
+    fn older_and_newer_than() {
+        let mut test = new_timed_cidrs_test();
+        test.subg
+            .arg("--newer-than")
+            .arg("2023-01-01")
+            .arg("--older-than")
+            .arg("2023-12-31");
+        test.subg
+            .assert()
+            .success()
+            .stdout("10.10.0.0/28\n")
+            .stderr("");
+    }
+
+    #[test]
+    fn older_than_relative() {
+        let mut test = new_timed_cidrs_test();
+        test.subg.arg("--older-than").arg("1d");
+        test.subg
+            .assert()
+            .success()
+            .stdout("10.10.0.0/28\n10.10.0.16/28\n")
+            .stderr("");
+    }
+
+    #[test]
+    fn untimed() {
+        let mut test = new_cidrs_test();
+        test.pool_path
+            .write_str(
+                "cidr: 10.10.0.0/16\n\
+                 subnets:\n\
+                 - cidr: 10.10.0.0/24\n\
+                 - cidr: 10.10.1.0/24\n  \
+                   created_at: 2023-06-01T00:00:00Z\n",
+            )
+            .unwrap();
+        test.subg.arg("--older-than").arg("2024-01-01");
+        test.subg
+            .assert()
+            .success()
+            .stdout("10.10.1.0/24\n")
+            .stderr("");
+    }
+
+    #[test]
+    fn include_untimed() {
+        let mut test = new_cidrs_test();
+        test.pool_path
+            .write_str(
+                "cidr: 10.10.0.0/16\n\
+                 subnets:\n\
+                 - cidr: 10.10.0.0/24\n\
+                 - cidr: 10.10.1.0/24\n  \
+                   created_at: 2024-06-01T00:00:00Z\n",
+            )
+            .unwrap();
+        test.subg
+            .arg("--older-than")
+            .arg("2024-01-01")
+            .arg("--include-untimed");
+        test.subg
+            .assert()
+            .success()
+            .stdout("10.10.0.0/24\n")
+            .stderr("");
+    }
+
+    #[test]
+    fn bad_time() {
+        let mut test = new_cidrs_test();
+        test.subg.arg("--older-than").arg("yesterday");
+        test.subg
+            .assert()
+            .failure()
+            .code(fixture::HELP_EXIT_CODE)
+            .stdout("")
+            .stderr(predicates::str::contains(
+                "Expected age (e.g. 30d), date or RFC 3339 time, found yesterday",
+            ));
+    }
+
+    #[test]
+    fn long_not_colored_when_captured() {
+        let mut test = new_cidrs_test();
+        test.subg.arg("-l");
+        test.pool.allocate(4, None).unwrap();
+        test.store();
+        test.subg
+            .assert()
+            .success()
+            .stdout(predicates::str::contains("\x1b[").not())
+            .stderr("");
+    }
+
+    #[test]
+    fn long_color_always() {
+        let mut test = fixture::new_test();
+        test.pool.allocate(4, Some("test1")).unwrap();
+        test.pool.allocate(6, None).unwrap();
+        test.store();
+        test.subg.args(["--color", "always", "cidrs", "-l"]);
+        test.subg
+            .assert()
+            .success()
+            .stdout(
+                "\x1b[1mtotal 2\x1b[0m\n\
+                 \x1b[36m10.10.0.0/28 \x1b[0m  test1\n\
+                 \x1b[36m10.10.0.64/26\x1b[0m  \x1b[2m-\x1b[0m\n",
+            )
+            .stderr("");
+    }
+
+    #[test]
+    fn long_color_never() {
+        let mut test = fixture::new_test();
+        test.pool.allocate(4, None).unwrap();
+        test.store();
+        test.subg.args(["--color", "never", "cidrs", "-l"]);
+        test.subg
+            .assert()
+            .success()
+            .stdout("total 1\n10.10.0.0/28  -\n")
+            .stderr("");
+    }
 }
 
 mod names {
@@ -97,6 +424,36 @@ mod names {
             .stderr("");
     }
 
+    #[test]
+    fn reverse() {
+        let mut test = new_names_test();
+        test.subg.arg("--reverse");
+        test.pool.allocate(4, Some("test1")).unwrap();
+        test.pool.allocate(6, Some("test2")).unwrap();
+        test.pool.allocate(4, Some("test0")).unwrap();
+        test.store();
+        test.subg
+            .assert()
+            .success()
+            .stdout("test2\ntest1\ntest0\n")
+            .stderr("");
+    }
+
+    #[test]
+    fn porcelain() {
+        let mut test = new_names_test();
+        test.subg.arg("--porcelain");
+        test.pool.allocate(4, Some("test1")).unwrap();
+        test.pool.allocate(5, None).unwrap();
+        test.pool.allocate(4, Some("test0")).unwrap();
+        test.store();
+        test.subg
+            .assert()
+            .success()
+            .stdout("10.10.0.16/28\ttest0\n10.10.0.0/28\ttest1\n")
+            .stderr("");
+    }
+
     #[test]
     fn has_names_long() {
         let mut test = new_names_test();
@@ -117,4 +474,218 @@ mod names {
             )
             .stderr("");
     }
+
+    #[test]
+    fn has_names_long_no_header() {
+        let mut test = new_names_test();
+        test.subg.arg("-l").arg("--no-header");
+        test.pool.allocate(4, Some("test1")).unwrap();
+        test.pool.allocate(5, None).unwrap();
+        test.pool.allocate(6, Some("test2")).unwrap();
+        test.store();
+        test.subg
+            .assert()
+            .success()
+            .stdout(
+                "test1  10.10.0.0/28\n\
+                 test2  10.10.0.64/26\n",
+            )
+            .stderr("");
+    }
+
+    #[test]
+    fn has_names_long_spaces_and_multibyte() {
+        let mut test = new_names_test();
+        test.subg.arg("-l");
+        test.pool.allocate(4, Some("prod web")).unwrap();
+        test.pool.allocate(4, Some("café")).unwrap();
+        test.pool.allocate(4, Some("ab")).unwrap();
+        test.pool.allocate(4, Some("实验")).unwrap();
+        test.store();
+        test.subg
+            .assert()
+            .success()
+            .stdout(
+                "total 4 of 4\n\
+                 ab        10.10.0.32/28\n\
+                 café      10.10.0.16/28\n\
+                 prod web  10.10.0.0/28\n\
+                 实验      10.10.0.48/28\n",
+            )
+            .stderr("");
+    }
+
+    #[test]
+    fn long_color_always() {
+        let mut test = fixture::new_test();
+        test.pool.allocate(4, Some("test1")).unwrap();
+        test.pool.allocate(4, None).unwrap();
+        test.store();
+        test.subg.args(["--color", "always", "names", "-l"]);
+        test.subg
+            .assert()
+            .success()
+            .stdout(
+                "\x1b[1mtotal 1 of 2\x1b[0m\n\
+                 test1  \x1b[36m10.10.0.0/28\x1b[0m\n",
+            )
+            .stderr("");
+    }
+}
+
+mod grep {
+    use super::*;
+    fn new_grep_test(pattern: &str) -> Test {
+        let mut test = fixture::new_test();
+        test.pool.allocate(4, Some("web-prod")).unwrap();
+        test.pool.allocate(4, None).unwrap();
+        test.pool.allocate(6, Some("DB-prod")).unwrap();
+        test.pool.allocate(4, Some("web-test")).unwrap();
+        test.store();
+        test.subg.arg("grep").arg(pattern);
+        test
+    }
+
+    #[test]
+    fn no_matches() {
+        let mut test = new_grep_test("^staging");
+        test.subg.assert().success().stdout("").stderr("");
+    }
+
+    #[test]
+    fn matches() {
+        let mut test = new_grep_test("-prod$");
+        test.subg
+            .assert()
+            .success()
+            .stdout(
+                "10.10.0.0/28   web-prod\n\
+                 10.10.0.64/26  DB-prod\n",
+            )
+            .stderr("");
+    }
+
+    #[test]
+    fn ignore_case() {
+        let mut test = new_grep_test("^db");
+        test.subg.arg("-i");
+        test.subg
+            .assert()
+            .success()
+            .stdout("10.10.0.64/26  DB-prod\n")
+            .stderr("");
+    }
+
+    #[test]
+    fn invert() {
+        let mut test = new_grep_test("^web-");
+        test.subg.arg("--invert");
+        test.subg
+            .assert()
+            .success()
+            .stdout("10.10.0.64/26  DB-prod\n")
+            .stderr("");
+    }
+
+    #[test]
+    fn invalid_regex() {
+        let mut test = new_grep_test("web-(");
+        test.subg
+            .assert()
+            .failure()
+            .code(exitcode::USAGE)
+            .stdout("")
+            .stderr(predicates::str::starts_with(
+                "Invalid regular expression: web-(\n",
+            ));
+    }
+}
+
+mod render {
+    use super::*;
+    fn new_render_test(template: &str) -> Test {
+        let mut test = fixture::new_test();
+        test.pool.allocate(8, Some("web")).unwrap();
+        test.pool.allocate(4, None).unwrap();
+        test.store();
+        test.subg.arg("render").arg(template);
+        test
+    }
+
+    #[test]
+    fn no_subnets() {
+        let mut test = fixture::new_test();
+        test.store();
+        test.subg.arg("render").arg("{cidr}");
+        test.subg.assert().success().stdout("").stderr("");
+    }
+
+    #[test]
+    fn render() {
+        let mut test = new_render_test(r#"resource "aws_subnet" "{name}" { cidr = "{cidr}" }"#);
+        test.subg
+            .assert()
+            .success()
+            .stdout(
+                "resource \"aws_subnet\" \"web\" { cidr = \"10.10.0.0/24\" }\n\
+                 resource \"aws_subnet\" \"\" { cidr = \"10.10.1.0/28\" }\n",
+            )
+            .stderr("");
+    }
+
+    #[test]
+    fn first_and_last() {
+        let mut test = new_render_test("{first}-{last}");
+        test.subg
+            .assert()
+            .success()
+            .stdout("10.10.0.0-10.10.0.255\n10.10.1.0-10.10.1.15\n")
+            .stderr("");
+    }
+
+    #[test]
+    fn unknown_name() {
+        let mut test = new_render_test("{size}");
+        test.subg
+            .assert()
+            .failure()
+            .code(exitcode::USAGE)
+            .stdout("")
+            .stderr("Could not render template\nUnknown field: size\n");
+    }
+
+    #[test]
+    fn bad_template() {
+        let mut test = new_render_test("{cidr}\\");
+        test.subg
+            .assert()
+            .failure()
+            .code(exitcode::USAGE)
+            .stdout("")
+            .stderr("Could not parse template\nInvalid format: Unexpected end of format\n");
+    }
+}
+
+mod tree {
+    use super::*;
+
+    #[test]
+    fn tree() {
+        let mut test = fixture::new_test();
+        test.pool.allocate(15, Some("test1")).unwrap();
+        test.pool.allocate(14, None).unwrap();
+        test.store();
+        test.subg.arg("tree");
+        test.subg
+            .assert()
+            .success()
+            .stdout(
+                "10.10.0.0/16\n\
+                 ├── 10.10.0.0/17 test1\n\
+                 └── 10.10.128.0/17\n    \
+                     ├── 10.10.128.0/18 -\n    \
+                     └── 10.10.192.0/18 (free)\n",
+            )
+            .stderr("");
+    }
 }
@@ -1,6 +1,10 @@
 // Copyright 2024 The Milton Hirsch Institute, B.V.
 // SPDX-License-Identifier: Apache-2.0
 
+mod config;
 mod init;
+mod interchange;
 mod listing;
+mod space;
 mod subnet;
+mod watch;
@@ -0,0 +1,209 @@
+// Copyright 2024 The Milton Hirsch Institute, B.V.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::fixture;
+use subnet_garden_core::pool;
+use subnet_garden_core::pool_set::PoolSet;
+
+fn new_pool_set_test() -> fixture::Test {
+    let test = fixture::new_test();
+    let mut pool_set = PoolSet::new();
+    let mut prod = pool::SubnetPool::new(fixture::TEST_CIDR.parse().unwrap());
+    prod.allocate(8, Some("web")).unwrap();
+    pool_set.create("prod", prod).unwrap();
+    pool_set
+        .create(
+            "dev",
+            pool::SubnetPool::new("10.20.0.0/16".parse().unwrap()),
+        )
+        .unwrap();
+    store_set(&test, &pool_set);
+    test
+}
+
+fn store_set(test: &fixture::Test, pool_set: &PoolSet) {
+    subg::store_pool_set(test.pool_path.to_str().unwrap(), pool_set, false);
+}
+
+fn load_set(test: &fixture::Test) -> PoolSet {
+    subg::load_pool_set(test.pool_path.to_str().unwrap())
+}
+
+#[test]
+fn new() {
+    let mut test = new_pool_set_test();
+    test.subg.args(["space", "new", "test", "10.30.0.0/16"]);
+    test.subg.assert().success().stdout("").stderr("");
+
+    let pool_set = load_set(&test);
+    assert_eq!(
+        pool_set.names().collect::<Vec<_>>(),
+        vec!["dev", "prod", "test"]
+    );
+    assert_eq!(
+        pool_set.get("test").unwrap().cidr(),
+        &"10.30.0.0/16".parse::<cidr::IpCidr>().unwrap()
+    );
+    assert_eq!(pool_set.get("prod").unwrap().allocated_count(), 1);
+}
+
+#[test]
+fn new_file() {
+    let mut test = fixture::new_test();
+    test.subg.args(["space", "new", "test", fixture::TEST_CIDR]);
+    test.subg.assert().success().stdout("").stderr("");
+
+    let pool_set = load_set(&test);
+    assert_eq!(pool_set.names().collect::<Vec<_>>(), vec!["test"]);
+}
+
+#[test]
+fn new_duplicate() {
+    let mut test = new_pool_set_test();
+    test.subg.args(["space", "new", "prod", "10.30.0.0/16"]);
+    test.subg
+        .assert()
+        .failure()
+        .code(exitcode::USAGE)
+        .stdout("")
+        .stderr("Could not create space prod\nDuplicate object\n");
+
+    let pool_set = load_set(&test);
+    assert_eq!(pool_set.get("prod").unwrap().allocated_count(), 1);
+}
+
+#[test]
+fn delete() {
+    let mut test = new_pool_set_test();
+    test.subg.args(["space", "delete", "prod"]);
+    test.subg.assert().success().stdout("").stderr("");
+
+    let pool_set = load_set(&test);
+    assert_eq!(pool_set.names().collect::<Vec<_>>(), vec!["dev"]);
+}
+
+#[test]
+fn delete_missing() {
+    let mut test = new_pool_set_test();
+    test.subg.args(["space", "delete", "test"]);
+    test.subg
+        .assert()
+        .failure()
+        .code(exitcode::USAGE)
+        .stdout("")
+        .stderr("Could not delete space test\nNo such object\n");
+
+    assert_eq!(load_set(&test).len(), 2);
+}
+
+#[test]
+fn list() {
+    let mut test = new_pool_set_test();
+    test.subg.args(["space", "list"]);
+    test.subg
+        .assert()
+        .success()
+        .stdout("dev\nprod\n")
+        .stderr("");
+}
+
+#[test]
+fn list_single_pool() {
+    let mut test = fixture::new_test();
+    test.store();
+    test.subg.args(["space", "list"]);
+    test.subg
+        .assert()
+        .failure()
+        .code(exitcode::DATAERR)
+        .stdout("")
+        .stderr(predicates::str::starts_with(
+            "Unable to load subnet pool file\n",
+        ));
+}
+
+#[test]
+fn select_pool() {
+    let mut test = new_pool_set_test();
+    test.subg
+        .args(["--pool-name", "dev", "allocate", "8", "db"]);
+    test.subg
+        .assert()
+        .success()
+        .stdout("10.20.0.0/24\n")
+        .stderr("");
+
+    let pool_set = load_set(&test);
+    let dev = pool_set.get("dev").unwrap();
+    assert_eq!(
+        dev.find_by_name("db"),
+        Some("10.20.0.0/24".parse().unwrap())
+    );
+    let prod = pool_set.get("prod").unwrap();
+    assert_eq!(prod.allocated_count(), 1);
+    assert_eq!(prod.find_by_name("db"), None);
+}
+
+#[test]
+fn list_selected_pool() {
+    let mut test = new_pool_set_test();
+    test.subg.args(["--pool-name", "prod", "names"]);
+    test.subg.assert().success().stdout("web\n").stderr("");
+}
+
+#[test]
+fn no_such_pool() {
+    let mut test = new_pool_set_test();
+    test.subg.args(["--pool-name", "test", "names"]);
+    test.subg
+        .assert()
+        .failure()
+        .code(exitcode::DATAERR)
+        .stdout("")
+        .stderr(format!(
+            "No pool named test in {}\n",
+            test.pool_path.to_str().unwrap()
+        ));
+}
+
+#[test]
+fn init_new_file() {
+    let mut test = fixture::new_test();
+    test.subg
+        .args(["--pool-name", "dev", "init", fixture::TEST_CIDR]);
+    test.subg.assert().success().stdout("").stderr("");
+
+    let pool_set = load_set(&test);
+    assert_eq!(pool_set.names().collect::<Vec<_>>(), vec!["dev"]);
+}
+
+#[test]
+fn init_adds_pool() {
+    let mut test = new_pool_set_test();
+    test.subg
+        .args(["--pool-name", "test", "init", "10.30.0.0/16"]);
+    test.subg.assert().success().stdout("").stderr("");
+
+    let pool_set = load_set(&test);
+    assert_eq!(
+        pool_set.names().collect::<Vec<_>>(),
+        vec!["dev", "prod", "test"]
+    );
+    assert_eq!(pool_set.get("prod").unwrap().allocated_count(), 1);
+}
+
+#[test]
+fn init_existing_pool() {
+    let mut test = new_pool_set_test();
+    test.subg
+        .args(["--pool-name", "prod", "init", "10.30.0.0/16"]);
+    test.subg
+        .assert()
+        .failure()
+        .code(exitcode::CANTCREAT)
+        .stdout("")
+        .stderr(format!(
+            "Pool prod already exists in {}\n",
+            test.pool_path.to_str().unwrap()
+        ));
+}
@@ -19,6 +19,80 @@ mod allocate {
         test
     }
 
+    fn new_pool_cidr_test(pool_cidr: &str) -> Test {
+        let mut test = fixture::new_test();
+        test.store();
+        test.subg.args(["--pool-cidr", pool_cidr, "allocate", "8"]);
+        test
+    }
+
+    #[test]
+    fn allocate_pool_cidr() {
+        let mut test = new_pool_cidr_test(fixture::TEST_CIDR);
+        test.subg
+            .assert()
+            .success()
+            .stdout("10.10.0.0/24\n")
+            .stderr("");
+        test.load();
+        assert_eq!(test.pool.allocated_count(), 1);
+    }
+
+    #[test]
+    fn allocate_pool_cidr_mismatch() {
+        let mut test = new_pool_cidr_test("10.20.0.0/16");
+        test.subg
+            .assert()
+            .failure()
+            .code(exitcode::USAGE)
+            .stdout("")
+            .stderr("Pool CIDR is 10.10.0.0/16, expected 10.20.0.0/16\n");
+        test.load();
+        assert_eq!(test.pool.allocated_count(), 0);
+    }
+
+    fn new_verbosity_test(flag: &str) -> Test {
+        let mut test = fixture::new_test();
+        test.pool.allocate(8, None).unwrap();
+        test.store();
+        test.subg.args([flag, "allocate", "8", "test"]);
+        test
+    }
+
+    #[test]
+    fn allocate_global_quiet() {
+        let mut test = new_verbosity_test("--quiet");
+        test.subg.assert().success().stdout("").stderr("");
+        test.load();
+        assert!(test.pool.find_by_name("test").is_some());
+    }
+
+    #[test]
+    fn allocate_verbose() {
+        let mut test = new_verbosity_test("-v");
+        test.subg
+            .assert()
+            .success()
+            .stdout("10.10.1.0/24\n")
+            .stderr(
+                "Allocated 10.10.1.0/24 from free block 10.10.1.0/24\n\
+                 2 subnets allocated, 65024 addresses free\n",
+            );
+    }
+
+    #[test]
+    fn allocate_quiet_and_verbose() {
+        let mut test = fixture::new_test();
+        test.store();
+        test.subg.args(["-q", "-v", "allocate", "8"]);
+        test.subg
+            .assert()
+            .failure()
+            .code(fixture::HELP_EXIT_CODE)
+            .stdout("")
+            .stderr(predicates::str::contains("cannot be used with"));
+    }
+
     #[test]
     fn allocate_single_failure() {
         let mut test = new_allocate_test("8", Some("test"));
@@ -27,11 +101,26 @@ mod allocate {
         test.subg
             .assert()
             .failure()
-            .code(exitcode::SOFTWARE)
+            .code(subg::CONFLICT)
             .stdout("")
             .stderr("Could not allocate subnet\nNo space available\n");
     }
 
+    #[test]
+    fn allocate_duplicate_name() {
+        let mut test = new_allocate_test("8", Some("test"));
+        test.pool.allocate(4, Some("test")).unwrap();
+        test.store();
+        test.subg
+            .assert()
+            .failure()
+            .code(subg::CONFLICT)
+            .stdout("")
+            .stderr("Could not allocate subnet\nName test is already used by 10.10.0.0/28\n");
+        test.load();
+        assert_eq!(test.pool.allocated_count(), 1);
+    }
+
     #[test]
     fn allocate_multi_failure() {
         let mut test = new_allocate_test("8", Some("name-{}"));
@@ -41,7 +130,7 @@ mod allocate {
         test.subg
             .assert()
             .failure()
-            .code(exitcode::SOFTWARE)
+            .code(subg::CONFLICT)
             .stdout("")
             .stderr("Could not allocate subnet name-128\nNo space available\n");
     }
@@ -55,7 +144,7 @@ mod allocate {
         test.subg
             .assert()
             .failure()
-            .code(exitcode::SOFTWARE)
+            .code(exitcode::USAGE)
             .stdout("")
             .stderr(
                 "Could not format subnet names\n\
@@ -68,7 +157,11 @@ mod allocate {
     #[test]
     fn allocate_with_name() {
         let mut test = new_allocate_test("8", Some("test"));
-        test.subg.assert().success().stdout("").stderr("");
+        test.subg
+            .assert()
+            .success()
+            .stdout("10.10.0.0/24\n")
+            .stderr("");
         test.load();
         let subnets: Vec<&CidrRecord> = test.pool.records().collect();
         assert_eq!(subnets.len(), 1);
@@ -76,10 +169,194 @@ mod allocate {
         assert_eq!(subnets[0].cidr.to_string(), "10.10.0.0/24");
     }
 
+    #[test]
+    fn allocate_with_literal_name() {
+        let mut test = new_allocate_test("8", None);
+        test.subg.arg("--name").arg("prod web {}");
+        test.subg
+            .assert()
+            .success()
+            .stdout("10.10.0.0/24\n")
+            .stderr("");
+        test.load();
+        let subnets: Vec<&CidrRecord> = test.pool.records().collect();
+        assert_eq!(subnets.len(), 1);
+        assert_eq!(subnets[0].name.clone().unwrap(), "prod web {}");
+        assert_eq!(subnets[0].cidr.to_string(), "10.10.0.0/24");
+    }
+
+    #[test]
+    fn allocate_within() {
+        let mut test = new_allocate_test("8", Some("test"));
+        test.subg.args(["--within", "10.10.128.0/17"]);
+        test.pool.allocate(4, None).unwrap();
+        test.store();
+        test.subg
+            .assert()
+            .success()
+            .stdout("10.10.128.0/24\n")
+            .stderr("");
+        test.load();
+        assert_eq!(
+            test.pool.find_by_name("test").unwrap().to_string(),
+            "10.10.128.0/24"
+        );
+    }
+
+    #[test]
+    fn allocate_within_full() {
+        let mut test = new_allocate_test("8", Some("test"));
+        test.subg.args(["--within", "10.10.0.0/24"]);
+        test.pool.allocate(4, None).unwrap();
+        test.store();
+        test.subg
+            .assert()
+            .failure()
+            .code(subg::CONFLICT)
+            .stdout("")
+            .stderr("Could not allocate subnet\nNo space available\n");
+        test.load();
+        assert_eq!(test.pool.allocated_count(), 1);
+    }
+
+    #[test]
+    fn allocate_zero_bits() {
+        let mut test = new_allocate_test("0", Some("host"));
+        test.store();
+        test.subg
+            .assert()
+            .success()
+            .stdout("10.10.0.0\n")
+            .stderr("");
+        test.load();
+        assert_eq!(test.pool.find_by_name("host"), "10.10.0.0/32".parse().ok());
+    }
+
+    #[test]
+    fn allocate_json() {
+        let mut test = new_allocate_test("8", Some("name-{}"));
+        test.subg.args(["%0-1", "-o", "json"]);
+        test.store();
+        let output = test.subg.assert().success().get_output().stdout.clone();
+        let records: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        let records = records.as_array().unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["cidr"], "10.10.0.0/24");
+        assert_eq!(records[0]["name"], "name-0");
+        assert_eq!(records[1]["cidr"], "10.10.1.0/24");
+        assert_eq!(records[1]["name"], "name-1");
+        assert!(records[0]["created_at"].is_string());
+    }
+
+    #[test]
+    fn allocate_count_json() {
+        let mut test = new_allocate_test("4", None);
+        test.subg.args(["--count", "2", "--output", "json"]);
+        test.store();
+        let output = test.subg.assert().success().get_output().stdout.clone();
+        let records: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(
+            records
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|record| (record["cidr"].as_str().unwrap(), record.get("name")))
+                .collect::<Vec<_>>(),
+            vec![("10.10.0.0/28", None), ("10.10.0.16/28", None)]
+        );
+    }
+
+    #[test]
+    fn allocate_high() {
+        let mut test = new_allocate_test("8", Some("test"));
+        test.subg.arg("--high");
+        test.pool.allocate(4, None).unwrap();
+        test.store();
+        test.subg
+            .assert()
+            .success()
+            .stdout("10.10.255.0/24\n")
+            .stderr("");
+        test.load();
+        assert_eq!(
+            test.pool.find_by_name("test").unwrap().to_string(),
+            "10.10.255.0/24"
+        );
+        assert_eq!(
+            test.pool.allocate(8, None).unwrap().to_string(),
+            "10.10.1.0/24"
+        );
+    }
+
+    #[test]
+    fn allocate_high_multi() {
+        let mut test = new_allocate_test("8", Some("name-{}"));
+        test.subg.args(["%0-1", "--high"]);
+        test.store();
+        test.subg
+            .assert()
+            .success()
+            .stdout("10.10.255.0/24\n10.10.254.0/24\n")
+            .stderr("Allocated 2 subnets (0 skipped, 0 failed)\n");
+    }
+
+    #[test]
+    fn allocate_at_free() {
+        let mut test = new_allocate_test("8", Some("test"));
+        test.subg.args(["--at", "10.10.5.0/24"]);
+        test.pool.allocate(4, None).unwrap();
+        test.store();
+        test.subg
+            .assert()
+            .success()
+            .stdout("10.10.5.0/24\n")
+            .stderr("Claimed 10.10.5.0/24\n");
+        test.load();
+        assert_eq!(
+            test.pool.find_by_name("test").unwrap().to_string(),
+            "10.10.5.0/24"
+        );
+    }
+
+    #[test]
+    fn allocate_at_fallback() {
+        let mut test = new_allocate_test("8", Some("test"));
+        test.subg.args(["--at", "10.10.0.0/24"]);
+        test.pool.allocate(4, None).unwrap();
+        test.store();
+        test.subg
+            .assert()
+            .success()
+            .stdout("10.10.1.0/24\n")
+            .stderr("Subnet 10.10.0.0/24 is not free, allocated 10.10.1.0/24\n");
+        test.load();
+        assert_eq!(
+            test.pool.find_by_name("test").unwrap().to_string(),
+            "10.10.1.0/24"
+        );
+    }
+
+    #[test]
+    fn allocate_at_wrong_size() {
+        let mut test = new_allocate_test("8", None);
+        test.subg.args(["--at", "10.10.0.0/25"]);
+        test.store();
+        test.subg
+            .assert()
+            .failure()
+            .code(exitcode::USAGE)
+            .stdout("")
+            .stderr("Subnet 10.10.0.0/25 is not of the requested size\n");
+    }
+
     #[test]
     fn allocate_without_name() {
         let mut test = new_allocate_test("8", None);
-        test.subg.assert().success().stdout("").stderr("");
+        test.subg
+            .assert()
+            .success()
+            .stdout("10.10.0.0/24\n")
+            .stderr("");
         test.load();
         let subnets: Vec<&CidrRecord> = test.pool.records().collect();
         assert_eq!(subnets.len(), 1);
@@ -92,7 +369,11 @@ mod allocate {
         let mut test = new_allocate_test("8", Some("name-{}-{}"));
         test.subg.arg("%0-1");
         test.subg.arg("a,b");
-        test.subg.assert().success().stdout("").stderr("");
+        test.subg
+            .assert()
+            .success()
+            .stdout("10.10.0.0/24\n10.10.1.0/24\n10.10.2.0/24\n10.10.3.0/24\n")
+            .stderr("Allocated 4 subnets (0 skipped, 0 failed)\n");
         test.load();
         let subnets: Vec<&CidrRecord> = test.pool.records().collect();
         assert_eq!(subnets.len(), 4);
@@ -105,6 +386,92 @@ mod allocate {
         assert_eq!(subnets[3].name.clone().unwrap(), "name-1-b");
         assert_eq!(subnets[3].cidr.to_string(), "10.10.3.0/24");
     }
+
+    #[test]
+    fn allocate_quiet() {
+        let mut test = fixture::new_test();
+        test.store();
+        test.subg.args(["-q", "allocate", "8", "test"]);
+        test.subg.assert().success().stdout("").stderr("");
+        test.load();
+        assert_eq!(test.pool.allocated_count(), 1);
+    }
+
+    #[test]
+    fn allocate_count() {
+        let mut test = new_allocate_test("8", None);
+        test.subg.arg("--count").arg("3");
+        test.subg
+            .assert()
+            .success()
+            .stdout("10.10.0.0/24\n10.10.1.0/24\n10.10.2.0/24\n")
+            .stderr("Allocated 3 subnets (0 skipped, 0 failed)\n");
+        test.load();
+        assert_eq!(test.pool.allocated_count(), 3);
+        assert_eq!(test.pool.named_count(), 0);
+    }
+
+    #[test]
+    fn allocate_count_auto_name() {
+        let mut test = new_allocate_test("8", None);
+        test.subg.arg("--count").arg("2").arg("--auto-name");
+        test.subg
+            .assert()
+            .success()
+            .stdout("10.10.0.0/24\n10.10.1.0/24\n")
+            .stderr("Allocated 2 subnets (0 skipped, 0 failed)\n");
+        test.load();
+        assert_eq!(
+            test.pool.find_by_name("subnet-1").unwrap().to_string(),
+            "10.10.1.0/24"
+        );
+    }
+
+    #[test]
+    fn allocate_count_no_space() {
+        let mut test = new_allocate_test("14", None);
+        test.subg.arg("--count").arg("3");
+        test.pool.allocate(15, Some("existing")).unwrap();
+        test.store();
+        test.subg
+            .assert()
+            .failure()
+            .code(subg::CONFLICT)
+            .stdout("")
+            .stderr("Could not allocate subnets\nNo space available\n");
+        test.load();
+        assert_eq!(test.pool.allocated_count(), 1);
+    }
+
+    #[test]
+    fn allocate_prefix() {
+        let mut test = new_allocate_test("24", Some("test"));
+        test.subg.arg("--prefix");
+        test.subg
+            .assert()
+            .success()
+            .stdout("10.10.0.0/24\n")
+            .stderr("");
+        test.load();
+        assert_eq!(
+            test.pool.find_by_name("test").unwrap().to_string(),
+            "10.10.0.0/24"
+        );
+    }
+
+    #[test]
+    fn allocate_prefix_out_of_range() {
+        for prefix in ["15", "33"] {
+            let mut test = new_allocate_test(prefix, None);
+            test.subg.arg("--prefix-length");
+            test.subg
+                .assert()
+                .failure()
+                .code(exitcode::USAGE)
+                .stdout("")
+                .stderr(format!("Prefix length {prefix} is not between 16 and 32\n"));
+        }
+    }
 }
 
 mod free {
@@ -138,11 +505,42 @@ mod free {
         test.subg
             .assert()
             .failure()
-            .code(exitcode::SOFTWARE)
+            .code(subg::NOT_FOUND)
             .stdout("")
             .stderr("Could not free subnet 20.20.0.0/24\n");
     }
 
+    #[test]
+    fn free_missing_pool_file() {
+        let mut test = fixture::new_test();
+        test.subg.args(["free", "20.20.0.0/24"]);
+        test.subg
+            .assert()
+            .failure()
+            .code(exitcode::NOINPUT)
+            .stdout("")
+            .stderr(format!(
+                "Subnet pool file does not exist at {}\n",
+                test.pool_path.display()
+            ));
+        assert_ne!(exitcode::NOINPUT, subg::NOT_FOUND);
+    }
+
+    #[test]
+    fn free_family_mismatch() {
+        let mut test = new_free_test("1:2:3:4::/64");
+        test.store();
+        test.subg
+            .assert()
+            .failure()
+            .code(exitcode::USAGE)
+            .stdout("")
+            .stderr(
+                "Could not free subnet 1:2:3:4::/64\n\
+                 Address families do not match\n",
+            );
+    }
+
     #[test]
     fn free_success_with_name() {
         let mut test = new_free_test("test");
@@ -219,7 +617,7 @@ mod claim {
         test.subg
             .assert()
             .failure()
-            .code(exitcode::SOFTWARE)
+            .code(subg::CONFLICT)
             .stdout("")
             .stderr(
                 "Could not claim subnet\n\
@@ -227,10 +625,48 @@ mod claim {
             );
     }
 
+    #[test]
+    fn claim_overlap() {
+        let mut test = new_claim_test("10.10.0.0/24", None);
+        test.pool
+            .claim(&"10.10.0.0/25".parse().unwrap(), None)
+            .unwrap();
+        test.store();
+        test.subg
+            .assert()
+            .failure()
+            .code(subg::CONFLICT)
+            .stdout("")
+            .stderr(
+                "Could not claim subnet\n\
+                 10.10.0.0/24 overlaps 10.10.0.0/25; try 10.10.1.0/24\n",
+            );
+    }
+
+    #[test]
+    fn claim_overlap_full() {
+        let mut test = new_claim_test("10.10.0.0/24", None);
+        test.pool.allocate(16, Some("everything")).unwrap();
+        test.store();
+        test.subg
+            .assert()
+            .failure()
+            .code(subg::CONFLICT)
+            .stdout("")
+            .stderr(
+                "Could not claim subnet\n\
+                 10.10.0.0/24 overlaps 10.10.0.0/16\n",
+            );
+    }
+
     #[test]
     fn unnamed() {
         let mut test = new_claim_test("10.10.0.0/24", None);
-        test.subg.assert().success().stdout("").stderr("");
+        test.subg
+            .assert()
+            .success()
+            .stdout("10.10.0.0/24\n")
+            .stderr("");
         test.load();
         let subnets: Vec<&CidrRecord> = test.pool.records().collect();
         assert_eq!(subnets.len(), 1);
@@ -241,24 +677,345 @@ mod claim {
     #[test]
     fn named() {
         let mut test = new_claim_test("10.10.0.0/24", Some("test"));
-        test.subg.assert().success().stdout("").stderr("");
+        test.subg
+            .assert()
+            .success()
+            .stdout("10.10.0.0/24\n")
+            .stderr("");
         test.load();
         let subnets: Vec<&CidrRecord> = test.pool.records().collect();
         assert_eq!(subnets.len(), 1);
         assert_eq!(subnets[0].name.clone().unwrap(), "test");
         assert_eq!(subnets[0].cidr.to_string(), "10.10.0.0/24");
     }
-}
 
-mod rename {
-    use super::*;
-    use subnet_garden_core::CidrRecord;
-    fn new_rename_test(identifier: &str, name: Option<&str>) -> Test {
+    #[test]
+    fn quiet() {
         let mut test = fixture::new_test();
         test.store();
-        test.subg.arg("rename").arg(identifier);
-        if let Some(name) = name {
-            test.subg.arg(name);
+        test.subg.args(["--quiet", "claim", "10.10.0.0/24", "test"]);
+        test.subg.assert().success().stdout("").stderr("");
+        test.load();
+        assert_eq!(test.pool.allocated_count(), 1);
+    }
+
+    fn new_claim_from_test(content: &str) -> Test {
+        let mut test = fixture::new_test();
+        test.store();
+        let from = test.pool_path.with_file_name("claims.txt");
+        std::fs::write(&from, content).unwrap();
+        test.subg.arg("claim").arg("--from").arg(from);
+        test
+    }
+
+    #[test]
+    fn from_file() {
+        let mut test =
+            new_claim_from_test("10.10.0.0/24 test1\n10.10.1.0/24\n10.10.2.0/24 test lab\n");
+        test.subg
+            .assert()
+            .success()
+            .stdout("10.10.0.0/24\n10.10.1.0/24\n10.10.2.0/24\n")
+            .stderr("Claimed 3 subnets (0 skipped, 0 failed)\n");
+        test.load();
+        let subnets: Vec<(String, Option<String>)> = test
+            .pool
+            .records()
+            .map(|record| (record.cidr.to_string(), record.name.clone()))
+            .collect();
+        assert_eq!(
+            subnets,
+            vec![
+                ("10.10.0.0/24".to_string(), Some("test1".to_string())),
+                ("10.10.1.0/24".to_string(), None),
+                ("10.10.2.0/24".to_string(), Some("test lab".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn from_file_with_comments() {
+        let mut test = new_claim_from_test(
+            "# lab subnets\n\n10.10.0.0/24 test1\n   \n  # unnamed\n10.10.1.0/24\n",
+        );
+        test.subg
+            .assert()
+            .success()
+            .stdout("10.10.0.0/24\n10.10.1.0/24\n")
+            .stderr("Claimed 2 subnets (0 skipped, 0 failed)\n");
+        test.load();
+        assert_eq!(test.pool.allocated_count(), 2);
+        assert_eq!(
+            test.pool.find_by_name("test1"),
+            Some("10.10.0.0/24".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn from_file_already_claimed() {
+        let mut test = new_claim_from_test("10.10.0.0/24 test1\n10.10.1.0/24 test2\n");
+        test.pool
+            .claim(&"10.10.0.0/24".parse().unwrap(), Some("test1"))
+            .unwrap();
+        test.store();
+        test.subg
+            .assert()
+            .success()
+            .stdout("10.10.1.0/24\n")
+            .stderr("Claimed 1 subnet (1 skipped, 0 failed)\n");
+        test.load();
+        assert_eq!(test.pool.allocated_count(), 2);
+    }
+
+    #[test]
+    fn from_file_with_conflict() {
+        let mut test = new_claim_from_test(
+            "10.10.0.0/24 test1\n10.10.0.0/25 test2\nbad-cidr\n10.10.1.0/24 test3\n",
+        );
+        test.subg
+            .assert()
+            .failure()
+            .code(exitcode::DATAERR)
+            .stdout("10.10.0.0/24\n10.10.1.0/24\n")
+            .stderr(
+                "Claimed 2 subnets (0 skipped, 2 failed)\n\
+                 Line 2: 10.10.0.0/25 test2: No space available\n\
+                 Line 3: bad-cidr: couldn't parse address in network: invalid IP address syntax\n",
+            );
+        test.load();
+        assert_eq!(test.pool.allocated_count(), 2);
+        assert_eq!(test.pool.find_by_name("test2"), None);
+        assert_eq!(
+            test.pool.find_by_name("test3"),
+            Some("10.10.1.0/24".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn from_missing_file() {
+        let mut test = fixture::new_test();
+        test.store();
+        test.subg.args(["claim", "--from", "does-not-exist.txt"]);
+        test.subg
+            .assert()
+            .failure()
+            .code(exitcode::NOINPUT)
+            .stdout("")
+            .stderr(predicates::str::starts_with(
+                "Could not read does-not-exist.txt\n",
+            ));
+    }
+}
+
+mod apply {
+    use super::*;
+    use std::str::FromStr;
+    use subnet_garden_core::pool::SubnetPool;
+
+    fn cidr(cidr: &str) -> cidr::IpCidr {
+        cidr::IpCidr::from_str(cidr).unwrap()
+    }
+
+    /// Pool holding `10.10.0.0/24` as `web` and `10.10.1.0/24` as `db`,
+    /// applied against the desired subnets.
+    fn new_apply_test(desired: &[(&str, Option<&str>)], args: &[&str]) -> Test {
+        let mut test = fixture::new_test();
+        test.pool.claim(&cidr("10.10.0.0/24"), Some("web")).unwrap();
+        test.pool.claim(&cidr("10.10.1.0/24"), Some("db")).unwrap();
+        test.store();
+
+        let mut desired_pool = SubnetPool::new(fixture::TEST_CIDR.parse().unwrap());
+        for (desired_cidr, name) in desired {
+            desired_pool.claim(&cidr(desired_cidr), *name).unwrap();
+        }
+        let desired_path = test.pool_path.with_file_name("desired.yaml");
+        subg::store_pool(desired_path.to_str().unwrap(), &desired_pool);
+        test.subg.arg("apply").arg(desired_path).args(args);
+        test
+    }
+
+    #[test]
+    fn add_only() {
+        let mut test = new_apply_test(
+            &[("10.10.0.0/24", Some("web")), ("10.10.2.0/24", Some("lab"))],
+            &[],
+        );
+        test.subg
+            .assert()
+            .success()
+            .stdout("claim 10.10.2.0/24 lab\n")
+            .stderr("");
+        test.load();
+        assert_eq!(test.pool.find_by_name("lab"), Some(cidr("10.10.2.0/24")));
+        assert_eq!(test.pool.find_by_name("db"), Some(cidr("10.10.1.0/24")));
+    }
+
+    #[test]
+    fn rename() {
+        let mut test = new_apply_test(
+            &[
+                ("10.10.0.0/24", Some("db")),
+                ("10.10.1.0/24", Some("web")),
+                ("10.10.2.0/24", None),
+            ],
+            &[],
+        );
+        test.subg
+            .assert()
+            .success()
+            .stdout(
+                "rename 10.10.0.0/24 web -> db\n\
+                 rename 10.10.1.0/24 db -> web\n\
+                 claim 10.10.2.0/24 -\n",
+            )
+            .stderr("");
+        test.load();
+        assert_eq!(test.pool.find_by_name("db"), Some(cidr("10.10.0.0/24")));
+        assert_eq!(test.pool.find_by_name("web"), Some(cidr("10.10.1.0/24")));
+        assert!(test.pool.contains(&cidr("10.10.2.0/24")));
+    }
+
+    #[test]
+    fn prune() {
+        let mut test = new_apply_test(&[("10.10.0.0/23", Some("db"))], &["--prune"]);
+        test.subg
+            .assert()
+            .success()
+            .stdout(
+                "free 10.10.0.0/24 web\n\
+                 free 10.10.1.0/24 db\n\
+                 claim 10.10.0.0/23 db\n",
+            )
+            .stderr("");
+        test.load();
+        assert_eq!(test.pool.allocated_count(), 1);
+        assert_eq!(test.pool.find_by_name("db"), Some(cidr("10.10.0.0/23")));
+    }
+
+    #[test]
+    fn dry_run() {
+        let mut test = new_apply_test(&[("10.10.2.0/24", Some("lab"))], &["--prune", "--dry-run"]);
+        test.subg
+            .assert()
+            .success()
+            .stdout(
+                "free 10.10.0.0/24 web\n\
+                 free 10.10.1.0/24 db\n\
+                 claim 10.10.2.0/24 lab\n",
+            )
+            .stderr("");
+        test.load();
+        assert_eq!(test.pool.allocated_count(), 2);
+        assert_eq!(test.pool.find_by_name("lab"), None);
+    }
+
+    #[test]
+    fn conflict() {
+        let mut test = new_apply_test(&[("10.10.0.0/23", Some("lab"))], &[]);
+        test.subg
+            .assert()
+            .failure()
+            .code(subg::CONFLICT)
+            .stdout("")
+            .stderr("Could not claim 10.10.0.0/23\nNo space available\n");
+        test.load();
+        assert_eq!(test.pool.allocated_count(), 2);
+        assert_eq!(test.pool.find_by_name("lab"), None);
+    }
+}
+
+mod plan {
+    use super::*;
+
+    fn new_plan_test(args: &[&str]) -> Test {
+        let mut test = fixture::new_test();
+        test.store();
+        test.subg.arg("plan").args(args);
+        test
+    }
+
+    #[test]
+    fn success() {
+        let mut test = new_plan_test(&["100:lab", "500:web", "250"]);
+        test.subg
+            .assert()
+            .success()
+            .stdout(
+                "10.10.3.0/25 lab\n\
+                 10.10.0.0/23 web\n\
+                 10.10.2.0/24 -\n",
+            )
+            .stderr("");
+        test.load();
+        assert_eq!(test.pool.allocated_count(), 3);
+        assert_eq!(
+            test.pool.find_by_name("web"),
+            Some("10.10.0.0/23".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn from_file() {
+        let mut test = fixture::new_test();
+        test.store();
+        let requirements = test.pool_path.with_file_name("requirements.txt");
+        std::fs::write(&requirements, "# hosts name\n500 web\n\n100 lab\n").unwrap();
+        test.subg.arg("plan").arg("--from").arg(&requirements);
+        test.subg
+            .assert()
+            .success()
+            .stdout("10.10.0.0/23 web\n10.10.2.0/25 lab\n")
+            .stderr("");
+        test.load();
+        assert_eq!(test.pool.named_count(), 2);
+    }
+
+    #[test]
+    fn dry_run() {
+        let mut test = new_plan_test(&["--dry-run", "500:web"]);
+        test.subg
+            .assert()
+            .success()
+            .stdout("10.10.0.0/23 web\n")
+            .stderr("");
+        test.load();
+        assert_eq!(test.pool.allocated_count(), 0);
+    }
+
+    #[test]
+    fn does_not_fit() {
+        let mut test = new_plan_test(&["40000", "40000"]);
+        test.subg
+            .assert()
+            .failure()
+            .code(subg::CONFLICT)
+            .stdout("")
+            .stderr("Could not plan subnets\nNo space available\n");
+        test.load();
+        assert_eq!(test.pool.allocated_count(), 0);
+    }
+
+    #[test]
+    fn invalid_requirement() {
+        let mut test = new_plan_test(&["many:web"]);
+        test.subg
+            .assert()
+            .failure()
+            .code(fixture::HELP_EXIT_CODE)
+            .stdout("")
+            .stderr(predicates::str::contains("Invalid number of hosts: many"));
+    }
+}
+
+mod rename {
+    use super::*;
+    use subnet_garden_core::CidrRecord;
+    fn new_rename_test(identifier: &str, name: Option<&str>) -> Test {
+        let mut test = fixture::new_test();
+        test.store();
+        test.subg.arg("rename").arg(identifier);
+        if let Some(name) = name {
+            test.subg.arg(name);
         }
         test
     }
@@ -277,6 +1034,18 @@ mod rename {
             );
     }
 
+    #[test]
+    fn rename_not_found() {
+        let mut test = new_rename_test("10.20.0.0/24", Some("test"));
+        test.store();
+        test.subg
+            .assert()
+            .failure()
+            .code(subg::NOT_FOUND)
+            .stdout("")
+            .stderr("Could not rename subnet\nNo such object\n");
+    }
+
     #[test]
     fn rename_failure() {
         let mut test = new_rename_test("10.10.0.0/24", Some("test"));
@@ -286,9 +1055,9 @@ mod rename {
         test.subg
             .assert()
             .failure()
-            .code(exitcode::SOFTWARE)
+            .code(subg::CONFLICT)
             .stdout("")
-            .stderr("Could not rename subnet\nDuplicate name\n");
+            .stderr("Could not rename subnet\nName test is already used by 10.10.0.0/28\n");
     }
 
     #[test]
@@ -318,6 +1087,196 @@ mod rename {
     }
 }
 
+mod lock {
+    use super::*;
+    use std::str::FromStr;
+
+    fn cidr() -> cidr::IpCidr {
+        cidr::IpCidr::from_str("10.10.0.0/28").unwrap()
+    }
+
+    fn new_lock_test(args: &[&str], locked: bool) -> Test {
+        let mut test = fixture::new_test();
+        test.pool.allocate(4, Some("test")).unwrap();
+        test.pool.set_locked(&cidr(), locked).unwrap();
+        test.store();
+        test.subg.args(args);
+        test
+    }
+
+    fn is_locked(test: &Test) -> bool {
+        test.pool.records().next().unwrap().locked
+    }
+
+    #[test]
+    fn lock() {
+        let mut test = new_lock_test(&["lock", "test"], false);
+        test.subg.assert().success().stdout("").stderr("");
+        test.load();
+        assert!(is_locked(&test));
+    }
+
+    #[test]
+    fn unlock() {
+        let mut test = new_lock_test(&["unlock", "10.10.0.0/28"], true);
+        test.subg.assert().success().stdout("").stderr("");
+        test.load();
+        assert!(!is_locked(&test));
+    }
+
+    #[test]
+    fn lock_not_found() {
+        let mut test = new_lock_test(&["lock", "10.10.1.0/28"], false);
+        test.subg
+            .assert()
+            .failure()
+            .code(subg::NOT_FOUND)
+            .stdout("")
+            .stderr("Could not lock subnet 10.10.1.0/28\nNo such object\n");
+    }
+
+    #[test]
+    fn free_locked() {
+        let mut test = new_lock_test(&["free", "test"], true);
+        test.subg
+            .assert()
+            .failure()
+            .code(subg::CONFLICT)
+            .stdout("")
+            .stderr("Could not free subnet 10.10.0.0/28\nSubnet is locked\n");
+        test.load();
+        assert_eq!(test.pool.find_by_name("test"), Some(cidr()));
+    }
+
+    #[test]
+    fn free_locked_with_force() {
+        let mut test = new_lock_test(&["free", "test", "--force"], true);
+        test.subg.assert().success().stdout("").stderr("");
+        test.load();
+        assert_eq!(test.pool.allocated_count(), 0);
+    }
+
+    #[test]
+    fn rename_locked() {
+        let mut test = new_lock_test(&["rename", "test", "test2"], true);
+        test.subg
+            .assert()
+            .failure()
+            .code(subg::CONFLICT)
+            .stdout("")
+            .stderr("Could not rename subnet\nSubnet is locked\n");
+        test.load();
+        assert_eq!(test.pool.find_by_name("test"), Some(cidr()));
+    }
+
+    #[test]
+    fn rename_locked_with_force() {
+        let mut test = new_lock_test(&["rename", "test", "test2", "--force"], true);
+        test.subg.assert().success().stdout("").stderr("");
+        test.load();
+        assert_eq!(test.pool.find_by_name("test2"), Some(cidr()));
+        assert!(is_locked(&test));
+    }
+}
+
+mod renumber {
+    use super::*;
+    use subnet_garden_core::CidrRecord;
+    fn new_renumber_test(cidr: &str) -> Test {
+        let mut test = fixture::new_test();
+        test.store();
+        test.subg.arg("renumber").arg(cidr);
+        test
+    }
+
+    #[test]
+    fn success() {
+        let mut test = new_renumber_test("10.20.0.0/16");
+        test.pool.allocate(4, Some("test1")).unwrap();
+        test.pool.allocate(6, None).unwrap();
+        test.store();
+        test.subg.assert().success().stdout("").stderr("");
+        test.load();
+        assert_eq!(test.pool.cidr().to_string(), "10.20.0.0/16");
+        let subnets: Vec<&CidrRecord> = test.pool.records().collect();
+        assert_eq!(subnets.len(), 2);
+        assert_eq!(subnets[0].name.clone().unwrap(), "test1");
+        assert_eq!(subnets[0].cidr.to_string(), "10.20.0.0/28");
+        assert_eq!(subnets[1].name, None);
+        assert_eq!(subnets[1].cidr.to_string(), "10.20.0.64/26");
+    }
+
+    #[test]
+    fn size_mismatch() {
+        let mut test = new_renumber_test("10.20.0.0/24");
+        test.subg
+            .assert()
+            .failure()
+            .code(exitcode::USAGE)
+            .stdout("")
+            .stderr("Could not renumber pool\nSubnet sizes do not match\n");
+    }
+}
+
+mod split {
+    use super::*;
+    use subnet_garden_core::CidrRecord;
+    fn new_split_test(cidr: &str, bits: &str, name_format: Option<&str>) -> Test {
+        let mut test = fixture::new_test();
+        test.store();
+        test.subg.arg("split").arg(cidr).arg(bits);
+        if let Some(name_format) = name_format {
+            test.subg.arg(name_format);
+        }
+        test
+    }
+
+    #[test]
+    fn named_children() {
+        let mut test = new_split_test("10.10.0.0/24", "6", Some("child-{}"));
+        test.subg.assert().success().stdout("").stderr("");
+        test.load();
+        let subnets: Vec<&CidrRecord> = test.pool.records().collect();
+        assert_eq!(subnets.len(), 4);
+        for (index, subnet) in subnets.iter().enumerate() {
+            assert_eq!(subnet.name.clone().unwrap(), format!("child-{index}"));
+            assert_eq!(
+                subnet.cidr.to_string(),
+                format!("10.10.0.{}/26", index * 64)
+            );
+        }
+    }
+
+    #[test]
+    fn allocated_parent() {
+        let mut test = new_split_test("10.10.0.0/24", "7", None);
+        test.pool.allocate(8, Some("parent")).unwrap();
+        test.store();
+        test.subg.assert().success().stdout("").stderr("");
+        test.load();
+        let subnets: Vec<&CidrRecord> = test.pool.records().collect();
+        assert_eq!(subnets.len(), 2);
+        assert_eq!(subnets[0].name, None);
+        assert_eq!(subnets[0].cidr.to_string(), "10.10.0.0/25");
+        assert_eq!(subnets[1].name, None);
+        assert_eq!(subnets[1].cidr.to_string(), "10.10.0.128/25");
+        assert_eq!(test.pool.find_by_name("parent"), None);
+    }
+
+    #[test]
+    fn overlapping_parent() {
+        let mut test = new_split_test("10.10.0.0/24", "6", Some("child-{}"));
+        test.pool.allocate(4, Some("existing")).unwrap();
+        test.store();
+        test.subg
+            .assert()
+            .failure()
+            .code(subg::CONFLICT)
+            .stdout("")
+            .stderr("Could not split subnet\nNo space available\n");
+    }
+}
+
 mod max_available {
     use super::*;
     fn new_max_available_test() -> Test {
@@ -346,4 +1305,125 @@ mod max_available {
         test.store();
         test.subg.assert().success().stdout("13\n").stderr("");
     }
+
+    #[test]
+    fn all_no_subnets() {
+        let mut test = new_max_available_test();
+        test.subg.arg("--all");
+        test.subg
+            .assert()
+            .success()
+            .stdout(predicates::str::starts_with("16: 1\n15: 2\n14: 4\n"))
+            .stdout(predicates::str::ends_with("1: 32768\n0: 65536\n"))
+            .stderr("");
+    }
+
+    #[test]
+    fn all_has_subnets() {
+        let mut test = new_max_available_test();
+        test.subg.arg("--all");
+        test.pool.allocate(14, Some("test1")).unwrap();
+        test.pool.allocate(13, Some("test2")).unwrap();
+        test.store();
+        test.subg
+            .assert()
+            .success()
+            .stdout(predicates::str::starts_with(
+                "15: 1\n14: 2\n13: 5\n12: 10\n",
+            ))
+            .stdout(predicates::str::ends_with("0: 40960\n"))
+            .stderr("");
+    }
+
+    #[test]
+    fn all_full() {
+        let mut test = new_max_available_test();
+        test.subg.arg("--all");
+        test.pool.allocate(16, Some("test1")).unwrap();
+        test.store();
+        test.subg.assert().success().stdout("0: 0\n").stderr("");
+    }
+
+    #[test]
+    fn prefix() {
+        let mut test = new_max_available_test();
+        test.subg.arg("--prefix");
+        test.pool.allocate(4, Some("test1")).unwrap();
+        test.store();
+        test.subg.assert().success().stdout("17\n").stderr("");
+    }
+
+    #[test]
+    fn all_prefix() {
+        let mut test = new_max_available_test();
+        test.subg.arg("--all").arg("--prefix");
+        test.subg
+            .assert()
+            .success()
+            .stdout(predicates::str::starts_with("16: 1\n17: 2\n18: 4\n"))
+            .stdout(predicates::str::ends_with("31: 32768\n32: 65536\n"))
+            .stderr("");
+    }
+}
+
+mod stats {
+    use super::*;
+
+    fn new_stats_test(args: &[&str]) -> Test {
+        let mut test = fixture::new_test();
+        test.pool.allocate(14, Some("test1")).unwrap();
+        test.pool.allocate(12, None).unwrap();
+        test.store();
+        test.subg.arg("stats").args(args);
+        test
+    }
+
+    #[test]
+    fn text() {
+        let mut test = new_stats_test(&[]);
+        test.subg
+            .assert()
+            .success()
+            .stdout(
+                "cidr = 10.10.0.0/16\n\
+                 allocated_count = 2\n\
+                 named_count = 1\n\
+                 utilization = 0.3125\n\
+                 max_available_bits = 15\n\
+                 free_block_count = 3\n",
+            )
+            .stderr("");
+    }
+
+    #[test]
+    fn json() {
+        let mut test = new_stats_test(&["-o", "json"]);
+        let output = test.subg.assert().success().get_output().stdout.clone();
+        let summary: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(
+            summary,
+            serde_json::json!({
+                "cidr": "10.10.0.0/16",
+                "allocated_count": 2,
+                "named_count": 1,
+                "utilization": 0.3125,
+                "max_available_bits": 15,
+                "free_block_count": 3,
+            })
+        );
+    }
+}
+
+mod validate {
+    use super::*;
+
+    #[test]
+    fn valid() {
+        let mut test = fixture::new_test();
+        test.pool.allocate(4, Some("test1")).unwrap();
+        test.pool.allocate(8, None).unwrap();
+        test.store();
+        test.subg.arg("validate");
+        test.subg.assert().success().stdout("").stderr("");
+    }
 }
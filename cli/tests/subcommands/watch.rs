@@ -0,0 +1,86 @@
+// Copyright 2024 The Milton Hirsch Institute, B.V.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::fixture;
+use crate::fixture::Test;
+use assert_cmd::prelude::*;
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+struct Watch {
+    child: Child,
+    lines: mpsc::Receiver<String>,
+}
+
+impl Watch {
+    fn next_line(&self) -> String {
+        self.lines.recv_timeout(TIMEOUT).expect("watch output")
+    }
+}
+
+impl Drop for Watch {
+    fn drop(&mut self) {
+        self.child.kill().unwrap();
+        self.child.wait().unwrap();
+    }
+}
+
+fn new_watch_test() -> Test {
+    let mut test = fixture::new_test();
+    test.pool.allocate(4, Some("test0")).unwrap();
+    test.store();
+    test
+}
+
+fn spawn_watch(test: &Test, listing: &str) -> Watch {
+    let mut child = Command::cargo_bin(subg::SUBG_COMMAND)
+        .unwrap()
+        .args(["--pool-path", test.pool_path.to_str().unwrap()])
+        .args(["watch", listing])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let stdout = child.stdout.take().unwrap();
+    let (sender, lines) = mpsc::channel();
+    thread::spawn(move || {
+        for line in BufReader::new(stdout).lines() {
+            if sender.send(line.unwrap()).is_err() {
+                break;
+            }
+        }
+    });
+    Watch { child, lines }
+}
+
+#[test]
+fn renders_on_change() {
+    let mut test = new_watch_test();
+    let watch = spawn_watch(&test, "cidrs");
+    assert_eq!(watch.next_line(), "10.10.0.0/28");
+
+    test.pool.allocate(4, None).unwrap();
+    test.store();
+    assert_eq!(watch.next_line(), "");
+    assert_eq!(watch.next_line(), "10.10.0.0/28");
+    assert_eq!(watch.next_line(), "10.10.0.16/28");
+}
+
+#[test]
+fn renders_on_replace() {
+    let mut test = new_watch_test();
+    let watch = spawn_watch(&test, "names");
+    assert_eq!(watch.next_line(), "test0");
+
+    let replacement = test.pool_path.with_extension("tmp.yaml");
+    test.pool.allocate(4, Some("test1")).unwrap();
+    subg::store_pool(replacement.to_str().unwrap(), &test.pool);
+    std::fs::rename(&replacement, test.pool_path.path()).unwrap();
+    assert_eq!(watch.next_line(), "");
+    assert_eq!(watch.next_line(), "test0");
+    assert_eq!(watch.next_line(), "test1");
+}
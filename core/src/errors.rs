@@ -1,17 +1,20 @@
 // Copyright 2023 The Milton Hirsch Institute, B.V.
 // SPDX-License-Identifier: Apache-2.0
 
+use cidr::IpCidr;
 use std::error::Error;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum CreateError {
     DuplicateObject,
+    InvalidCidr(String),
 }
 
 impl std::fmt::Display for CreateError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
             CreateError::DuplicateObject => write!(f, "Duplicate object"),
+            CreateError::InvalidCidr(message) => write!(f, "Invalid CIDR: {message}"),
         }
     }
 }
@@ -44,14 +47,30 @@ impl Error for DeleteError {
 #[derive(Debug, Clone, PartialEq)]
 pub enum AllocateError {
     DuplicateName,
+    FamilyMismatch,
+    Locked,
     NoSpaceAvailable,
+    NoSuchObject,
+    NotSiblings,
+    PrefixTooLong,
+    PrefixTooShort,
+    SizeMismatch,
 }
 
 impl std::fmt::Display for AllocateError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
             AllocateError::DuplicateName => write!(f, "Duplicate name"),
+            AllocateError::FamilyMismatch => write!(f, "Address families do not match"),
+            AllocateError::Locked => write!(f, "Subnet is locked"),
             AllocateError::NoSpaceAvailable => write!(f, "No space available"),
+            AllocateError::NoSuchObject => write!(f, "No such object"),
+            AllocateError::NotSiblings => write!(f, "Subnets are not siblings"),
+            AllocateError::PrefixTooLong => {
+                write!(f, "Prefix is longer than the address length")
+            }
+            AllocateError::PrefixTooShort => write!(f, "Prefix is shorter than the pool prefix"),
+            AllocateError::SizeMismatch => write!(f, "Subnet sizes do not match"),
         }
     }
 }
@@ -65,6 +84,8 @@ impl Error for AllocateError {
 #[derive(Debug, Clone, PartialEq)]
 pub enum RenameError {
     DuplicateName,
+    FamilyMismatch,
+    Locked,
     NoSuchObject,
 }
 
@@ -72,6 +93,8 @@ impl std::fmt::Display for RenameError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
             RenameError::DuplicateName => write!(f, "Duplicate name"),
+            RenameError::FamilyMismatch => write!(f, "Address families do not match"),
+            RenameError::Locked => write!(f, "Subnet is locked"),
             RenameError::NoSuchObject => write!(f, "No such object"),
         }
     }
@@ -82,6 +105,163 @@ impl Error for RenameError {
     }
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum FormatError {
+    Parse(String),
+    UnknownField(String),
+}
+
+impl std::fmt::Display for FormatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            FormatError::Parse(message) => write!(f, "Invalid format: {message}"),
+            FormatError::UnknownField(name) => write!(f, "Unknown field: {name}"),
+        }
+    }
+}
+
+impl Error for FormatError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        None
+    }
+}
+
+/// A broken invariant of a pool, as reported by `SubnetPool::validate`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationError {
+    CountMismatch { counted: usize, found: usize },
+    OutOfRoot(IpCidr),
+    Overlap(IpCidr, IpCidr),
+    StaleName { name: String, cidr: IpCidr },
+    UnindexedName { name: String, cidr: IpCidr },
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ValidationError::CountMismatch { counted, found } => {
+                write!(
+                    f,
+                    "Allocated count {counted} does not match {found} subnets"
+                )
+            }
+            ValidationError::OutOfRoot(cidr) => write!(f, "Subnet {cidr} is outside the pool"),
+            ValidationError::Overlap(a, b) => write!(f, "Subnets {a} and {b} overlap"),
+            ValidationError::StaleName { name, cidr } => {
+                write!(f, "Name {name} refers to {cidr}, which does not have it")
+            }
+            ValidationError::UnindexedName { name, cidr } => {
+                write!(f, "Name {name} of subnet {cidr} is not indexed")
+            }
+        }
+    }
+}
+
+impl Error for ValidationError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        None
+    }
+}
+
+/// Failure to read or write a pool in one of the formats of
+/// `storage::Format`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StorageError {
+    Io(String),
+    Parse(String),
+    Serialize(String),
+}
+
+impl std::fmt::Display for StorageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            StorageError::Io(message)
+            | StorageError::Parse(message)
+            | StorageError::Serialize(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl Error for StorageError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        None
+    }
+}
+
+/// Any error of the pool operations, for callers that propagate errors
+/// from several kinds of operation with `?`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SubgError {
+    Allocate(AllocateError),
+    Create(CreateError),
+    Delete(DeleteError),
+    Format(FormatError),
+    Rename(RenameError),
+    Storage(StorageError),
+}
+
+impl std::fmt::Display for SubgError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SubgError::Allocate(err) => err.fmt(f),
+            SubgError::Create(err) => err.fmt(f),
+            SubgError::Delete(err) => err.fmt(f),
+            SubgError::Format(err) => err.fmt(f),
+            SubgError::Rename(err) => err.fmt(f),
+            SubgError::Storage(err) => err.fmt(f),
+        }
+    }
+}
+
+impl Error for SubgError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SubgError::Allocate(err) => Some(err),
+            SubgError::Create(err) => Some(err),
+            SubgError::Delete(err) => Some(err),
+            SubgError::Format(err) => Some(err),
+            SubgError::Rename(err) => Some(err),
+            SubgError::Storage(err) => Some(err),
+        }
+    }
+}
+
+impl From<AllocateError> for SubgError {
+    fn from(err: AllocateError) -> Self {
+        SubgError::Allocate(err)
+    }
+}
+
+impl From<CreateError> for SubgError {
+    fn from(err: CreateError) -> Self {
+        SubgError::Create(err)
+    }
+}
+
+impl From<DeleteError> for SubgError {
+    fn from(err: DeleteError) -> Self {
+        SubgError::Delete(err)
+    }
+}
+
+impl From<FormatError> for SubgError {
+    fn from(err: FormatError) -> Self {
+        SubgError::Format(err)
+    }
+}
+
+impl From<RenameError> for SubgError {
+    fn from(err: RenameError) -> Self {
+        SubgError::Rename(err)
+    }
+}
+
+impl From<StorageError> for SubgError {
+    fn from(err: StorageError) -> Self {
+        SubgError::Storage(err)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -94,8 +274,174 @@ mod tests {
         );
     }
 
+    #[test]
+    fn display_create_error_invalid_cidr() {
+        assert_eq!(
+            format!("{}", CreateError::InvalidCidr("bad".to_string())),
+            "Invalid CIDR: bad"
+        );
+    }
+
     #[test]
     fn display_delete_error_no_such_object() {
         assert_eq!(format!("{}", DeleteError::NoSuchObject), "No such object");
     }
+
+    #[test]
+    fn display_allocate_error_not_siblings() {
+        assert_eq!(
+            format!("{}", AllocateError::NotSiblings),
+            "Subnets are not siblings"
+        );
+    }
+
+    #[test]
+    fn display_allocate_error_prefix() {
+        assert_eq!(
+            format!("{}", AllocateError::PrefixTooLong),
+            "Prefix is longer than the address length"
+        );
+        assert_eq!(
+            format!("{}", AllocateError::PrefixTooShort),
+            "Prefix is shorter than the pool prefix"
+        );
+    }
+
+    #[test]
+    fn display_allocate_error_family_mismatch() {
+        assert_eq!(
+            format!("{}", AllocateError::FamilyMismatch),
+            "Address families do not match"
+        );
+    }
+
+    #[test]
+    fn display_rename_error_family_mismatch() {
+        assert_eq!(
+            format!("{}", RenameError::FamilyMismatch),
+            "Address families do not match"
+        );
+    }
+
+    #[test]
+    fn display_format_error() {
+        assert_eq!(
+            format!(
+                "{}",
+                FormatError::Parse("Unexpected end of format".to_string())
+            ),
+            "Invalid format: Unexpected end of format"
+        );
+        assert_eq!(
+            format!("{}", FormatError::UnknownField("size".to_string())),
+            "Unknown field: size"
+        );
+    }
+
+    #[test]
+    fn subg_error_from() {
+        assert_eq!(
+            SubgError::from(AllocateError::NoSpaceAvailable),
+            SubgError::Allocate(AllocateError::NoSpaceAvailable)
+        );
+        assert_eq!(
+            SubgError::from(CreateError::DuplicateObject),
+            SubgError::Create(CreateError::DuplicateObject)
+        );
+        assert_eq!(
+            SubgError::from(DeleteError::NoSuchObject),
+            SubgError::Delete(DeleteError::NoSuchObject)
+        );
+        assert_eq!(
+            SubgError::from(FormatError::UnknownField("size".to_string())),
+            SubgError::Format(FormatError::UnknownField("size".to_string()))
+        );
+        assert_eq!(
+            SubgError::from(RenameError::DuplicateName),
+            SubgError::Rename(RenameError::DuplicateName)
+        );
+    }
+
+    #[test]
+    fn subg_error_propagates() {
+        fn allocate_then_rename() -> Result<(), SubgError> {
+            Err(AllocateError::NoSpaceAvailable)?;
+            Err(RenameError::DuplicateName)?
+        }
+        assert_eq!(
+            allocate_then_rename(),
+            Err(SubgError::Allocate(AllocateError::NoSpaceAvailable))
+        );
+    }
+
+    #[test]
+    fn display_subg_error() {
+        assert_eq!(
+            format!("{}", SubgError::from(AllocateError::NoSpaceAvailable)),
+            "No space available"
+        );
+        assert_eq!(
+            format!("{}", SubgError::from(CreateError::DuplicateObject)),
+            "Duplicate object"
+        );
+        assert_eq!(
+            format!("{}", SubgError::from(DeleteError::NoSuchObject)),
+            "No such object"
+        );
+        assert_eq!(
+            format!(
+                "{}",
+                SubgError::from(FormatError::UnknownField("size".to_string()))
+            ),
+            "Unknown field: size"
+        );
+        assert_eq!(
+            format!("{}", SubgError::from(RenameError::DuplicateName)),
+            "Duplicate name"
+        );
+    }
+
+    #[test]
+    fn display_validation_error() {
+        let a = "10.20.0.0/24".parse().unwrap();
+        let b = "10.20.0.0/25".parse().unwrap();
+        assert_eq!(
+            format!(
+                "{}",
+                ValidationError::CountMismatch {
+                    counted: 2,
+                    found: 1
+                }
+            ),
+            "Allocated count 2 does not match 1 subnets"
+        );
+        assert_eq!(
+            format!("{}", ValidationError::OutOfRoot(a)),
+            "Subnet 10.20.0.0/24 is outside the pool"
+        );
+        assert_eq!(
+            format!("{}", ValidationError::Overlap(a, b)),
+            "Subnets 10.20.0.0/24 and 10.20.0.0/25 overlap"
+        );
+        assert_eq!(
+            format!(
+                "{}",
+                ValidationError::StaleName {
+                    name: "a-name".to_string(),
+                    cidr: a
+                }
+            ),
+            "Name a-name refers to 10.20.0.0/24, which does not have it"
+        );
+        assert_eq!(
+            format!(
+                "{}",
+                ValidationError::UnindexedName {
+                    name: "a-name".to_string(),
+                    cidr: a
+                }
+            ),
+            "Name a-name of subnet 10.20.0.0/24 is not indexed"
+        );
+    }
 }
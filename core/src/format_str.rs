@@ -0,0 +1,103 @@
+// Copyright 2024 The Milton Hirsch Institute, B.V.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Rendering of text templates with named `{field}` placeholders.
+
+mod parser;
+
+use crate::errors::FormatError;
+use crate::{util, CidrRecord, FormatResult};
+use std::collections::BTreeMap;
+
+pub use parser::parse;
+
+#[derive(Debug, PartialEq)]
+pub enum Segment {
+    Text(String),
+    Named(String),
+}
+
+pub type Segments = Vec<Segment>;
+
+pub type Fields = BTreeMap<String, String>;
+
+/// Replace every named placeholder in `segments` with its value in `fields`.
+pub fn format(segments: &Segments, fields: &Fields) -> FormatResult<String> {
+    let mut result = String::new();
+    for segment in segments {
+        match segment {
+            Segment::Text(text) => result.push_str(text),
+            Segment::Named(name) => match fields.get(name) {
+                Some(value) => result.push_str(value),
+                None => return Err(FormatError::UnknownField(name.clone())),
+            },
+        }
+    }
+    Ok(result)
+}
+
+/// Fields describing a record: `cidr`, `name` (empty when unnamed), and the
+/// `first` and `last` addresses of the subnet.
+pub fn record_fields(record: &CidrRecord) -> Fields {
+    let (first, last) = util::host_range(&record.cidr);
+    Fields::from([
+        ("cidr".to_string(), record.cidr.to_string()),
+        ("name".to_string(), record.name.clone().unwrap_or_default()),
+        ("first".to_string(), first.to_string()),
+        ("last".to_string(), last.to_string()),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cidr::IpCidr;
+    use std::str::FromStr;
+
+    mod format {
+        use super::*;
+
+        #[test]
+        fn named() {
+            let segments = parse("a{x}b{y}").unwrap();
+            let fields = Fields::from([
+                ("x".to_string(), "1".to_string()),
+                ("y".to_string(), "2".to_string()),
+            ]);
+            assert_eq!(format(&segments, &fields), Ok("a1b2".to_string()));
+        }
+
+        #[test]
+        fn unknown_field() {
+            let segments = parse("a{z}").unwrap();
+            assert_eq!(
+                format(&segments, &Fields::new()),
+                Err(FormatError::UnknownField("z".to_string()))
+            );
+        }
+    }
+
+    mod record_fields {
+        use super::*;
+
+        #[test]
+        fn named() {
+            let record = CidrRecord::new(IpCidr::from_str("10.20.0.0/24").unwrap(), Some("web"));
+            let segments = parse("{name} {cidr} {first} {last}").unwrap();
+            assert_eq!(
+                format(&segments, &record_fields(&record)),
+                Ok("web 10.20.0.0/24 10.20.0.0 10.20.0.255".to_string())
+            );
+        }
+
+        #[test]
+        fn unnamed() {
+            let record = CidrRecord::new(IpCidr::from_str("1:2:3:4::/127").unwrap(), None);
+            let segments = parse("[{name}] {first} {last}").unwrap();
+            assert_eq!(
+                format(&segments, &record_fields(&record)),
+                Ok("[] 1:2:3:4:: 1:2:3:4::1".to_string())
+            );
+        }
+    }
+}
@@ -0,0 +1,141 @@
+// Copyright 2024 The Milton Hirsch Institute, B.V.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::errors::FormatError;
+use crate::format_str::{Segment, Segments};
+use crate::FormatResult;
+use std::str::Chars;
+
+fn is_field_char(c: char, first: bool) -> bool {
+    c.is_ascii_alphabetic() || c == '_' || (!first && c.is_ascii_digit())
+}
+
+/// Take a `field}` placeholder name from the front of `chars`, which follow
+/// an opening brace. `chars` is left as it was if they do not start with one.
+fn take_field(chars: &mut Chars) -> Option<String> {
+    let mut lookahead = chars.clone();
+    let mut name = String::new();
+    loop {
+        match lookahead.next() {
+            Some('}') if !name.is_empty() => break,
+            Some(c) if is_field_char(c, name.is_empty()) => name.push(c),
+            _ => return None,
+        }
+    }
+    *chars = lookahead;
+    Some(name)
+}
+
+/// Parse a template of literal text and `{field}` placeholders. A brace that
+/// does not start a placeholder is literal text, and a backslash escapes the
+/// character that follows it.
+pub fn parse(template: &str) -> FormatResult<Segments> {
+    let mut segments = Segments::new();
+    let mut text = String::new();
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(escaped) => text.push(escaped),
+                None => return Err(unexpected_end()),
+            },
+            '{' => match take_field(&mut chars) {
+                Some(name) => {
+                    if !text.is_empty() {
+                        segments.push(Segment::Text(std::mem::take(&mut text)));
+                    }
+                    segments.push(Segment::Named(name));
+                }
+                None => text.push(c),
+            },
+            _ => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        segments.push(Segment::Text(text));
+    }
+    Ok(segments)
+}
+
+fn unexpected_end() -> FormatError {
+    FormatError::Parse("Unexpected end of format".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty() {
+        assert_eq!(parse(""), Ok(vec![]));
+    }
+
+    #[test]
+    fn text() {
+        assert_eq!(parse("aaa"), Ok(vec![Segment::Text("aaa".to_string())]));
+    }
+
+    #[test]
+    fn named() {
+        assert_eq!(
+            parse("aaa{cidr}bbb{first_1}"),
+            Ok(vec![
+                Segment::Text("aaa".to_string()),
+                Segment::Named("cidr".to_string()),
+                Segment::Text("bbb".to_string()),
+                Segment::Named("first_1".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn escape() {
+        assert_eq!(
+            parse("\\{ {name} }\\\\"),
+            Ok(vec![
+                Segment::Text("{ ".to_string()),
+                Segment::Named("name".to_string()),
+                Segment::Text(" }\\".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn missing_escape_character() {
+        assert_eq!(
+            parse("aaa\\"),
+            Err(FormatError::Parse("Unexpected end of format".to_string()))
+        );
+    }
+
+    #[test]
+    fn unterminated_field() {
+        assert_eq!(
+            parse("aaa{cidr"),
+            Ok(vec![Segment::Text("aaa{cidr".to_string())])
+        );
+    }
+
+    #[test]
+    fn empty_field() {
+        assert_eq!(parse("aaa{}"), Ok(vec![Segment::Text("aaa{}".to_string())]));
+    }
+
+    #[test]
+    fn bad_field_character() {
+        assert_eq!(parse("{1st}"), Ok(vec![Segment::Text("{1st}".to_string())]));
+        assert_eq!(parse("{a-b}"), Ok(vec![Segment::Text("{a-b}".to_string())]));
+    }
+
+    #[test]
+    fn literal_braces() {
+        assert_eq!(
+            parse("{ cidr = \"{cidr}\" }"),
+            Ok(vec![
+                Segment::Text("{ cidr = \"".to_string()),
+                Segment::Named("cidr".to_string()),
+                Segment::Text("\" }".to_string()),
+            ])
+        );
+    }
+}
@@ -1,16 +1,21 @@
 // Copyright 2023-2024 The Milton Hirsch Institute, B.V.
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::errors::{AllocateError, CreateError, DeleteError, RenameError};
+use crate::errors::{
+    AllocateError, CreateError, DeleteError, FormatError, RenameError, StorageError, SubgError,
+};
 use cidr::IpCidr;
 use serde::de;
 use serde::ser::SerializeStruct;
 use std::str::FromStr;
 
 pub mod errors;
+pub mod format_str;
 pub mod pool;
+pub mod pool_set;
+pub mod storage;
 mod subspace;
-mod util;
+pub mod util;
 
 pub type CreateResult<T> = Result<T, CreateError>;
 
@@ -20,12 +25,41 @@ pub type AllocateResult<T> = Result<T, AllocateError>;
 
 pub type RenameResult<T> = Result<T, RenameError>;
 
+pub type FormatResult<T> = Result<T, FormatError>;
+
+pub type StorageResult<T> = Result<T, StorageError>;
+
+pub type SubgResult<T> = Result<T, SubgError>;
+
 pub type Bits = u8;
 
-#[derive(Debug, Eq, Ord, PartialEq, PartialOrd, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressFamily {
+    Ipv4,
+    Ipv6,
+}
+
+impl From<&IpCidr> for AddressFamily {
+    fn from(cidr: &IpCidr) -> Self {
+        match cidr {
+            IpCidr::V4(_) => AddressFamily::Ipv4,
+            IpCidr::V6(_) => AddressFamily::Ipv6,
+        }
+    }
+}
+
+#[derive(Debug, Eq, Hash, Ord, PartialEq, PartialOrd, Clone)]
 pub struct CidrRecord {
     pub cidr: IpCidr,
     pub name: Option<String>,
+    /// RFC 3339 time at which the subnet was allocated, if known
+    pub created_at: Option<String>,
+    /// Free-form description of the subnet
+    pub description: Option<String>,
+    /// Labels for grouping subnets
+    pub tags: Vec<String>,
+    /// Protects the subnet from being freed or renamed without force
+    pub locked: bool,
 }
 
 impl CidrRecord {
@@ -33,6 +67,10 @@ impl CidrRecord {
         CidrRecord {
             cidr,
             name: name.map(|name| name.to_string()),
+            created_at: None,
+            description: None,
+            tags: Vec::new(),
+            locked: false,
         }
     }
 }
@@ -42,11 +80,34 @@ impl serde::Serialize for CidrRecord {
     where
         S: serde::Serializer,
     {
-        let mut structure = serializer.serialize_struct("CidrRecord", 2)?;
+        let human_readable = serializer.is_human_readable();
+        let mut structure = serializer.serialize_struct("CidrRecord", 6)?;
         structure.serialize_field("cidr", &self.cidr.to_string())?;
+        // Binary formats have no field names to tell which fields are left
+        // out, so they get every field.
+        if !human_readable {
+            structure.serialize_field("name", &self.name)?;
+            structure.serialize_field("created_at", &self.created_at)?;
+            structure.serialize_field("description", &self.description)?;
+            structure.serialize_field("tags", &self.tags)?;
+            structure.serialize_field("locked", &self.locked)?;
+            return structure.end();
+        }
         if let Some(name) = &self.name {
             structure.serialize_field("name", name)?;
         }
+        if let Some(created_at) = &self.created_at {
+            structure.serialize_field("created_at", created_at)?;
+        }
+        if let Some(description) = &self.description {
+            structure.serialize_field("description", description)?;
+        }
+        if !self.tags.is_empty() {
+            structure.serialize_field("tags", &self.tags)?;
+        }
+        if self.locked {
+            structure.serialize_field("locked", &self.locked)?;
+        }
         structure.end()
     }
 }
@@ -61,7 +122,51 @@ impl<'s> serde::Deserialize<'s> for CidrRecord {
         enum Field {
             Cidr,
             Name,
+            #[serde(rename = "created_at")]
+            CreatedAt,
+            Description,
+            Tags,
+            Locked,
+        }
+        /// Name of a record, which may be given as null.
+        struct NullableName(Option<String>);
+
+        impl<'d> serde::Deserialize<'d> for NullableName {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: serde::Deserializer<'d>,
+            {
+                struct NullableNameVisitor;
+                impl<'d> de::Visitor<'d> for NullableNameVisitor {
+                    type Value = NullableName;
+
+                    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                        formatter.write_str("a name or null")
+                    }
+
+                    fn visit_str<E: de::Error>(self, name: &str) -> Result<Self::Value, E> {
+                        Ok(NullableName(Some(name.to_string())))
+                    }
+
+                    fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+                        Ok(NullableName(None))
+                    }
+
+                    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+                        Ok(NullableName(None))
+                    }
+
+                    fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+                    where
+                        D: serde::Deserializer<'d>,
+                    {
+                        deserializer.deserialize_any(self)
+                    }
+                }
+                deserializer.deserialize_any(NullableNameVisitor)
+            }
         }
+
         struct CidrRecordVisitor;
         impl<'d> de::Visitor<'d> for CidrRecordVisitor {
             type Value = CidrRecord;
@@ -82,18 +187,30 @@ impl<'s> serde::Deserialize<'s> for CidrRecord {
                     Err(err) => return Err(serde::de::Error::custom(err)),
                 };
 
-                let name = seq
-                    .next_element::<Option<&str>>()?
-                    .ok_or_else(|| serde::de::Error::missing_field("name"))?;
+                let name = seq.next_element::<Option<&str>>()?.flatten();
+                let created_at = seq.next_element::<Option<String>>()?.flatten();
+                let description = seq.next_element::<Option<String>>()?.flatten();
+                let tags = seq.next_element::<Vec<String>>()?.unwrap_or_default();
+                let locked = seq.next_element::<bool>()?.unwrap_or_default();
 
-                Ok(CidrRecord::new(cidr, name))
+                Ok(CidrRecord {
+                    created_at,
+                    description,
+                    tags,
+                    locked,
+                    ..CidrRecord::new(cidr, name)
+                })
             }
             fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
             where
                 A: de::MapAccess<'d>,
             {
                 let mut cidr: Option<IpCidr> = None;
-                let mut name: Option<String> = None;
+                let mut name: Option<NullableName> = None;
+                let mut created_at: Option<String> = None;
+                let mut description: Option<String> = None;
+                let mut tags: Option<Vec<String>> = None;
+                let mut locked: Option<bool> = None;
                 while let Some(key) = map.next_key()? {
                     match key {
                         Field::Cidr => {
@@ -114,17 +231,58 @@ impl<'s> serde::Deserialize<'s> for CidrRecord {
                             }
                             name = Some(map.next_value()?);
                         }
+                        Field::CreatedAt => {
+                            if created_at.is_some() {
+                                return Err(de::Error::duplicate_field("created_at"));
+                            }
+                            created_at = Some(map.next_value()?);
+                        }
+                        Field::Description => {
+                            if description.is_some() {
+                                return Err(de::Error::duplicate_field("description"));
+                            }
+                            description = Some(map.next_value()?);
+                        }
+                        Field::Tags => {
+                            if tags.is_some() {
+                                return Err(de::Error::duplicate_field("tags"));
+                            }
+                            tags = Some(map.next_value()?);
+                        }
+                        Field::Locked => {
+                            if locked.is_some() {
+                                return Err(de::Error::duplicate_field("locked"));
+                            }
+                            locked = Some(map.next_value()?);
+                        }
                     }
                 }
                 let cidr = match cidr {
                     Some(cidr) => cidr,
                     None => return Err(de::Error::missing_field("cidr")),
                 };
-                Ok(CidrRecord::new(cidr, name.as_deref()))
+                Ok(CidrRecord {
+                    created_at,
+                    description,
+                    tags: tags.unwrap_or_default(),
+                    locked: locked.unwrap_or_default(),
+                    ..CidrRecord::new(cidr, name.and_then(|name| name.0).as_deref())
+                })
             }
         }
 
-        deserializer.deserialize_struct("CidrRecord", &["cidr", "name"], CidrRecordVisitor)
+        deserializer.deserialize_struct(
+            "CidrRecord",
+            &[
+                "cidr",
+                "name",
+                "created_at",
+                "description",
+                "tags",
+                "locked",
+            ],
+            CidrRecordVisitor,
+        )
     }
 }
 
@@ -140,6 +298,8 @@ mod tests {
             _ => panic!("Failed to create test v4 cidr"),
         });
 
+    pub static TEST_TIME: &str = "2024-01-26T12:00:00Z";
+
     pub static TEST_CIDR6: IpCidr = IpCidr::V6(
         match Ipv6Cidr::new(Ipv6Addr::new(1, 2, 3, 4, 10, 20, 0, 0), 112) {
             Ok(cidr) => cidr,
@@ -148,7 +308,7 @@ mod tests {
     );
     mod cidr_record {
         use super::*;
-        use serde_test::{assert_de_tokens_error, assert_tokens};
+        use serde_test::{assert_de_tokens, assert_de_tokens_error, assert_tokens, Configure};
         use std::str::FromStr;
 
         #[test]
@@ -160,16 +320,32 @@ mod tests {
             assert_eq!(record.name.as_deref(), name);
         }
 
+        #[test]
+        fn hash() {
+            use std::collections::HashSet;
+
+            let cidr = IpCidr::from_str("10.20.30.0/24").unwrap();
+            let mut records = HashSet::new();
+            assert!(records.insert(CidrRecord::new(cidr, Some("a-record"))));
+            assert!(records.insert(CidrRecord::new(cidr, None)));
+            assert!(!records.insert(CidrRecord::new(cidr, Some("a-record"))));
+            assert!(records.insert(CidrRecord {
+                tags: vec!["lab".to_string()],
+                ..CidrRecord::new(cidr, Some("a-record"))
+            }));
+            assert_eq!(records.len(), 3);
+        }
+
         #[test]
         fn serialize_named() {
             let cidr = IpCidr::from_str("10.20.30.0/24").unwrap();
             let record = CidrRecord::new(cidr, Some("a-record"));
             assert_tokens(
-                &record,
+                &record.readable(),
                 &[
                     serde_test::Token::Struct {
                         name: "CidrRecord",
-                        len: 2,
+                        len: 6,
                     },
                     serde_test::Token::Str("cidr"),
                     serde_test::Token::Str("10.20.30.0/24"),
@@ -185,7 +361,135 @@ mod tests {
             let cidr = IpCidr::from_str("10.20.30.0/24").unwrap();
             let record = CidrRecord::new(cidr, None);
             assert_tokens(
-                &record,
+                &record.readable(),
+                &[
+                    serde_test::Token::Struct {
+                        name: "CidrRecord",
+                        len: 6,
+                    },
+                    serde_test::Token::Str("cidr"),
+                    serde_test::Token::Str("10.20.30.0/24"),
+                    serde_test::Token::StructEnd,
+                ],
+            );
+        }
+
+        #[test]
+        fn serialize_created_at() {
+            let cidr = IpCidr::from_str("10.20.30.0/24").unwrap();
+            let record = CidrRecord {
+                created_at: Some(TEST_TIME.to_string()),
+                ..CidrRecord::new(cidr, Some("a-record"))
+            };
+            assert_tokens(
+                &record.readable(),
+                &[
+                    serde_test::Token::Struct {
+                        name: "CidrRecord",
+                        len: 6,
+                    },
+                    serde_test::Token::Str("cidr"),
+                    serde_test::Token::Str("10.20.30.0/24"),
+                    serde_test::Token::Str("name"),
+                    serde_test::Token::Str("a-record"),
+                    serde_test::Token::Str("created_at"),
+                    serde_test::Token::Str(TEST_TIME),
+                    serde_test::Token::StructEnd,
+                ],
+            );
+        }
+
+        #[test]
+        fn serialize_description_and_tags() {
+            let cidr = IpCidr::from_str("10.20.30.0/24").unwrap();
+            let record = CidrRecord {
+                description: Some("Lab network".to_string()),
+                tags: vec!["lab".to_string(), "west".to_string()],
+                ..CidrRecord::new(cidr, None)
+            };
+            assert_tokens(
+                &record.readable(),
+                &[
+                    serde_test::Token::Struct {
+                        name: "CidrRecord",
+                        len: 6,
+                    },
+                    serde_test::Token::Str("cidr"),
+                    serde_test::Token::Str("10.20.30.0/24"),
+                    serde_test::Token::Str("description"),
+                    serde_test::Token::Str("Lab network"),
+                    serde_test::Token::Str("tags"),
+                    serde_test::Token::Seq { len: Some(2) },
+                    serde_test::Token::Str("lab"),
+                    serde_test::Token::Str("west"),
+                    serde_test::Token::SeqEnd,
+                    serde_test::Token::StructEnd,
+                ],
+            );
+        }
+
+        #[test]
+        fn serialize_locked() {
+            let cidr = IpCidr::from_str("10.20.30.0/24").unwrap();
+            let record = CidrRecord {
+                locked: true,
+                ..CidrRecord::new(cidr, Some("a-record"))
+            };
+            assert_tokens(
+                &record.readable(),
+                &[
+                    serde_test::Token::Struct {
+                        name: "CidrRecord",
+                        len: 6,
+                    },
+                    serde_test::Token::Str("cidr"),
+                    serde_test::Token::Str("10.20.30.0/24"),
+                    serde_test::Token::Str("name"),
+                    serde_test::Token::Str("a-record"),
+                    serde_test::Token::Str("locked"),
+                    serde_test::Token::Bool(true),
+                    serde_test::Token::StructEnd,
+                ],
+            );
+        }
+
+        #[test]
+        fn deserialize_sequence_without_created_at() {
+            let cidr = IpCidr::from_str("10.20.30.0/24").unwrap();
+            assert_de_tokens(
+                &CidrRecord::new(cidr, Some("a-record")),
+                &[
+                    serde_test::Token::Seq { len: Some(2) },
+                    serde_test::Token::BorrowedStr("10.20.30.0/24"),
+                    serde_test::Token::Some,
+                    serde_test::Token::BorrowedStr("a-record"),
+                    serde_test::Token::SeqEnd,
+                ],
+            );
+        }
+
+        #[test]
+        fn deserialize_without_name() {
+            let cidr = IpCidr::from_str("10.20.30.0/24").unwrap();
+            assert_de_tokens(
+                &CidrRecord::new(cidr, None),
+                &[
+                    serde_test::Token::Struct {
+                        name: "CidrRecord",
+                        len: 1,
+                    },
+                    serde_test::Token::Str("cidr"),
+                    serde_test::Token::Str("10.20.30.0/24"),
+                    serde_test::Token::StructEnd,
+                ],
+            );
+        }
+
+        #[test]
+        fn deserialize_null_name() {
+            let cidr = IpCidr::from_str("10.20.30.0/24").unwrap();
+            assert_de_tokens(
+                &CidrRecord::new(cidr, None),
                 &[
                     serde_test::Token::Struct {
                         name: "CidrRecord",
@@ -193,18 +497,33 @@ mod tests {
                     },
                     serde_test::Token::Str("cidr"),
                     serde_test::Token::Str("10.20.30.0/24"),
+                    serde_test::Token::Str("name"),
+                    serde_test::Token::None,
                     serde_test::Token::StructEnd,
                 ],
             );
         }
 
+        #[test]
+        fn deserialize_sequence_without_name() {
+            let cidr = IpCidr::from_str("10.20.30.0/24").unwrap();
+            assert_de_tokens(
+                &CidrRecord::new(cidr, None),
+                &[
+                    serde_test::Token::Seq { len: Some(1) },
+                    serde_test::Token::BorrowedStr("10.20.30.0/24"),
+                    serde_test::Token::SeqEnd,
+                ],
+            );
+        }
+
         #[test]
         fn deserialize_as_sequence() {
             assert_de_tokens_error::<CidrRecord>(
                 &[
                     serde_test::Token::Struct {
                         name: "CidrRecord",
-                        len: 2,
+                        len: 6,
                     },
                     serde_test::Token::Str("cidr"),
                     serde_test::Token::Str("invalid"),
@@ -4,17 +4,176 @@
 #[cfg(test)]
 mod tests;
 
-use crate::errors::{AllocateError, RenameError};
+use crate::errors::{AllocateError, CreateError, RenameError, ValidationError};
 use crate::subspace::{State, Subspace};
-use crate::{AllocateResult, Bits, CidrRecord, RenameResult};
+use crate::{
+    format_str, util, AddressFamily, AllocateResult, Bits, CidrRecord, CreateResult, FormatResult,
+    RenameResult,
+};
+use chrono::{DateTime, Utc};
 use cidr::IpCidr;
 use serde::ser::SerializeStruct;
-use std::collections::HashMap;
+use std::cmp;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
 
-#[derive(PartialEq, Debug)]
+/// Changes that turn one pool's allocations into another's.
+#[derive(Debug, Default, PartialEq)]
+pub struct PoolDiff {
+    pub added: Vec<CidrRecord>,
+    pub removed: Vec<CidrRecord>,
+    pub renamed: Vec<(IpCidr, Option<String>, Option<String>)>,
+}
+
+/// Where in the free space a new subnet is placed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum AllocStrategy {
+    /// Lowest free addresses first
+    #[default]
+    LowFirst,
+    /// Highest free addresses first, keeping allocations apart from those
+    /// made low first
+    HighFirst,
+}
+
+/// Overview of how much of a pool is in use, as returned by
+/// `SubnetPool::summary` for embedding in reports.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoolSummary {
+    pub cidr: IpCidr,
+    pub allocated_count: usize,
+    pub named_count: usize,
+    /// Fraction of the addresses of the pool that are allocated, from 0 to 1
+    pub utilization: f64,
+    pub max_available_bits: Bits,
+    pub free_block_count: usize,
+}
+
+impl serde::Serialize for PoolSummary {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut structure = serializer.serialize_struct("PoolSummary", 6)?;
+        structure.serialize_field("cidr", &self.cidr.to_string())?;
+        structure.serialize_field("allocated_count", &self.allocated_count)?;
+        structure.serialize_field("named_count", &self.named_count)?;
+        structure.serialize_field("utilization", &self.utilization)?;
+        structure.serialize_field("max_available_bits", &self.max_available_bits)?;
+        structure.serialize_field("free_block_count", &self.free_block_count)?;
+        structure.end()
+    }
+}
+
+/// Which parts of a record `SubnetPool::update_metadata` changed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MetadataUpdate {
+    pub name: bool,
+    pub description: bool,
+    pub tags: bool,
+}
+
+impl MetadataUpdate {
+    pub fn changed(&self) -> bool {
+        self.name || self.description || self.tags
+    }
+}
+
+/// Mutable view of an allocated record, yielded by `SubnetPool::records_mut`.
+/// Only the description and tags can be changed; the CIDR is fixed by the
+/// record's place in the pool and names must go through `SubnetPool::rename`
+/// so that they stay unique.
+#[derive(Debug)]
+pub struct RecordMut<'a> {
+    record: &'a mut CidrRecord,
+}
+
+impl RecordMut<'_> {
+    pub fn cidr(&self) -> &IpCidr {
+        &self.record.cidr
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.record.name.as_deref()
+    }
+
+    pub fn description(&self) -> Option<&str> {
+        self.record.description.as_deref()
+    }
+
+    pub fn set_description(&mut self, description: Option<&str>) {
+        self.record.description = description.map(str::to_string);
+    }
+
+    pub fn tags(&self) -> &[String] {
+        &self.record.tags
+    }
+
+    pub fn tags_mut(&mut self) -> &mut Vec<String> {
+        &mut self.record.tags
+    }
+}
+
+/// Saved state of a pool, taken by `SubnetPool::snapshot` and put back by
+/// `SubnetPool::restore`.
+#[derive(Debug, Clone)]
+pub struct PoolSnapshot {
+    root: Subspace,
+    names: HashMap<String, IpCidr>,
+    description: Option<String>,
+    metadata: BTreeMap<String, String>,
+}
+
+/// Change made to a pool, passed to the callbacks registered with
+/// `SubnetPool::on_change`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChangeEvent {
+    Allocated(CidrRecord),
+    Freed(IpCidr),
+    Renamed {
+        cidr: IpCidr,
+        old: Option<String>,
+        new: Option<String>,
+    },
+}
+
+type ChangeCallback = Box<dyn FnMut(&ChangeEvent) + Send>;
+
+/// Callbacks registered with `SubnetPool::on_change`. They are not part of
+/// the state of the pool, so they are not compared, cloned or stored.
+#[derive(Default)]
+struct ChangeCallbacks(Vec<ChangeCallback>);
+
+impl fmt::Debug for ChangeCallbacks {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("ChangeCallbacks")
+            .field(&self.0.len())
+            .finish()
+    }
+}
+
+impl Clone for ChangeCallbacks {
+    fn clone(&self) -> Self {
+        ChangeCallbacks::default()
+    }
+}
+
+impl PartialEq for ChangeCallbacks {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
 pub struct SubnetPool {
     root: Subspace,
     names: HashMap<String, IpCidr>,
+    description: Option<String>,
+    metadata: BTreeMap<String, String>,
+    callbacks: ChangeCallbacks,
+}
+
+/// An empty name is no name, so that `""` never names an allocation.
+fn non_empty(name: Option<&str>) -> Option<&str> {
+    name.filter(|name| !name.is_empty())
 }
 
 impl SubnetPool {
@@ -22,20 +181,102 @@ impl SubnetPool {
         SubnetPool {
             root: Subspace::new(cidr),
             names: HashMap::new(),
+            description: None,
+            metadata: BTreeMap::new(),
+            callbacks: ChangeCallbacks::default(),
         }
     }
 
-    fn iter_allocated_subspaces_from<'a>(
+    /// Call `callback` after each successful allocation, free and rename.
+    /// Splitting or merging allocations reports the allocations freed and
+    /// allocated. Callbacks are not copied by `clone`.
+    pub fn on_change(&mut self, callback: ChangeCallback) {
+        self.callbacks.0.push(callback);
+    }
+
+    /// Pass the event made by `event` to the callbacks. The event is only
+    /// made when there are callbacks to pass it to.
+    fn notify(&mut self, event: impl FnOnce(&Self) -> ChangeEvent) {
+        if self.callbacks.0.is_empty() {
+            return;
+        }
+        let event = event(self);
+        for callback in &mut self.callbacks.0 {
+            callback(&event);
+        }
+    }
+
+    fn notify_allocated(&mut self, cidr: &IpCidr) {
+        self.notify(|pool| {
+            let subspace = pool
+                .root
+                .find_record(cidr)
+                .expect("allocated subnet is in pool");
+            ChangeEvent::Allocated(subspace.record.clone())
+        });
+    }
+
+    fn notify_renamed(&mut self, cidr: &IpCidr, old: Option<String>, new: Option<&str>) {
+        self.notify(|_| ChangeEvent::Renamed {
+            cidr: *cidr,
+            old,
+            new: new.map(|new| new.to_string()),
+        });
+    }
+
+    /// Save the whole state of the pool so a sequence of changes can be
+    /// rolled back with `restore`.
+    pub fn snapshot(&self) -> PoolSnapshot {
+        PoolSnapshot {
+            root: self.root.clone(),
+            names: self.names.clone(),
+            description: self.description.clone(),
+            metadata: self.metadata.clone(),
+        }
+    }
+
+    /// Put the pool back to the state saved in `snapshot`, discarding every
+    /// change made since.
+    pub fn restore(&mut self, snapshot: PoolSnapshot) {
+        self.root = snapshot.root;
+        self.names = snapshot.names;
+        self.description = snapshot.description;
+        self.metadata = snapshot.metadata;
+    }
+
+    /// Free-form description of the pool
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    pub fn set_description(&mut self, description: Option<&str>) {
+        self.description = description.map(|description| description.to_string());
+    }
+
+    /// Pool-level metadata such as `region` or `owner`, in key order.
+    pub fn metadata(&self) -> &BTreeMap<String, String> {
+        &self.metadata
+    }
+
+    /// Set a metadata value, returning the value it replaced.
+    pub fn set_metadata(&mut self, key: &str, value: &str) -> Option<String> {
+        self.metadata.insert(key.to_string(), value.to_string())
+    }
+
+    /// Depth-first traversal of the leaves below `subspace` in address order.
+    ///
+    /// The stack never holds more than one entry per tree level plus one, so
+    /// it is allocated once up front and never grows.
+    fn iter_leaf_subspaces_from<'a>(
         &'a self,
         subspace: &'a Subspace,
-    ) -> impl Iterator<Item = &Subspace> {
-        let mut stack = Vec::new();
+    ) -> impl Iterator<Item = &'a Subspace> {
+        let mut stack = Vec::with_capacity(subspace.host_length() as usize + 1);
         stack.push(subspace);
         std::iter::from_fn(move || {
             while let Some(subspace) = stack.pop() {
                 match subspace.state {
-                    State::Allocated => return Some(subspace),
-                    State::Free => {}
+                    State::Allocated | State::Free => return Some(subspace),
                     State::Unavailable => {
                         stack.push(subspace.high.as_deref().unwrap());
                         stack.push(subspace.low.as_deref().unwrap());
@@ -46,6 +287,14 @@ impl SubnetPool {
         })
     }
 
+    fn iter_allocated_subspaces_from<'a>(
+        &'a self,
+        subspace: &'a Subspace,
+    ) -> impl Iterator<Item = &'a Subspace> {
+        self.iter_leaf_subspaces_from(subspace)
+            .filter(|subspace| subspace.state == State::Allocated)
+    }
+
     fn iter_allocated_subspaces(&self) -> impl Iterator<Item = &Subspace> {
         self.iter_allocated_subspaces_from(&self.root)
     }
@@ -55,6 +304,21 @@ impl SubnetPool {
         &self.root.record.cidr
     }
 
+    #[inline(always)]
+    pub fn family(&self) -> AddressFamily {
+        AddressFamily::from(self.cidr())
+    }
+
+    #[inline(always)]
+    pub fn is_ipv4(&self) -> bool {
+        self.family() == AddressFamily::Ipv4
+    }
+
+    #[inline(always)]
+    pub fn is_ipv6(&self) -> bool {
+        self.family() == AddressFamily::Ipv6
+    }
+
     #[inline(always)]
     pub fn allocated_count(&self) -> usize {
         self.root.allocated_count
@@ -70,51 +334,847 @@ impl SubnetPool {
         self.root.max_available_bits
     }
 
+    /// Host bits of the largest free block inside `within`, or 0 when
+    /// nothing inside it is free or it lies outside the pool.
+    pub fn max_available_bits_within(&self, within: &IpCidr) -> Bits {
+        if util::cidr_contains(within, self.cidr()) {
+            return self.max_available_bits();
+        }
+        match self.root.find_containing(within) {
+            None => 0,
+            Some(subspace) if subspace.record.cidr == *within => subspace.max_available_bits,
+            Some(subspace) if subspace.state == State::Free => util::host_length(within),
+            Some(_) => 0,
+        }
+    }
+
+    /// Total number of addresses in the pool. A pool covering the whole IPv6
+    /// space (`::/0`) does not fit in a `u128` and saturates to `u128::MAX`.
+    pub fn capacity(&self) -> u128 {
+        1u128
+            .checked_shl(self.root.host_length() as u32)
+            .unwrap_or(u128::MAX)
+    }
+
+    /// Number of addresses in allocations.
+    pub fn allocated_addresses(&self) -> u128 {
+        self.addresses_in(State::Allocated)
+    }
+
+    /// Number of addresses outside every allocation, summed over the free
+    /// blocks in a single walk. Together with `allocated_addresses` this
+    /// makes up `capacity`.
+    pub fn free_addresses(&self) -> u128 {
+        self.addresses_in(State::Free)
+    }
+
+    fn addresses_in(&self, state: State) -> u128 {
+        self.iter_leaf_subspaces_from(&self.root)
+            .filter(|subspace| subspace.state == state)
+            .map(|subspace| {
+                1u128
+                    .checked_shl(subspace.host_length() as u32)
+                    .unwrap_or(u128::MAX)
+            })
+            .fold(0, u128::saturating_add)
+    }
+
+    /// Summarize the use of the pool. Apart from the cached counters this
+    /// takes a single walk over the free blocks.
+    pub fn summary(&self) -> PoolSummary {
+        let (free_block_count, free_addresses) = self
+            .free_blocks()
+            .map(|block| {
+                1u128
+                    .checked_shl(util::host_length(block) as u32)
+                    .unwrap_or(u128::MAX)
+            })
+            .fold((0, 0u128), |(count, addresses), block_addresses| {
+                (count + 1, addresses.saturating_add(block_addresses))
+            });
+        let capacity = self.capacity();
+        PoolSummary {
+            cidr: *self.cidr(),
+            allocated_count: self.allocated_count(),
+            named_count: self.named_count(),
+            utilization: (capacity - free_addresses) as f64 / capacity as f64,
+            max_available_bits: self.max_available_bits(),
+            free_block_count,
+        }
+    }
+
+    /// Iterate over the largest unallocated blocks of the pool in address
+    /// order.
+    pub fn free_blocks(&self) -> impl Iterator<Item = &IpCidr> {
+        self.iter_leaf_subspaces_from(&self.root)
+            .filter(|subspace| subspace.state == State::Free)
+            .map(|subspace| &subspace.record.cidr)
+    }
+
+    /// The first free block, in address order, that can hold a subnet of
+    /// `bits` host bits, with the host bits of the whole block.
+    pub fn find_gap(&self, bits: Bits) -> Option<(IpCidr, Bits)> {
+        self.free_blocks()
+            .map(|block| (*block, util::host_length(block)))
+            .find(|(_, block_bits)| *block_bits >= bits)
+    }
+
+    /// Number of subnets with the given host bits that could be allocated
+    /// from the remaining free space.
+    pub fn capacity_for(&self, bits: Bits) -> u128 {
+        self.free_blocks()
+            .filter_map(|cidr| util::host_length(cidr).checked_sub(bits))
+            .map(|spare| 1u128.checked_shl(spare as u32).unwrap_or(u128::MAX))
+            .fold(0, u128::saturating_add)
+    }
+
+    /// The lowest allocation overlapping `cidr`, if any.
+    pub fn overlapping(&self, cidr: &IpCidr) -> Option<&CidrRecord> {
+        let subspace = match util::cidr_contains(cidr, self.cidr()) {
+            true => &self.root,
+            false => self.root.find_containing(cidr)?,
+        };
+        self.iter_allocated_subspaces_from(subspace)
+            .next()
+            .map(|subspace| &subspace.record)
+    }
+
+    /// The nearest free subnet of the same size as `cidr`, looking first at
+    /// `cidr` itself and the addresses after it and then from the start of
+    /// the pool.
+    pub fn next_free(&self, cidr: &IpCidr) -> Option<IpCidr> {
+        if self.check_family(cidr).is_err() {
+            return None;
+        }
+        let bits = util::host_length(cidr);
+        let network_length = cidr.network_length();
+        let start = cidr.first_address();
+        let fitting: Vec<&IpCidr> = self
+            .free_blocks()
+            .filter(|block| util::host_length(block) >= bits)
+            .collect();
+        let following = fitting
+            .iter()
+            .find(|block| block.last_address() >= start)
+            .map(|block| cmp::max(block.first_address(), start));
+        following
+            .or_else(|| fitting.first().map(|block| block.first_address()))
+            .map(|address| IpCidr::new(address, network_length).expect("free subnet is aligned"))
+    }
+
+    /// Number of allocations of each size, keyed by host bits.
+    pub fn prefixes_by_size(&self) -> BTreeMap<Bits, usize> {
+        let mut histogram = BTreeMap::new();
+        for record in self.records() {
+            *histogram
+                .entry(util::host_length(&record.cidr))
+                .or_insert(0) += 1;
+        }
+        histogram
+    }
+
     #[inline(always)]
     pub fn find_by_name(&self, name: &str) -> Option<IpCidr> {
         self.names.get(name).copied()
     }
 
+    /// CIDR of the allocation with the given name; the same as
+    /// `find_by_name`.
+    pub fn cidr_for_name(&self, name: &str) -> Option<IpCidr> {
+        self.find_by_name(name)
+    }
+
+    /// CIDR of the allocation already holding `name`, if any, for checking
+    /// before an allocation or rename that it would not fail with a
+    /// duplicate name.
+    pub fn contains_name_conflict(&self, name: &str) -> Option<IpCidr> {
+        self.find_by_name(name)
+    }
+
+    /// The whole record of the allocation with the given name.
+    pub fn find_record_by_name(&self, name: &str) -> Option<&CidrRecord> {
+        let cidr = self.names.get(name)?;
+        self.root.find_record(cidr).map(|subspace| &subspace.record)
+    }
+
+    /// Ensure `cidr` belongs to the same address family as the pool.
+    pub fn check_family(&self, cidr: &IpCidr) -> AllocateResult<()> {
+        match AddressFamily::from(cidr) == self.family() {
+            true => Ok(()),
+            false => Err(AllocateError::FamilyMismatch),
+        }
+    }
+
+    /// Whether `cidr` is exactly one of the pool's allocations. A subnet or
+    /// supernet of an allocation, or a free block, is not contained. See
+    /// `contains_range` to test whether a CIDR lies within the pool at all.
     pub fn contains(&self, cidr: &IpCidr) -> bool {
+        if self.check_family(cidr).is_err() {
+            return false;
+        }
         if let Some(subspace) = self.root.find_record(cidr) {
             return subspace.state == State::Allocated;
         }
         false
     }
 
+    /// Whether `cidr` lies within the pool's address range, regardless of
+    /// what is allocated.
+    pub fn contains_range(&self, cidr: &IpCidr) -> bool {
+        util::cidr_contains(self.cidr(), cidr)
+    }
+
     pub fn allocate(&mut self, bits: Bits, name: Option<&str>) -> AllocateResult<IpCidr> {
-        match self.root.allocate_free_space(bits, name) {
-            Some(cidr) => {
-                if let Some(name) = name {
-                    if self.names.contains_key(name) {
-                        return Err(AllocateError::DuplicateName);
+        self.allocate_at_time(bits, name, &util::now())
+    }
+
+    /// Allocate a subnet of `preferred_bits` host bits, or if there is no
+    /// room for one, the largest subnet down to `min_bits` that fits. The
+    /// size actually allocated is the prefix of the returned CIDR.
+    pub fn allocate_flexible(
+        &mut self,
+        preferred_bits: Bits,
+        min_bits: Bits,
+        name: Option<&str>,
+    ) -> AllocateResult<IpCidr> {
+        for bits in (min_bits..=preferred_bits).rev() {
+            match self.allocate(bits, name) {
+                Err(AllocateError::NoSpaceAvailable) => continue,
+                result => return result,
+            }
+        }
+        Err(AllocateError::NoSpaceAvailable)
+    }
+
+    /// Allocate a subnet at a place picked by `seed` from every place a
+    /// subnet of `bits` host bits fits, instead of the lowest one. The same
+    /// seed picks the same place in the same pool while different seeds
+    /// spread subnets across it.
+    pub fn allocate_spread(
+        &mut self,
+        bits: Bits,
+        name: Option<&str>,
+        seed: u64,
+    ) -> AllocateResult<IpCidr> {
+        let count = self.capacity_for(bits);
+        if count == 0 {
+            return Err(AllocateError::NoSpaceAvailable);
+        }
+        let mut index = util::seed_hash(seed) % count;
+        let cidr = self
+            .free_blocks()
+            .find_map(|block| {
+                let fits = util::host_length(block)
+                    .checked_sub(bits)
+                    .map(|spare| 1u128.checked_shl(spare as u32).unwrap_or(u128::MAX))
+                    .unwrap_or(0);
+                if index < fits {
+                    return Some(util::nth_subnet(block, bits, index));
+                }
+                index -= fits;
+                None
+            })
+            .ok_or(AllocateError::NoSpaceAvailable)?;
+        self.claim_record(&CidrRecord {
+            created_at: Some(util::now()),
+            ..CidrRecord::new(cidr, name)
+        })?;
+        Ok(cidr)
+    }
+
+    /// Allocate one subnet of `bits` host bits for each entry of `names`.
+    ///
+    /// Either every subnet is allocated or, on error, none are.
+    pub fn allocate_many(
+        &mut self,
+        bits: Bits,
+        names: &[Option<&str>],
+    ) -> AllocateResult<Vec<IpCidr>> {
+        let names: Vec<Option<&str>> = names.iter().map(|name| non_empty(*name)).collect();
+        let mut new_names = HashSet::new();
+        for name in names.iter().flatten() {
+            if self.names.contains_key(*name) || !new_names.insert(*name) {
+                return Err(AllocateError::DuplicateName);
+            }
+        }
+
+        let created_at = util::now();
+        let mut cidrs = Vec::with_capacity(names.len());
+        for name in &names {
+            match self.allocate_at_time(bits, *name, &created_at) {
+                Ok(cidr) => cidrs.push(cidr),
+                Err(err) => {
+                    for cidr in &cidrs {
+                        self.free(cidr).expect("allocated above");
                     }
-                    self.names.insert(name.to_string(), cidr);
+                    return Err(err);
                 }
-                Ok(cidr)
             }
-            None => Err(AllocateError::NoSpaceAvailable),
         }
+        Ok(cidrs)
     }
 
-    pub fn free(&mut self, cidr: &IpCidr) -> bool {
-        self.root.free(cidr)
+    /// Allocate a subnet for each `(hosts, name)` requirement, each just
+    /// large enough for that many usable hosts. Larger subnets are allocated
+    /// first so that the free space stays in as few blocks as possible. The
+    /// CIDRs are returned in the order of the requirements; if any cannot be
+    /// allocated, none are.
+    pub fn plan_vlsm(
+        &mut self,
+        requirements: &[(u128, Option<String>)],
+    ) -> AllocateResult<Vec<IpCidr>> {
+        let mut new_names = HashSet::new();
+        for (_, name) in requirements {
+            if let Some(name) = non_empty(name.as_deref()) {
+                if self.names.contains_key(name) || !new_names.insert(name) {
+                    return Err(AllocateError::DuplicateName);
+                }
+            }
+        }
+        let family = AddressFamily::from(self.cidr());
+        let mut plan = Vec::with_capacity(requirements.len());
+        for (index, (hosts, _)) in requirements.iter().enumerate() {
+            let bits =
+                util::host_bits_for(family, *hosts).ok_or(AllocateError::NoSpaceAvailable)?;
+            plan.push((index, bits));
+        }
+        plan.sort_by_key(|(index, bits)| (cmp::Reverse(*bits), *index));
+
+        let created_at = util::now();
+        let mut cidrs = vec![None; requirements.len()];
+        for (index, bits) in plan {
+            let name = requirements[index].1.as_deref();
+            match self.allocate_at_time(bits, name, &created_at) {
+                Ok(cidr) => cidrs[index] = Some(cidr),
+                Err(err) => {
+                    for cidr in cidrs.iter().flatten() {
+                        self.free(cidr).expect("allocated above");
+                    }
+                    return Err(err);
+                }
+            }
+        }
+        Ok(cidrs.into_iter().flatten().collect())
+    }
+
+    /// Allocate the largest block still available.
+    pub fn allocate_largest_available(&mut self, name: Option<&str>) -> AllocateResult<IpCidr> {
+        self.allocate(self.max_available_bits(), name)
+    }
+
+    /// Allocate a subnet from the free space inside `parent` only.
+    pub fn allocate_within(
+        &mut self,
+        bits: Bits,
+        name: Option<&str>,
+        parent: &IpCidr,
+    ) -> AllocateResult<IpCidr> {
+        self.check_family(parent)?;
+        let name = non_empty(name);
+        if let Some(name) = name {
+            if self.names.contains_key(name) {
+                return Err(AllocateError::DuplicateName);
+            }
+        }
+        let cidr = self
+            .root
+            .allocate_free_space_within(parent, bits, name, &util::now())
+            .ok_or(AllocateError::NoSpaceAvailable)?;
+        if let Some(name) = name {
+            self.names.insert(name.to_string(), cidr);
+        }
+        self.notify_allocated(&cidr);
+        Ok(cidr)
+    }
+
+    /// Allocate a subnet by its network prefix length rather than its host
+    /// bits, e.g. 24 for a /24.
+    pub fn allocate_prefix(
+        &mut self,
+        prefix_len: u8,
+        name: Option<&str>,
+    ) -> AllocateResult<IpCidr> {
+        if prefix_len > util::max_bits(self.cidr()) {
+            return Err(AllocateError::PrefixTooLong);
+        }
+        if prefix_len < self.cidr().network_length() {
+            return Err(AllocateError::PrefixTooShort);
+        }
+        self.allocate(util::max_bits(self.cidr()) - prefix_len, name)
+    }
+
+    /// Allocate a subnet named `base_name`, or `base_name` with the first
+    /// numeric suffix (`-1`, `-2`, ...) that is not already taken. Returns
+    /// the subnet and the name it was given.
+    pub fn allocate_unique_name(
+        &mut self,
+        bits: Bits,
+        base_name: &str,
+    ) -> AllocateResult<(IpCidr, String)> {
+        let name = (0..)
+            .map(|suffix| match suffix {
+                0 => base_name.to_string(),
+                suffix => format!("{base_name}-{suffix}"),
+            })
+            .find(|name| !self.names.contains_key(name))
+            .expect("unbounded suffixes");
+        let cidr = self.allocate(bits, Some(&name))?;
+        Ok((cidr, name))
+    }
+
+    /// Allocate a subnet and return its record.
+    pub fn allocate_record(
+        &mut self,
+        bits: Bits,
+        name: Option<&str>,
+    ) -> AllocateResult<&CidrRecord> {
+        let cidr = self.allocate(bits, name)?;
+        Ok(&self
+            .root
+            .find_record(&cidr)
+            .expect("allocated subnet is in pool")
+            .record)
+    }
+
+    /// Allocate a subnet recording `created_at` (RFC 3339) as its creation
+    /// time instead of the current time.
+    pub fn allocate_at_time(
+        &mut self,
+        bits: Bits,
+        name: Option<&str>,
+        created_at: &str,
+    ) -> AllocateResult<IpCidr> {
+        self.allocate_with_at_time(bits, name, AllocStrategy::LowFirst, created_at)
+    }
+
+    /// Allocate a subnet, placing it according to `strategy`.
+    pub fn allocate_with(
+        &mut self,
+        bits: Bits,
+        name: Option<&str>,
+        strategy: AllocStrategy,
+    ) -> AllocateResult<IpCidr> {
+        self.allocate_with_at_time(bits, name, strategy, &util::now())
+    }
+
+    fn allocate_with_at_time(
+        &mut self,
+        bits: Bits,
+        name: Option<&str>,
+        strategy: AllocStrategy,
+        created_at: &str,
+    ) -> AllocateResult<IpCidr> {
+        let name = non_empty(name);
+        if let Some(name) = name {
+            if self.names.contains_key(name) {
+                return Err(AllocateError::DuplicateName);
+            }
+        }
+        let cidr = self
+            .root
+            .allocate_free_space(bits, name, created_at, strategy)
+            .ok_or(AllocateError::NoSpaceAvailable)?;
+        if let Some(name) = name {
+            self.names.insert(name.to_string(), cidr);
+        }
+        self.notify_allocated(&cidr);
+        Ok(cidr)
+    }
+
+    /// Free an allocation. Locked allocations are kept and fail with
+    /// `AllocateError::Locked`.
+    pub fn free(&mut self, cidr: &IpCidr) -> AllocateResult<()> {
+        self.free_unless_locked(cidr, false)
+    }
+
+    /// Free an allocation even if it is locked.
+    pub fn force_free(&mut self, cidr: &IpCidr) -> AllocateResult<()> {
+        self.free_unless_locked(cidr, true)
+    }
+
+    fn free_unless_locked(&mut self, cidr: &IpCidr, force: bool) -> AllocateResult<()> {
+        self.check_family(cidr)?;
+        let name = match self.root.find_record(cidr) {
+            Some(subspace) if subspace.state == State::Allocated => {
+                if subspace.record.locked && !force {
+                    return Err(AllocateError::Locked);
+                }
+                subspace.record.name.clone()
+            }
+            _ => return Err(AllocateError::NoSuchObject),
+        };
+        if !self.root.free(cidr) {
+            return Err(AllocateError::NoSuchObject);
+        }
+        if let Some(name) = name {
+            self.names.remove(&name);
+        }
+        self.notify(|_| ChangeEvent::Freed(*cidr));
+        Ok(())
+    }
+
+    /// Lock or unlock an allocation. Locked allocations can only be freed or
+    /// renamed by force.
+    pub fn set_locked(&mut self, cidr: &IpCidr, locked: bool) -> AllocateResult<()> {
+        self.check_family(cidr)?;
+        match self.root.find_record_mut(cidr) {
+            Some(subspace) if subspace.state == State::Allocated => {
+                subspace.record.locked = locked;
+                Ok(())
+            }
+            _ => Err(AllocateError::NoSuchObject),
+        }
+    }
+
+    /// Free every unnamed allocation created before `time`, returning the
+    /// freed CIDRs in address order. Allocations without a creation time are
+    /// kept, as how old they are is not known, and so are locked ones.
+    pub fn reclaim_unnamed_older_than(&mut self, time: &DateTime<Utc>) -> Vec<IpCidr> {
+        let stale: Vec<IpCidr> = self
+            .records()
+            .filter(|record| record.name.is_none() && !record.locked)
+            .filter(|record| {
+                record
+                    .created_at
+                    .as_deref()
+                    .and_then(|created_at| DateTime::parse_from_rfc3339(created_at).ok())
+                    .is_some_and(|created_at| created_at < *time)
+            })
+            .map(|record| record.cidr)
+            .collect();
+        for cidr in &stale {
+            self.free(cidr).expect("unlocked allocation");
+        }
+        stale
+    }
+
+    /// Name the unnamed allocations in address order from `template`, which
+    /// may use `{index}` for the position of the allocation among the
+    /// unnamed ones as well as the fields of `format_str::record_fields`.
+    /// Allocations whose generated name is already taken stay unnamed.
+    ///
+    /// Returns the number of allocations named.
+    pub fn assign_names_from_template(&mut self, template: &str) -> FormatResult<usize> {
+        let segments = format_str::parse(template)?;
+        let mut names = Vec::new();
+        for (index, record) in self
+            .records()
+            .filter(|record| record.name.is_none())
+            .enumerate()
+        {
+            let mut fields = format_str::record_fields(record);
+            fields.insert("index".to_string(), index.to_string());
+            names.push((record.cidr, format_str::format(&segments, &fields)?));
+        }
+        Ok(names
+            .into_iter()
+            .filter(|(cidr, name)| self.rename(cidr, Some(name)).is_ok())
+            .count())
+    }
+
+    /// Free the allocation with the given name as `free` does.
+    pub fn free_by_name(&mut self, name: &str) -> AllocateResult<()> {
+        match self.find_by_name(name) {
+            Some(cidr) => self.free(&cidr),
+            None => Err(AllocateError::NoSuchObject),
+        }
+    }
+
+    /// Collapse every split part of the pool that is entirely free, leaving
+    /// the smallest tree that holds the allocations. `free` already merges
+    /// free halves as it goes, so this only tidies trees that were left split
+    /// some other way.
+    pub fn compact(&mut self) {
+        self.root.compact();
+    }
+
+    /// Free every allocation inside `range`, returning the freed records in
+    /// address order so they can be logged or claimed again. Allocations that
+    /// only partly overlap `range` are kept, as are locked ones.
+    pub fn free_all_within(&mut self, range: &IpCidr) -> Vec<CidrRecord> {
+        let records: Vec<CidrRecord> = self
+            .records_within(range)
+            .filter(|record| !record.locked)
+            .cloned()
+            .collect();
+        for record in &records {
+            self.free(&record.cidr).expect("unlocked allocation");
+        }
+        records
+    }
+
+    /// Split a block into unnamed child allocations of `bits` host bits each.
+    ///
+    /// The block may be free or a single allocation, in which case that
+    /// allocation (and its name) is replaced by the children unless it is
+    /// locked. Nothing is changed if the block overlaps any other allocation.
+    pub fn split(&mut self, cidr: &IpCidr, bits: Bits) -> AllocateResult<Vec<IpCidr>> {
+        if bits > util::host_length(cidr) || !util::cidr_contains(self.cidr(), cidr) {
+            return Err(AllocateError::NoSpaceAvailable);
+        }
+        let children = util::sub_networks(cidr, util::max_bits(cidr) - bits)
+            .expect("bits within cidr host length");
+
+        let parent = match self.root.find_record(cidr) {
+            Some(subspace) if subspace.state == State::Allocated => Some(subspace.record.clone()),
+            _ => None,
+        };
+        if parent.as_ref().is_some_and(|parent| parent.locked) {
+            return Err(AllocateError::Locked);
+        }
+        if let Some(parent) = &parent {
+            if let Some(name) = &parent.name {
+                self.names.remove(name);
+            }
+            self.root.free(cidr);
+        }
+
+        let created_at = util::now();
+        for (index, child) in children.iter().enumerate() {
+            let record = CidrRecord {
+                created_at: Some(created_at.clone()),
+                ..CidrRecord::new(*child, None)
+            };
+            if !self.root.claim(&record) {
+                for claimed in &children[..index] {
+                    self.root.free(claimed);
+                }
+                if let Some(parent) = parent {
+                    self.claim_record(&parent)
+                        .expect("parent was allocated before split");
+                }
+                return Err(AllocateError::NoSpaceAvailable);
+            }
+        }
+        if parent.is_some() {
+            self.notify(|_| ChangeEvent::Freed(*cidr));
+        }
+        for child in &children {
+            self.notify_allocated(child);
+        }
+        Ok(children)
+    }
+
+    /// Merge two allocations that are the halves of a common parent into a
+    /// single allocation of the parent. Locked allocations are not merged.
+    pub fn coalesce(
+        &mut self,
+        a: &IpCidr,
+        b: &IpCidr,
+        name: Option<&str>,
+    ) -> AllocateResult<IpCidr> {
+        let parent = match util::parent(a) {
+            Some(parent) if a != b && util::parent(b) == Some(parent) => parent,
+            _ => return Err(AllocateError::NotSiblings),
+        };
+        let name = non_empty(name);
+        if !self.contains(a) || !self.contains(b) {
+            return Err(AllocateError::NoSuchObject);
+        }
+
+        if [a, b]
+            .iter()
+            .any(|cidr| self.root.find_record(cidr).unwrap().record.locked)
+        {
+            return Err(AllocateError::Locked);
+        }
+        let sibling_names: Vec<String> = [a, b]
+            .iter()
+            .filter_map(|cidr| self.root.find_record(cidr)?.record.name.clone())
+            .collect();
+        if let Some(name) = name {
+            if self.names.contains_key(name) && !sibling_names.iter().any(|n| n == name) {
+                return Err(AllocateError::DuplicateName);
+            }
+        }
+
+        for sibling_name in &sibling_names {
+            self.names.remove(sibling_name);
+        }
+        self.root.free(a);
+        self.root.free(b);
+        self.notify(|_| ChangeEvent::Freed(*a));
+        self.notify(|_| ChangeEvent::Freed(*b));
+        self.claim(&parent, name)
+            .expect("both halves of parent were freed");
+        Ok(parent)
     }
 
     pub fn claim(&mut self, cidr: &IpCidr, name: Option<&str>) -> AllocateResult<()> {
+        self.claim_record(&CidrRecord {
+            created_at: Some(util::now()),
+            ..CidrRecord::new(*cidr, name)
+        })
+    }
+
+    /// Claim each `(cidr, name)` item, returning the outcome of each item
+    /// tried. Without `stop_on_error` every item is tried and the failed ones
+    /// are skipped. With it, claiming stops at the first failure and the
+    /// items claimed before it are freed again, leaving the pool as it was;
+    /// their outcomes are still reported as they were when claimed.
+    pub fn claim_batch(
+        &mut self,
+        items: &[(IpCidr, Option<String>)],
+        stop_on_error: bool,
+    ) -> Vec<(IpCidr, AllocateResult<()>)> {
+        let mut outcomes = Vec::with_capacity(items.len());
+        for (cidr, name) in items {
+            let result = self.claim(cidr, name.as_deref());
+            let failed = result.is_err();
+            outcomes.push((*cidr, result));
+            if failed && stop_on_error {
+                for (cidr, _) in outcomes.iter().filter(|(_, result)| result.is_ok()) {
+                    self.free(cidr).expect("claimed above");
+                }
+                break;
+            }
+        }
+        outcomes
+    }
+
+    /// Claim a subnet unless it is already allocated under the same name,
+    /// so that desired state can be applied again safely. Returns whether
+    /// the subnet was newly claimed. Claiming an allocated subnet under a
+    /// different name, or one overlapping other allocations, fails as
+    /// `claim` does.
+    pub fn claim_idempotent(&mut self, cidr: &IpCidr, name: Option<&str>) -> AllocateResult<bool> {
+        let name = non_empty(name);
+        match self.root.find_record(cidr) {
+            Some(subspace)
+                if subspace.state == State::Allocated
+                    && subspace.record.name.as_deref() == name =>
+            {
+                Ok(false)
+            }
+            _ => self.claim(cidr, name).map(|()| true),
+        }
+    }
+
+    /// Claim a subnet along with its description and tags.
+    pub fn claim_full(
+        &mut self,
+        cidr: &IpCidr,
+        name: Option<&str>,
+        description: Option<&str>,
+        tags: &[&str],
+    ) -> AllocateResult<()> {
+        self.claim_record(&CidrRecord {
+            created_at: Some(util::now()),
+            description: description.map(|description| description.to_string()),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            ..CidrRecord::new(*cidr, name)
+        })
+    }
+
+    /// Iterate over the largest unallocated blocks inside `within` in
+    /// address order. When `within` lies in a single free block, `within`
+    /// itself is the only block.
+    pub fn iter_free_within(&self, within: &IpCidr) -> impl Iterator<Item = IpCidr> + '_ {
+        let within = *within;
+        let start = match util::cidr_contains(&within, self.cidr()) {
+            true => Some(&self.root),
+            false => self.root.find_containing(&within),
+        };
+        start
+            .into_iter()
+            .flat_map(|subspace| self.iter_leaf_subspaces_from(subspace))
+            .filter(|subspace| subspace.state == State::Free)
+            .filter_map(move |subspace| {
+                let block = subspace.record.cidr;
+                if util::cidr_contains(&within, &block) {
+                    Some(block)
+                } else if util::cidr_contains(&block, &within) {
+                    Some(within)
+                } else {
+                    None
+                }
+            })
+    }
+
+    /// Claim every free block within `range`, leaving existing allocations
+    /// in place. Each block is named by `name_fn`.
+    ///
+    /// Either every block is claimed or, on error, none are.
+    pub fn claim_free_within(
+        &mut self,
+        range: &IpCidr,
+        name_fn: impl Fn(&IpCidr) -> Option<String>,
+    ) -> AllocateResult<Vec<IpCidr>> {
+        self.check_family(range)?;
+        if !util::cidr_contains(self.cidr(), range) && !util::cidr_contains(range, self.cidr()) {
+            return Err(AllocateError::NoSpaceAvailable);
+        }
+        let blocks: Vec<IpCidr> = self
+            .free_blocks()
+            .filter_map(|block| {
+                if util::cidr_contains(range, block) {
+                    Some(*block)
+                } else if util::cidr_contains(block, range) {
+                    Some(*range)
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        for (index, block) in blocks.iter().enumerate() {
+            if let Err(err) = self.claim(block, name_fn(block).as_deref()) {
+                for claimed in &blocks[..index] {
+                    self.free(claimed).expect("claimed above");
+                }
+                return Err(err);
+            }
+        }
+        Ok(blocks)
+    }
+
+    fn claim_record(&mut self, record: &CidrRecord) -> AllocateResult<()> {
+        if record.name.as_deref() == Some("") {
+            return self.claim_record(&CidrRecord {
+                name: None,
+                ..record.clone()
+            });
+        }
+        let cidr = &record.cidr;
+        self.check_family(cidr)?;
+        let name = record.name.as_deref();
         if let Some(name) = name {
             if self.names.contains_key(name) {
                 return Err(AllocateError::DuplicateName);
             }
-            self.names.insert(name.to_string(), *cidr);
         }
-        if self.root.claim(cidr, name) {
-            return Ok(());
+        if !self.root.claim(record) {
+            return Err(AllocateError::NoSpaceAvailable);
         }
-        Err(AllocateError::NoSpaceAvailable)
+        if let Some(name) = name {
+            self.names.insert(name.to_string(), *cidr);
+        }
+        self.notify_allocated(cidr);
+        Ok(())
     }
 
+    /// Rename an allocation. Locked allocations keep their name and fail
+    /// with `RenameError::Locked`.
     pub fn rename(&mut self, cidr: &IpCidr, name: Option<&str>) -> RenameResult<()> {
+        self.rename_unless_locked(cidr, name, false)
+    }
+
+    /// Rename an allocation even if it is locked.
+    pub fn force_rename(&mut self, cidr: &IpCidr, name: Option<&str>) -> RenameResult<()> {
+        self.rename_unless_locked(cidr, name, true)
+    }
+
+    fn rename_unless_locked(
+        &mut self,
+        cidr: &IpCidr,
+        name: Option<&str>,
+        force: bool,
+    ) -> RenameResult<()> {
+        let name = non_empty(name);
+        if self.check_family(cidr).is_err() {
+            return Err(RenameError::FamilyMismatch);
+        }
+
         // Find record that is being renamed
         let subspace: &mut Subspace = match self.root.find_record_mut(cidr) {
             Some(record) => record,
@@ -126,6 +1186,10 @@ impl SubnetPool {
             return Ok(());
         }
 
+        if subspace.record.locked && !force {
+            return Err(RenameError::Locked);
+        }
+
         // Check that name does not already exist
         if let Some(name) = name {
             if self.names.contains_key(name) {
@@ -140,23 +1204,307 @@ impl SubnetPool {
         }
 
         // Update record name
-        subspace.record.name = name.map(|name| name.to_string());
+        let old = std::mem::replace(&mut subspace.record.name, name.map(|name| name.to_string()));
+        self.notify_renamed(cidr, old, name);
+        Ok(())
+    }
+
+    /// Set the name, description and tags of an allocation together,
+    /// reporting which of them actually changed. The name of a locked
+    /// allocation cannot be changed this way.
+    pub fn update_metadata(
+        &mut self,
+        cidr: &IpCidr,
+        name: Option<&str>,
+        description: Option<&str>,
+        tags: &[&str],
+    ) -> RenameResult<MetadataUpdate> {
+        let name = non_empty(name);
+        if self.check_family(cidr).is_err() {
+            return Err(RenameError::FamilyMismatch);
+        }
+        let subspace = match self.root.find_record_mut(cidr) {
+            Some(subspace) if subspace.state == State::Allocated => subspace,
+            _ => return Err(RenameError::NoSuchObject),
+        };
+        let record = &mut subspace.record;
+        let update = MetadataUpdate {
+            name: record.name.as_deref() != name,
+            description: record.description.as_deref() != description,
+            tags: !record.tags.iter().eq(tags.iter().copied()),
+        };
+
+        let mut old_name = None;
+        if update.name {
+            if record.locked {
+                return Err(RenameError::Locked);
+            }
+            if let Some(name) = name {
+                if self.names.contains_key(name) {
+                    return Err(RenameError::DuplicateName);
+                }
+                self.names.insert(name.to_string(), *cidr);
+            }
+            if let Some(record_name) = &record.name {
+                self.names.remove(record_name);
+            }
+            old_name = std::mem::replace(&mut record.name, name.map(|name| name.to_string()));
+        }
+        if update.description {
+            record.description = description.map(|description| description.to_string());
+        }
+        if update.tags {
+            record.tags = tags.iter().map(|tag| tag.to_string()).collect();
+        }
+        if update.name {
+            self.notify_renamed(cidr, old_name, name);
+        }
+        Ok(update)
+    }
+
+    /// Exchange the names of two allocations in one step, so that neither
+    /// name is ever unassigned or duplicated.
+    pub fn swap_names(&mut self, cidr_a: &IpCidr, cidr_b: &IpCidr) -> RenameResult<()> {
+        if !self.contains(cidr_a) || !self.contains(cidr_b) {
+            return Err(RenameError::NoSuchObject);
+        }
+        if cidr_a == cidr_b {
+            return Ok(());
+        }
+        if [cidr_a, cidr_b]
+            .iter()
+            .any(|cidr| self.root.find_record(cidr).unwrap().record.locked)
+        {
+            return Err(RenameError::Locked);
+        }
+
+        let name_a = self
+            .root
+            .find_record_mut(cidr_a)
+            .unwrap()
+            .record
+            .name
+            .take();
+        let name_b = self
+            .root
+            .find_record_mut(cidr_b)
+            .unwrap()
+            .record
+            .name
+            .take();
+        if let Some(name) = &name_a {
+            self.names.insert(name.clone(), *cidr_b);
+        }
+        if let Some(name) = &name_b {
+            self.names.insert(name.clone(), *cidr_a);
+        }
+        self.root.find_record_mut(cidr_a).unwrap().record.name = name_b;
+        self.root.find_record_mut(cidr_b).unwrap().record.name = name_a;
+        for (cidr, old_cidr) in [(cidr_a, cidr_b), (cidr_b, cidr_a)] {
+            self.notify(|pool| ChangeEvent::Renamed {
+                cidr: *cidr,
+                old: pool.root.find_record(old_cidr).unwrap().record.name.clone(),
+                new: pool.root.find_record(cidr).unwrap().record.name.clone(),
+            });
+        }
         Ok(())
     }
 
+    /// Build a pool rooted at the smallest network enclosing every record and
+    /// claim each record in it.
+    pub fn enclosing(records: &[CidrRecord]) -> AllocateResult<SubnetPool> {
+        let (first, rest) = records.split_first().ok_or(AllocateError::NoSuchObject)?;
+        let mut root = first.cidr;
+        for record in rest {
+            root = util::supernet(&root, &record.cidr).ok_or(AllocateError::FamilyMismatch)?;
+        }
+        let mut pool = SubnetPool::new(root);
+        for record in records {
+            pool.claim_record(record)?;
+        }
+        Ok(pool)
+    }
+
+    /// Copy the pool to a new root of the same size, keeping the relative
+    /// position and name of every allocation.
+    pub fn renumber(&self, new_root: IpCidr) -> AllocateResult<SubnetPool> {
+        if util::max_bits(&new_root) != util::max_bits(self.cidr())
+            || new_root.network_length() != self.cidr().network_length()
+        {
+            return Err(AllocateError::SizeMismatch);
+        }
+        let mut pool = SubnetPool::new(new_root);
+        pool.description.clone_from(&self.description);
+        pool.metadata.clone_from(&self.metadata);
+        for record in self.records() {
+            pool.claim_record(&CidrRecord {
+                cidr: util::rebase(&record.cidr, self.cidr(), &new_root),
+                ..record.clone()
+            })?;
+        }
+        Ok(pool)
+    }
+
+    /// Compare allocations by CIDR, describing how to get from this pool to
+    /// `other`.
+    pub fn diff(&self, other: &SubnetPool) -> PoolDiff {
+        let mut diff = PoolDiff::default();
+        let mut ours = self.records().peekable();
+        let mut theirs = other.records().peekable();
+        loop {
+            match (ours.peek(), theirs.peek()) {
+                (Some(our), Some(their)) if our.cidr == their.cidr => {
+                    if our.name != their.name {
+                        diff.renamed
+                            .push((our.cidr, our.name.clone(), their.name.clone()));
+                    }
+                    ours.next();
+                    theirs.next();
+                }
+                (Some(our), Some(their)) if our.cidr < their.cidr => {
+                    diff.removed.push((*our).clone());
+                    ours.next();
+                }
+                (Some(_), Some(their)) => {
+                    diff.added.push((*their).clone());
+                    theirs.next();
+                }
+                (Some(our), None) => {
+                    diff.removed.push((*our).clone());
+                    ours.next();
+                }
+                (None, Some(their)) => {
+                    diff.added.push((*their).clone());
+                    theirs.next();
+                }
+                (None, None) => return diff,
+            }
+        }
+    }
+
+    /// Check that the allocations and the name index agree, returning every
+    /// inconsistency found.
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let mut errors = Vec::new();
+        let records: Vec<&CidrRecord> = self.records().collect();
+        if records.len() != self.allocated_count() {
+            errors.push(ValidationError::CountMismatch {
+                counted: self.allocated_count(),
+                found: records.len(),
+            });
+        }
+        for (index, record) in records.iter().enumerate() {
+            if !util::cidr_contains(self.cidr(), &record.cidr) {
+                errors.push(ValidationError::OutOfRoot(record.cidr));
+            }
+            if let Some(next) = records.get(index + 1) {
+                if record.cidr.last_address() >= next.cidr.first_address() {
+                    errors.push(ValidationError::Overlap(record.cidr, next.cidr));
+                }
+            }
+            if let Some(name) = &record.name {
+                if self.names.get(name) != Some(&record.cidr) {
+                    errors.push(ValidationError::UnindexedName {
+                        name: name.clone(),
+                        cidr: record.cidr,
+                    });
+                }
+            }
+        }
+        for (name, cidr) in self.names_with_cidrs() {
+            let named = match self.root.find_record(&cidr) {
+                Some(subspace) if subspace.state == State::Allocated => {
+                    subspace.record.name.as_ref() == Some(&name)
+                }
+                _ => false,
+            };
+            if !named {
+                errors.push(ValidationError::StaleName { name, cidr });
+            }
+        }
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(errors),
+        }
+    }
+
     pub fn names(&self) -> impl Iterator<Item = String> + '_ {
         self.names.keys().map(|name| name.to_string())
     }
 
+    /// Every name paired with its CIDR, sorted by name.
+    pub fn names_with_cidrs(&self) -> Vec<(String, IpCidr)> {
+        let mut names: Vec<(String, IpCidr)> = self
+            .names
+            .iter()
+            .map(|(name, cidr)| (name.clone(), *cidr))
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Names starting with `prefix`, in no particular order.
+    pub fn names_matching_prefix<'a>(
+        &'a self,
+        prefix: &'a str,
+    ) -> impl Iterator<Item = &'a str> + 'a {
+        self.names
+            .keys()
+            .map(String::as_str)
+            .filter(move |name| name.starts_with(prefix))
+    }
+
+    /// Number of names starting with `prefix`, such as how many subnets
+    /// follow a `prod-` naming convention.
+    pub fn names_starting_with(&self, prefix: &str) -> usize {
+        self.names_matching_prefix(prefix).count()
+    }
+
+    /// Iterate over allocated CIDRs in ascending address order.
     pub fn cidrs(&self) -> impl Iterator<Item = &IpCidr> {
         self.iter_allocated_subspaces()
             .map(|subspace| &subspace.record.cidr)
     }
 
+    /// Render the subspaces of the pool as a tree, one per line. Allocated
+    /// subnets are followed by their name, or `-` when unnamed, and free
+    /// blocks by `(free)`.
+    pub fn pretty_tree_string(&self) -> String {
+        let mut tree = String::new();
+        write_tree(&mut tree, &self.root, "", "");
+        tree
+    }
+
+    /// Iterate over allocated records in ascending address order. Each
+    /// iteration makes a single, fixed-size allocation for its traversal
+    /// stack.
     pub fn records(&self) -> Box<dyn Iterator<Item = &CidrRecord> + '_> {
         self.records_within(&self.root.record.cidr)
     }
 
+    /// Iterate over the allocated records for editing their descriptions and
+    /// tags in bulk.
+    pub fn records_mut(&mut self) -> impl Iterator<Item = RecordMut<'_>> {
+        let mut stack = vec![&mut self.root];
+        std::iter::from_fn(move || {
+            while let Some(subspace) = stack.pop() {
+                match subspace.state {
+                    State::Allocated => {
+                        return Some(RecordMut {
+                            record: &mut subspace.record,
+                        })
+                    }
+                    State::Free => {}
+                    State::Unavailable => {
+                        stack.push(subspace.high.as_deref_mut().unwrap());
+                        stack.push(subspace.low.as_deref_mut().unwrap());
+                    }
+                }
+            }
+            None
+        })
+    }
+
     pub fn records_within(&self, cidr: &IpCidr) -> Box<dyn Iterator<Item = &CidrRecord> + '_> {
         let start_cidr = match crate::util::cidr_contains(cidr, &self.root.record.cidr) {
             true => &self.root.record.cidr,
@@ -172,94 +1520,234 @@ impl SubnetPool {
     }
 }
 
+/// Append `subspace` and everything below it to `tree`. `connector` leads
+/// the line of the subspace and `prefix` the lines of its children.
+fn write_tree(tree: &mut String, subspace: &Subspace, connector: &str, prefix: &str) {
+    tree.push_str(connector);
+    tree.push_str(&subspace.record.cidr.to_string());
+    match subspace.state {
+        State::Allocated => {
+            tree.push(' ');
+            tree.push_str(subspace.record.name.as_deref().unwrap_or("-"));
+        }
+        State::Free => tree.push_str(" (free)"),
+        State::Unavailable => {}
+    }
+    tree.push('\n');
+    if subspace.state == State::Unavailable {
+        let low = subspace.low.as_deref().unwrap();
+        let high = subspace.high.as_deref().unwrap();
+        write_tree(
+            tree,
+            low,
+            &format!("{prefix}├── "),
+            &format!("{prefix}│   "),
+        );
+        write_tree(
+            tree,
+            high,
+            &format!("{prefix}└── "),
+            &format!("{prefix}    "),
+        );
+    }
+}
+
+/// Create an empty pool from a CIDR such as `"10.0.0.0/8"`.
+impl FromStr for SubnetPool {
+    type Err = CreateError;
+
+    fn from_str(cidr: &str) -> CreateResult<Self> {
+        cidr.parse::<IpCidr>()
+            .map(SubnetPool::new)
+            .map_err(|err| CreateError::InvalidCidr(err.to_string()))
+    }
+}
+
+impl<'a> IntoIterator for &'a SubnetPool {
+    type Item = &'a CidrRecord;
+    type IntoIter = Box<dyn Iterator<Item = &'a CidrRecord> + 'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.records()
+    }
+}
+
 impl serde::Serialize for SubnetPool {
     fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
-        let mut pool = serializer.serialize_struct("SubnetPool", 2)?;
+        let human_readable = serializer.is_human_readable();
+        // Records are written in address order so stored pools diff cleanly.
+        let mut records: Vec<&CidrRecord> = self.records().collect();
+        records.sort();
+        let mut pool = serializer.serialize_struct("SubnetPool", 4)?;
         pool.serialize_field("cidr", &self.root.record.cidr.to_string())?;
-        let records: Vec<&CidrRecord> = self.records().collect();
+        // Binary formats are read back as a sequence, so every field is
+        // written in the order the sequence is read.
+        if !human_readable {
+            pool.serialize_field("subnets", &records)?;
+            pool.serialize_field("description", &self.description)?;
+            pool.serialize_field("metadata", &self.metadata)?;
+            return pool.end();
+        }
+        if let Some(description) = &self.description {
+            pool.serialize_field("description", description)?;
+        }
+        if !self.metadata.is_empty() {
+            pool.serialize_field("metadata", &self.metadata)?;
+        }
         pool.serialize_field("subnets", &records)?;
 
         pool.end()
     }
 }
 
-impl<'s> serde::Deserialize<'s> for SubnetPool {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-    where
-        D: serde::Deserializer<'s>,
-    {
-        #[derive(serde::Deserialize)]
-        #[serde(field_identifier, rename_all = "lowercase")]
-        enum Field {
-            Cidr,
-            Subnets,
-        }
-
-        fn load_cidrs(
-            entries: &Vec<CidrRecord>,
-            cidr: &IpCidr,
-        ) -> Result<SubnetPool, AllocateError> {
-            let mut pool = SubnetPool::new(*cidr);
-            for entry in entries {
-                let entry_name = entry.name.as_deref();
-                pool.claim(&entry.cidr, entry_name)?;
+#[derive(serde::Deserialize)]
+#[serde(field_identifier, rename_all = "lowercase")]
+enum PoolField {
+    Cidr,
+    Description,
+    Metadata,
+    Subnets,
+}
+
+/// Claim the entries of a pool file in a new pool. Leniently, entries
+/// overlapping earlier ones are skipped and returned instead of failing.
+fn load_cidrs<E: serde::de::Error>(
+    entries: Vec<CidrRecord>,
+    cidr: &IpCidr,
+    lenient: bool,
+) -> Result<LenientPool, E> {
+    // Claiming a CIDR twice would only fail for lack of space, so
+    // repeated CIDRs are reported by name first.
+    if !lenient {
+        let mut seen = HashSet::new();
+        for entry in &entries {
+            if !seen.insert(entry.cidr) {
+                return Err(E::custom(format!("Duplicate CIDR: {}", entry.cidr)));
             }
-            Ok(pool)
         }
-        struct SubnetPoolVisitor;
-        impl<'s> serde::de::Visitor<'s> for SubnetPoolVisitor {
-            type Value = SubnetPool;
+    }
+    let mut pool = SubnetPool::new(*cidr);
+    let mut skipped = Vec::new();
+    for entry in entries {
+        if lenient && pool.overlapping(&entry.cidr).is_some() {
+            skipped.push(entry);
+            continue;
+        }
+        pool.claim_record(&entry).map_err(E::custom)?;
+    }
+    Ok(LenientPool { pool, skipped })
+}
 
-            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-                formatter.write_str("struct SubspacePool")
-            }
-            fn visit_seq<V>(self, mut seq: V) -> Result<SubnetPool, V::Error>
-            where
-                V: serde::de::SeqAccess<'s>,
-            {
-                let cidr = seq
-                    .next_element::<&str>()?
-                    .ok_or_else(|| serde::de::Error::missing_field("cidr"))?;
-                let cidr = cidr.parse::<IpCidr>().map_err(serde::de::Error::custom)?;
-                let entries = seq
-                    .next_element::<Vec<CidrRecord>>()?
-                    .ok_or_else(|| serde::de::Error::missing_field("subnets"))?;
-
-                load_cidrs(&entries, &cidr).map_err(serde::de::Error::custom)
-            }
-            fn visit_map<V>(self, mut map: V) -> Result<Self::Value, V::Error>
-            where
-                V: serde::de::MapAccess<'s>,
-            {
-                let mut cidr: Option<IpCidr> = None;
-                let mut entries: Option<Vec<CidrRecord>> = None;
-                while let Some(key) = map.next_key()? {
-                    match key {
-                        Field::Cidr => {
-                            if cidr.is_some() {
-                                return Err(serde::de::Error::duplicate_field("cidr"));
-                            }
-                            let cidr_string = map.next_value::<String>()?;
-                            cidr = Some(
-                                cidr_string
-                                    .parse::<IpCidr>()
-                                    .map_err(serde::de::Error::custom)?,
-                            );
-                        }
-                        Field::Subnets => {
-                            if entries.is_some() {
-                                return Err(serde::de::Error::duplicate_field("subnets"));
-                            }
-                            entries = Some(map.next_value()?);
-                        }
+struct SubnetPoolVisitor {
+    lenient: bool,
+}
+
+impl<'s> serde::de::Visitor<'s> for SubnetPoolVisitor {
+    type Value = LenientPool;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("struct SubspacePool")
+    }
+    fn visit_seq<V>(self, mut seq: V) -> Result<Self::Value, V::Error>
+    where
+        V: serde::de::SeqAccess<'s>,
+    {
+        let cidr = seq
+            .next_element::<&str>()?
+            .ok_or_else(|| serde::de::Error::missing_field("cidr"))?;
+        let cidr = cidr.parse::<IpCidr>().map_err(serde::de::Error::custom)?;
+        let entries = seq
+            .next_element::<Vec<CidrRecord>>()?
+            .ok_or_else(|| serde::de::Error::missing_field("subnets"))?;
+        let description = seq.next_element::<Option<String>>()?.flatten();
+        let metadata = seq
+            .next_element::<BTreeMap<String, String>>()?
+            .unwrap_or_default();
+
+        let mut loaded = load_cidrs(entries, &cidr, self.lenient)?;
+        loaded.pool.description = description;
+        loaded.pool.metadata = metadata;
+        Ok(loaded)
+    }
+    fn visit_map<V>(self, mut map: V) -> Result<Self::Value, V::Error>
+    where
+        V: serde::de::MapAccess<'s>,
+    {
+        let mut cidr: Option<IpCidr> = None;
+        let mut entries: Option<Vec<CidrRecord>> = None;
+        let mut description: Option<String> = None;
+        let mut metadata: Option<BTreeMap<String, String>> = None;
+        while let Some(key) = map.next_key()? {
+            match key {
+                PoolField::Cidr => {
+                    if cidr.is_some() {
+                        return Err(serde::de::Error::duplicate_field("cidr"));
+                    }
+                    let cidr_string = map.next_value::<String>()?;
+                    cidr = Some(
+                        cidr_string
+                            .parse::<IpCidr>()
+                            .map_err(serde::de::Error::custom)?,
+                    );
+                }
+                PoolField::Description => {
+                    if description.is_some() {
+                        return Err(serde::de::Error::duplicate_field("description"));
+                    }
+                    description = Some(map.next_value()?);
+                }
+                PoolField::Metadata => {
+                    if metadata.is_some() {
+                        return Err(serde::de::Error::duplicate_field("metadata"));
                     }
+                    metadata = Some(map.next_value()?);
+                }
+                PoolField::Subnets => {
+                    if entries.is_some() {
+                        return Err(serde::de::Error::duplicate_field("subnets"));
+                    }
+                    entries = Some(map.next_value()?);
                 }
-                let cidr = cidr.ok_or_else(|| serde::de::Error::missing_field("cidr"))?;
-                let subnets = entries.ok_or_else(|| serde::de::Error::missing_field("subnets"))?;
-                load_cidrs(&subnets, &cidr).map_err(serde::de::Error::custom)
             }
         }
-        const FIELDS: &[&str] = &["cidr", "subnets"];
-        deserializer.deserialize_struct("SubnetPool", FIELDS, SubnetPoolVisitor)
+        let cidr = cidr.ok_or_else(|| serde::de::Error::missing_field("cidr"))?;
+        let subnets = entries.ok_or_else(|| serde::de::Error::missing_field("subnets"))?;
+        let mut loaded = load_cidrs(subnets, &cidr, self.lenient)?;
+        loaded.pool.description = description;
+        loaded.pool.metadata = metadata.unwrap_or_default();
+        Ok(loaded)
+    }
+}
+
+const POOL_FIELDS: &[&str] = &["cidr", "description", "metadata", "subnets"];
+
+impl<'s> serde::Deserialize<'s> for SubnetPool {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'s>,
+    {
+        let visitor = SubnetPoolVisitor { lenient: false };
+        let loaded = deserializer.deserialize_struct("SubnetPool", POOL_FIELDS, visitor)?;
+        Ok(loaded.pool)
+    }
+}
+
+/// A pool read leniently, for recovering a partly broken pool file. Subnets
+/// overlapping an earlier subnet of the file, including repeated ones, are
+/// skipped instead of failing the whole pool.
+#[derive(Debug, PartialEq)]
+pub struct LenientPool {
+    pub pool: SubnetPool,
+    /// Skipped subnets in the order of the file
+    pub skipped: Vec<CidrRecord>,
+}
+
+impl<'s> serde::Deserialize<'s> for LenientPool {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'s>,
+    {
+        let visitor = SubnetPoolVisitor { lenient: true };
+        deserializer.deserialize_struct("SubnetPool", POOL_FIELDS, visitor)
     }
 }
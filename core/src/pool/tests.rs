@@ -18,12 +18,49 @@ fn new_test_pool6() -> SubnetPool {
     SubnetPool::new(TEST_CIDR6)
 }
 
+fn test_record(cidr: IpCidr, name: Option<&str>) -> CidrRecord {
+    CidrRecord {
+        created_at: Some(TEST_TIME.to_string()),
+        ..CidrRecord::new(cidr, name)
+    }
+}
+
 #[test]
 fn test_cidr() {
     let pool = new_test_pool();
     assert_eq!(pool.cidr(), &TEST_CIDR4);
 }
 
+mod family {
+    use super::*;
+
+    #[test]
+    fn v4() {
+        let pool = new_test_pool();
+        assert_eq!(pool.family(), AddressFamily::Ipv4);
+        assert!(pool.is_ipv4());
+        assert!(!pool.is_ipv6());
+    }
+
+    #[test]
+    fn v6() {
+        let pool = new_test_pool6();
+        assert_eq!(pool.family(), AddressFamily::Ipv6);
+        assert!(!pool.is_ipv4());
+        assert!(pool.is_ipv6());
+    }
+
+    #[test]
+    fn check_family() {
+        let pool = new_test_pool();
+        assert_eq!(pool.check_family(&TEST_CIDR4), Ok(()));
+        assert_eq!(
+            pool.check_family(&TEST_CIDR6),
+            Err(AllocateError::FamilyMismatch)
+        );
+    }
+}
+
 mod contains {
     use super::*;
 
@@ -46,6 +83,36 @@ mod contains {
         let allocated = pool.allocate(4, None).unwrap();
         assert!(pool.contains(&allocated));
     }
+
+    #[test]
+    fn family_mismatch() {
+        let mut pool = new_test_pool6();
+        pool.allocate(16, None).unwrap();
+        assert!(!pool.contains(&TEST_CIDR4));
+    }
+}
+
+mod contains_range {
+    use super::*;
+
+    #[test]
+    fn within_range() {
+        let mut pool = new_test_pool();
+        let allocated = pool.allocate(4, None).unwrap();
+        let free = IpCidr::from_str("10.20.1.0/24").unwrap();
+        assert!(pool.contains_range(&allocated));
+        assert!(pool.contains_range(&free));
+        assert!(pool.contains_range(&TEST_CIDR4));
+        assert!(!pool.contains(&free));
+    }
+
+    #[test]
+    fn out_of_range() {
+        let pool = new_test_pool();
+        for cidr in ["10.20.0.0/15", "10.21.0.0/24", "1:2:3:4:a:14::/120"] {
+            assert!(!pool.contains_range(&IpCidr::from_str(cidr).unwrap()));
+        }
+    }
 }
 
 mod named_count {
@@ -66,371 +133,3367 @@ mod named_count {
     }
 }
 
-mod allocate {
+mod free_blocks {
     use super::*;
 
     #[test]
-    fn too_many_bits() {
-        let mut pool = new_test_pool();
-        let result = pool.allocate(17, None);
-        assert_eq!(result.err(), Some(AllocateError::NoSpaceAvailable));
-        assert_eq!(pool.allocated_count(), pool.cidrs().count());
+    fn empty_pool() {
+        let pool = new_test_pool();
+        assert_eq!(pool.free_blocks().collect::<Vec<_>>(), vec![&TEST_CIDR4]);
     }
 
     #[test]
-    fn no_pool_available() {
+    fn full_pool() {
         let mut pool = new_test_pool();
         pool.allocate(16, None).unwrap();
-        let result = pool.allocate(16, None);
-        assert_eq!(result.err(), Some(AllocateError::NoSpaceAvailable));
-        assert_eq!(pool.allocated_count(), pool.cidrs().count());
+        assert_eq!(pool.free_blocks().count(), 0);
     }
 
     #[test]
-    fn allocate_name_already_exists() {
+    fn partially_allocated() {
         let mut pool = new_test_pool();
-        pool.allocate(4, Some("a-name")).unwrap();
-        let result = pool.allocate(4, Some("a-name"));
-        assert_eq!(result.err(), Some(AllocateError::DuplicateName));
-        assert_eq!(pool.allocated_count(), pool.cidrs().count());
+        pool.allocate(14, None).unwrap();
+        pool.allocate(13, None).unwrap();
+        assert_eq!(
+            pool.free_blocks()
+                .map(|cidr| cidr.to_string())
+                .collect_vec(),
+            vec!["10.20.96.0/19", "10.20.128.0/17"]
+        );
+    }
+}
+
+mod max_available_bits_within {
+    use super::*;
+
+    fn bits_within(pool: &SubnetPool, within: &str) -> Bits {
+        pool.max_available_bits_within(&IpCidr::from_str(within).unwrap())
     }
 
     #[test]
-    fn name_is_not_cidr_record() {
+    fn with_free_space() {
         let mut pool = new_test_pool();
-        pool.allocate(4, Some("10.20.0.16/28")).unwrap();
-        let result = pool.allocate(4, None);
-        assert_eq!(result.err(), None);
-        assert_eq!(pool.allocated_count(), pool.cidrs().count());
+        pool.allocate(14, None).unwrap();
+        pool.allocate(12, None).unwrap();
+        assert_eq!(bits_within(&pool, "10.20.64.0/18"), 13);
+        assert_eq!(bits_within(&pool, "10.20.80.0/20"), 12);
+        assert_eq!(bits_within(&pool, "10.20.128.0/17"), 15);
+        assert_eq!(bits_within(&pool, "10.20.200.0/24"), 8);
+        assert_eq!(bits_within(&pool, "10.0.0.0/8"), 15);
     }
 
     #[test]
-    fn allocate_named() {
+    fn without_free_space() {
         let mut pool = new_test_pool();
-        let result = pool.allocate(4, Some("a-name")).unwrap();
-        let looked_up = pool.find_by_name("a-name").unwrap();
-        assert_eq!(looked_up, result);
-        assert_eq!(pool.allocated_count(), pool.cidrs().count());
+        pool.allocate(14, None).unwrap();
+        pool.allocate(12, None).unwrap();
+        assert_eq!(bits_within(&pool, "10.20.0.0/18"), 0);
+        assert_eq!(bits_within(&pool, "10.20.1.0/24"), 0);
+        assert_eq!(bits_within(&pool, "10.20.64.0/20"), 0);
     }
 
     #[test]
-    fn allocate_success_v4() {
+    fn outside_pool() {
+        let pool = new_test_pool();
+        assert_eq!(bits_within(&pool, "10.21.0.0/24"), 0);
+        assert_eq!(pool.max_available_bits_within(&TEST_CIDR6), 0);
+    }
+}
+
+mod iter_free_within {
+    use super::*;
+
+    fn free_within(pool: &SubnetPool, within: &str) -> Vec<String> {
+        pool.iter_free_within(&IpCidr::from_str(within).unwrap())
+            .map(|cidr| cidr.to_string())
+            .collect_vec()
+    }
+
+    #[test]
+    fn partially_allocated() {
         let mut pool = new_test_pool();
-        let result = pool.allocate(4, None).unwrap();
+        pool.allocate(14, None).unwrap();
+        pool.allocate(12, None).unwrap();
         assert_eq!(
-            result,
-            IpCidr::V4(Ipv4Cidr::new(Ipv4Addr::new(10, 20, 0, 0), 28).unwrap())
+            free_within(&pool, "10.20.64.0/18"),
+            vec!["10.20.80.0/20", "10.20.96.0/19"]
+        );
+        assert_eq!(free_within(&pool, "10.20.0.0/18"), Vec::<String>::new());
+        assert_eq!(
+            free_within(&pool, "10.0.0.0/8"),
+            vec!["10.20.80.0/20", "10.20.96.0/19", "10.20.128.0/17"]
         );
-        assert_eq!(pool.allocated_count(), pool.cidrs().count());
     }
 
     #[test]
-    fn allocate_success_v6() {
-        let mut pool = new_test_pool6();
-        let result = pool.allocate(4, None).unwrap();
+    fn entirely_free() {
+        let mut pool = new_test_pool();
+        pool.allocate(14, None).unwrap();
+        assert_eq!(free_within(&pool, "10.20.128.0/17"), vec!["10.20.128.0/17"]);
+        assert_eq!(free_within(&pool, "10.20.200.0/24"), vec!["10.20.200.0/24"]);
+    }
+
+    #[test]
+    fn outside_pool() {
+        let pool = new_test_pool();
+        assert_eq!(free_within(&pool, "10.21.0.0/24"), Vec::<String>::new());
+        assert_eq!(pool.iter_free_within(&TEST_CIDR6).count(), 0);
+    }
+}
+
+mod from_str {
+    use super::*;
+    use crate::errors::CreateError;
+
+    #[test]
+    fn valid() {
+        let pool: SubnetPool = "10.20.0.0/16".parse().unwrap();
+        assert_eq!(pool, new_test_pool());
         assert_eq!(
-            result,
-            IpCidr::V6(Ipv6Cidr::new(Ipv6Addr::new(1, 2, 3, 4, 10, 20, 0, 0), 124).unwrap())
+            SubnetPool::from_str("1:2:3:4:a:14::/112"),
+            Ok(new_test_pool6())
         );
-        assert_eq!(pool.allocated_count(), pool.cidrs().count());
     }
 
     #[test]
-    fn allocate_multi_sizes() {
-        let mut pool = new_test_pool();
-        let result1 = pool.allocate(4, None).unwrap();
+    fn invalid() {
         assert_eq!(
-            result1,
-            IpCidr::V4(Ipv4Cidr::new(Ipv4Addr::new(10, 20, 0, 0), 28).unwrap())
+            "invalid".parse::<SubnetPool>(),
+            Err(CreateError::InvalidCidr(
+                "couldn't parse address in network: invalid IP address syntax".to_string()
+            ))
         );
-        let result2 = pool.allocate(8, None).unwrap();
+        assert!(matches!(
+            "10.20.0.1/16".parse::<SubnetPool>(),
+            Err(CreateError::InvalidCidr(_))
+        ));
+    }
+}
+
+mod into_iter {
+    use super::*;
+
+    #[test]
+    fn for_loop() {
+        let mut pool = new_test_pool();
+        pool.allocate(4, Some("a-name")).unwrap();
+        pool.allocate(8, None).unwrap();
+        pool.allocate(4, Some("b-name")).unwrap();
+        let mut count = 0;
+        for record in &pool {
+            assert!(pool.contains(&record.cidr));
+            count += 1;
+        }
+        assert_eq!(count, 3);
         assert_eq!(
-            result2,
-            IpCidr::V4(Ipv4Cidr::new(Ipv4Addr::new(10, 20, 1, 0), 24).unwrap())
+            (&pool).into_iter().collect_vec(),
+            pool.records().collect_vec()
         );
-        assert_eq!(pool.allocated_count(), pool.cidrs().count());
+    }
+}
+
+mod on_change {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    fn record_events(pool: &mut SubnetPool) -> Arc<Mutex<Vec<ChangeEvent>>> {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&events);
+        pool.on_change(Box::new(move |event| {
+            recorded.lock().unwrap().push(event.clone())
+        }));
+        events
     }
 
     #[test]
-    fn max_subnet_allocation() {
+    fn allocate_and_free() {
         let mut pool = new_test_pool();
-        let result = pool.allocate(16, None).unwrap();
+        let events = record_events(&mut pool);
+        let cidr = pool.allocate_at_time(4, Some("a-name"), TEST_TIME).unwrap();
+        assert_eq!(pool.free(&cidr), Ok(()));
         assert_eq!(
-            result,
-            IpCidr::V4(Ipv4Cidr::new(Ipv4Addr::new(10, 20, 0, 0), 16).unwrap())
+            *events.lock().unwrap(),
+            vec![
+                ChangeEvent::Allocated(test_record(cidr, Some("a-name"))),
+                ChangeEvent::Freed(cidr),
+            ]
         );
     }
 
     #[test]
-    fn max_available_bits() {
+    fn claim() {
         let mut pool = new_test_pool();
-        assert_eq!(pool.max_available_bits(), 16);
-
-        // Use up all the bits!
-        let result = pool.allocate(16, None).unwrap();
-        assert_eq!(pool.max_available_bits(), 0);
-        pool.free(&result);
-        assert_eq!(pool.max_available_bits(), 16);
+        let events = record_events(&mut pool);
+        let cidr = IpCidr::from_str("10.20.1.0/24").unwrap();
+        pool.claim(&cidr, None).unwrap();
+        let events = events.lock().unwrap();
+        assert!(matches!(&events[..], [ChangeEvent::Allocated(record)] if record.cidr == cidr));
+    }
 
-        let net1 = pool.allocate(14, None).unwrap();
-        assert_eq!(pool.max_available_bits(), 15);
-        let net2 = pool.allocate(15, None).unwrap();
-        assert_eq!(pool.max_available_bits(), 14);
-        let net3 = pool.allocate(13, None).unwrap();
-        assert_eq!(pool.max_available_bits(), 13);
-        pool.free(&net1);
-        assert_eq!(pool.max_available_bits(), 14);
-        pool.free(&net3);
-        assert_eq!(pool.max_available_bits(), 15);
-        pool.free(&net2);
-        assert_eq!(pool.max_available_bits(), 16);
+    #[test]
+    fn rename() {
+        let mut pool = new_test_pool();
+        let cidr = pool.allocate(4, Some("a-name")).unwrap();
+        let events = record_events(&mut pool);
+        pool.rename(&cidr, Some("b-name")).unwrap();
+        pool.rename(&cidr, Some("b-name")).unwrap();
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![ChangeEvent::Renamed {
+                cidr,
+                old: Some("a-name".to_string()),
+                new: Some("b-name".to_string()),
+            }]
+        );
     }
-}
 
-mod free {
-    use super::*;
+    #[test]
+    fn swap_names() {
+        let mut pool = new_test_pool();
+        let cidr_a = pool.allocate(4, Some("a-name")).unwrap();
+        let cidr_b = pool.allocate(4, None).unwrap();
+        let events = record_events(&mut pool);
+        pool.swap_names(&cidr_a, &cidr_b).unwrap();
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                ChangeEvent::Renamed {
+                    cidr: cidr_a,
+                    old: Some("a-name".to_string()),
+                    new: None,
+                },
+                ChangeEvent::Renamed {
+                    cidr: cidr_b,
+                    old: None,
+                    new: Some("a-name".to_string()),
+                },
+            ]
+        );
+    }
 
     #[test]
-    fn out_of_range() {
+    fn failure() {
         let mut pool = new_test_pool();
-        assert!(!pool.free(&IpCidr::from_str("20.20.0.0/16").unwrap()));
-        assert_eq!(pool.allocated_count(), pool.cidrs().count());
+        pool.allocate(4, Some("a-name")).unwrap();
+        let cidr = pool.allocate(4, None).unwrap();
+        let events = record_events(&mut pool);
+        assert!(pool.allocate(17, None).is_err());
+        assert_eq!(
+            pool.free(&IpCidr::from_str("10.20.1.0/24").unwrap()),
+            Err(AllocateError::NoSuchObject)
+        );
+        assert!(pool.rename(&cidr, Some("a-name")).is_err());
+        assert!(events.lock().unwrap().is_empty());
     }
 
     #[test]
-    fn free() {
-        let index_list = [0, 1, 2, 3];
-        for indices in index_list.iter().permutations(index_list.len()) {
-            let mut pool = new_test_pool();
-            let mut cidrs = vec![];
-            for _ in 0..index_list.len() {
-                cidrs.push(pool.allocate(14, None).unwrap());
-            }
-            fn free_test(pool: &mut SubnetPool, cidr: &IpCidr) {
-                assert!(pool.free(cidr));
-                assert!(!pool.free(cidr));
-                pool.claim(cidr, None).unwrap();
-            }
-            for index in indices {
-                let cidr = cidrs[*index];
-                free_test(&mut pool, &cidr);
-            }
-            assert_eq!(pool.allocated_count(), pool.cidrs().count());
+    fn split() {
+        let mut pool = new_test_pool();
+        let cidr = pool.allocate(2, None).unwrap();
+        let events = record_events(&mut pool);
+        let children = pool.split(&cidr, 1).unwrap();
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0], ChangeEvent::Freed(cidr));
+        for (event, child) in events[1..].iter().zip(&children) {
+            assert!(matches!(event, ChangeEvent::Allocated(record) if record.cidr == *child));
         }
     }
-}
-
-mod claim {
-    use super::*;
-    use crate::errors::AllocateError;
 
     #[test]
-    fn out_of_range() {
+    fn several_callbacks() {
         let mut pool = new_test_pool();
-        let cidr = IpCidr::V4(Ipv4Cidr::new(Ipv4Addr::new(10, 21, 0, 0), 28).unwrap());
-        let result = pool.claim(&cidr, None);
-        assert_eq!(result, Err(AllocateError::NoSpaceAvailable));
-        assert_eq!(pool.allocated_count(), pool.cidrs().count());
+        let first = record_events(&mut pool);
+        let second = record_events(&mut pool);
+        pool.allocate(4, None).unwrap();
+        assert_eq!(first.lock().unwrap().len(), 1);
+        assert_eq!(second.lock().unwrap().len(), 1);
     }
 
     #[test]
-    fn already_claimed() {
+    fn clone_drops_callbacks() {
         let mut pool = new_test_pool();
-        let cidr = IpCidr::V4(Ipv4Cidr::new(Ipv4Addr::new(10, 20, 0, 0), 28).unwrap());
-        pool.claim(&cidr, None).unwrap();
-        let result = pool.claim(&cidr, None);
-        assert_eq!(result, Err(AllocateError::NoSpaceAvailable));
-        assert_eq!(pool.allocated_count(), pool.cidrs().count());
+        let events = record_events(&mut pool);
+        let mut clone = pool.clone();
+        assert_eq!(clone, pool);
+        clone.allocate(4, None).unwrap();
+        assert!(events.lock().unwrap().is_empty());
     }
+}
 
-    #[test]
-    fn already_allocated() {
+mod snapshot {
+    use super::*;
+
+    fn new_snapshot_test_pool() -> SubnetPool {
         let mut pool = new_test_pool();
-        let cidr = IpCidr::V4(Ipv4Cidr::new(Ipv4Addr::new(10, 20, 0, 0), 28).unwrap());
-        pool.allocate(16, None).unwrap();
-        let result = pool.claim(&cidr, None);
-        assert_eq!(result, Err(AllocateError::NoSpaceAvailable));
-        assert_eq!(pool.allocated_count(), pool.cidrs().count());
+        pool.allocate_at_time(4, Some("a-name"), TEST_TIME).unwrap();
+        pool.allocate_at_time(8, None, TEST_TIME).unwrap();
+        pool.set_metadata("owner", "alice");
+        pool
     }
 
     #[test]
-    fn already_named() {
-        let mut pool = new_test_pool();
-        let cidr = IpCidr::V4(Ipv4Cidr::new(Ipv4Addr::new(10, 20, 0, 16), 28).unwrap());
-        pool.allocate(4, Some("a-name")).unwrap();
-        let result = pool.claim(&cidr, Some("a-name"));
-        assert_eq!(result, Err(AllocateError::DuplicateName));
-        assert_eq!(pool.allocated_count(), pool.cidrs().count());
+    fn restore() {
+        let mut pool = new_snapshot_test_pool();
+        let snapshot = pool.snapshot();
+
+        pool.allocate(6, Some("b-name")).unwrap();
+        pool.claim(&IpCidr::from_str("10.20.128.0/17").unwrap(), None)
+            .unwrap();
+        pool.rename(&IpCidr::from_str("10.20.0.0/28").unwrap(), Some("c-name"))
+            .unwrap();
+        pool.free(&IpCidr::from_str("10.20.1.0/24").unwrap())
+            .unwrap();
+        pool.set_metadata("owner", "bob");
+        pool.set_description(Some("Lab networks"));
+
+        pool.restore(snapshot);
+        assert_eq!(pool, new_snapshot_test_pool());
+        assert_eq!(pool.metadata()["owner"], "alice");
+        assert_eq!(pool.description(), None);
+        assert_eq!(pool.validate(), Ok(()));
     }
 
     #[test]
-    fn unnamed() {
+    fn restore_repeatedly() {
+        let mut pool = new_snapshot_test_pool();
+        let snapshot = pool.snapshot();
+        for _ in 0..2 {
+            pool.allocate(4, Some("b-name")).unwrap();
+            pool.restore(snapshot.clone());
+            assert_eq!(pool.find_by_name("b-name"), None);
+            assert_eq!(pool.allocated_count(), 2);
+        }
+    }
+}
+
+mod pool_metadata {
+    use super::*;
+
+    #[test]
+    fn set_metadata() {
         let mut pool = new_test_pool();
-        let cidr = IpCidr::V4(Ipv4Cidr::new(Ipv4Addr::new(10, 20, 0, 0), 28).unwrap());
-        let result = pool.claim(&cidr, None);
-        assert_eq!(result, Ok(()));
-        assert_eq!(pool.allocated_count(), pool.cidrs().count());
+        assert!(pool.metadata().is_empty());
+        assert_eq!(pool.set_metadata("owner", "alice"), None);
+        assert_eq!(pool.set_metadata("owner", "bob"), Some("alice".to_string()));
+        pool.set_metadata("region", "eu-west");
+        assert_eq!(
+            pool.metadata().iter().collect_vec(),
+            vec![
+                (&"owner".to_string(), &"bob".to_string()),
+                (&"region".to_string(), &"eu-west".to_string()),
+            ]
+        );
     }
 
     #[test]
-    fn named() {
+    fn set_description() {
         let mut pool = new_test_pool();
-        let cidr = IpCidr::V4(Ipv4Cidr::new(Ipv4Addr::new(10, 20, 0, 0), 28).unwrap());
-        let result = pool.claim(&cidr, Some("a-name"));
-        assert_eq!(result, Ok(()));
-        let looked_up = pool.find_by_name("a-name").unwrap();
-        assert_eq!(looked_up, cidr);
-        assert_eq!(pool.allocated_count(), pool.cidrs().count());
+        assert_eq!(pool.description(), None);
+        pool.set_description(Some("Lab networks"));
+        assert_eq!(pool.description(), Some("Lab networks"));
+        pool.set_description(None);
+        assert_eq!(pool.description(), None);
     }
 }
 
-mod rename {
+mod overlapping {
     use super::*;
-    use crate::errors::RenameError;
 
     #[test]
-    fn not_found() {
+    fn overlaps() {
         let mut pool = new_test_pool();
-        let cidr = IpCidr::V4(Ipv4Cidr::new(Ipv4Addr::new(10, 21, 0, 0), 28).unwrap());
-        let result = pool.rename(&cidr, Some("a-name"));
-        assert_eq!(result, Err(RenameError::NoSuchObject));
-        assert_eq!(pool.find_by_name("a-name"), None);
+        pool.allocate(4, Some("a-name")).unwrap();
+        pool.allocate(8, Some("b-name")).unwrap();
+        let overlapping = |cidr: &str| {
+            pool.overlapping(&IpCidr::from_str(cidr).unwrap())
+                .map(|record| record.cidr.to_string())
+        };
+        assert_eq!(
+            overlapping("10.20.0.0/24"),
+            Some("10.20.0.0/28".to_string())
+        );
+        assert_eq!(
+            overlapping("10.20.1.0/25"),
+            Some("10.20.1.0/24".to_string())
+        );
+        assert_eq!(overlapping("10.0.0.0/8"), Some("10.20.0.0/28".to_string()));
+        assert_eq!(overlapping("10.20.0.16/28"), None);
+        assert_eq!(overlapping("10.20.128.0/17"), None);
+        assert_eq!(overlapping("10.21.0.0/24"), None);
     }
+}
+
+mod next_free {
+    use super::*;
+
+    fn next_free(pool: &SubnetPool, cidr: &str) -> Option<String> {
+        pool.next_free(&IpCidr::from_str(cidr).unwrap())
+            .map(|cidr| cidr.to_string())
+    }
+
+    #[test]
+    fn free() {
+        let pool = new_test_pool();
+        assert_eq!(
+            next_free(&pool, "10.20.5.0/24"),
+            Some("10.20.5.0/24".to_string())
+        );
+    }
+
+    #[test]
+    fn following() {
+        let mut pool = new_test_pool();
+        pool.claim(&IpCidr::from_str("10.20.0.0/25").unwrap(), None)
+            .unwrap();
+        pool.claim(&IpCidr::from_str("10.20.2.0/24").unwrap(), None)
+            .unwrap();
+        assert_eq!(
+            next_free(&pool, "10.20.0.0/24"),
+            Some("10.20.1.0/24".to_string())
+        );
+        assert_eq!(
+            next_free(&pool, "10.20.2.0/24"),
+            Some("10.20.3.0/24".to_string())
+        );
+    }
+
+    #[test]
+    fn wraps_around() {
+        let mut pool = new_test_pool();
+        pool.claim(&IpCidr::from_str("10.20.128.0/17").unwrap(), None)
+            .unwrap();
+        assert_eq!(
+            next_free(&pool, "10.20.200.0/24"),
+            Some("10.20.0.0/24".to_string())
+        );
+    }
+
+    #[test]
+    fn no_space() {
+        let mut pool = new_test_pool();
+        pool.allocate(15, None).unwrap();
+        pool.allocate(14, None).unwrap();
+        assert_eq!(next_free(&pool, "10.20.0.0/17"), None);
+        assert_eq!(pool.next_free(&TEST_CIDR6), None);
+    }
+}
+
+mod prefixes_by_size {
+    use super::*;
+
+    #[test]
+    fn empty_pool() {
+        assert!(new_test_pool().prefixes_by_size().is_empty());
+    }
+
+    #[test]
+    fn mixed_sizes() {
+        let mut pool = new_test_pool();
+        pool.allocate(4, None).unwrap();
+        pool.allocate(8, Some("a-name")).unwrap();
+        pool.allocate(4, None).unwrap();
+        pool.allocate(8, None).unwrap();
+        pool.allocate(4, None).unwrap();
+        pool.allocate(12, None).unwrap();
+        assert_eq!(
+            pool.prefixes_by_size().into_iter().collect_vec(),
+            vec![(4, 3), (8, 2), (12, 1)]
+        );
+    }
+}
+
+mod find_gap {
+    use super::*;
+
+    #[test]
+    fn exact_fit() {
+        let mut pool = new_test_pool();
+        pool.allocate(8, None).unwrap();
+        pool.allocate(4, None).unwrap();
+        assert_eq!(
+            pool.find_gap(7),
+            Some((IpCidr::from_str("10.20.1.128/25").unwrap(), 7))
+        );
+    }
+
+    #[test]
+    fn much_larger() {
+        let mut pool = new_test_pool();
+        pool.allocate(4, None).unwrap();
+        assert_eq!(
+            pool.find_gap(4),
+            Some((IpCidr::from_str("10.20.0.16/28").unwrap(), 4))
+        );
+        assert_eq!(
+            pool.find_gap(12),
+            Some((IpCidr::from_str("10.20.16.0/20").unwrap(), 12))
+        );
+        pool.allocate(14, None).unwrap();
+        assert_eq!(
+            pool.find_gap(14),
+            Some((IpCidr::from_str("10.20.128.0/17").unwrap(), 15))
+        );
+    }
+
+    #[test]
+    fn nothing_fits() {
+        let mut pool = new_test_pool();
+        pool.allocate(15, None).unwrap();
+        assert_eq!(pool.find_gap(16), None);
+        pool.allocate(15, None).unwrap();
+        assert_eq!(pool.find_gap(0), None);
+    }
+}
+
+mod capacity {
+    use super::*;
+
+    #[test]
+    fn ipv4() {
+        let mut pool = new_test_pool();
+        assert_eq!(pool.capacity(), 65536);
+        pool.allocate(8, None).unwrap();
+        assert_eq!(pool.capacity(), 65536);
+    }
+
+    #[test]
+    fn ipv6() {
+        let pool = new_test_pool6();
+        assert_eq!(pool.capacity(), 65536);
+    }
+
+    #[test]
+    fn saturates() {
+        let pool = SubnetPool::new(IpCidr::from_str("::/0").unwrap());
+        assert_eq!(pool.capacity(), u128::MAX);
+    }
+}
+
+mod summary {
+    use super::*;
+    use serde_test::{assert_ser_tokens, Token};
+
+    fn new_summary_pool() -> SubnetPool {
+        let mut pool = new_test_pool();
+        pool.allocate(14, Some("a-name")).unwrap();
+        pool.allocate(12, None).unwrap();
+        pool
+    }
+
+    #[test]
+    fn empty_pool() {
+        let pool = new_test_pool();
+        assert_eq!(
+            pool.summary(),
+            PoolSummary {
+                cidr: TEST_CIDR4,
+                allocated_count: 0,
+                named_count: 0,
+                utilization: 0.0,
+                max_available_bits: 16,
+                free_block_count: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn allocated() {
+        let pool = new_summary_pool();
+        assert_eq!(
+            pool.summary(),
+            PoolSummary {
+                cidr: TEST_CIDR4,
+                allocated_count: 2,
+                named_count: 1,
+                utilization: 0.3125,
+                max_available_bits: 15,
+                free_block_count: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn serialize() {
+        assert_ser_tokens(
+            &new_summary_pool().summary(),
+            &[
+                Token::Struct {
+                    name: "PoolSummary",
+                    len: 6,
+                },
+                Token::Str("cidr"),
+                Token::Str("10.20.0.0/16"),
+                Token::Str("allocated_count"),
+                Token::U64(2),
+                Token::Str("named_count"),
+                Token::U64(1),
+                Token::Str("utilization"),
+                Token::F64(0.3125),
+                Token::Str("max_available_bits"),
+                Token::U8(15),
+                Token::Str("free_block_count"),
+                Token::U64(3),
+                Token::StructEnd,
+            ],
+        );
+    }
+}
+
+mod free_addresses {
+    use super::*;
+
+    fn assert_adds_up(pool: &SubnetPool) {
+        assert_eq!(
+            pool.free_addresses() + pool.allocated_addresses(),
+            pool.capacity()
+        );
+    }
+
+    #[test]
+    fn empty_pool() {
+        let pool = new_test_pool();
+        assert_eq!(pool.free_addresses(), 65536);
+        assert_eq!(pool.allocated_addresses(), 0);
+        assert_adds_up(&pool);
+    }
+
+    #[test]
+    fn allocated() {
+        let mut pool = new_test_pool();
+        pool.allocate(8, None).unwrap();
+        pool.allocate(4, None).unwrap();
+        pool.allocate(0, None).unwrap();
+        assert_eq!(pool.allocated_addresses(), 256 + 16 + 1);
+        assert_eq!(pool.free_addresses(), 65536 - 273);
+        assert_adds_up(&pool);
+    }
+
+    #[test]
+    fn freed() {
+        let mut pool = new_test_pool6();
+        let cidr = pool.allocate(8, None).unwrap();
+        pool.allocate(12, None).unwrap();
+        pool.free(&cidr).unwrap();
+        assert_eq!(pool.allocated_addresses(), 4096);
+        assert_adds_up(&pool);
+    }
+
+    #[test]
+    fn full() {
+        let mut pool = SubnetPool::new(IpCidr::from_str("10.20.0.0/30").unwrap());
+        pool.allocate(1, None).unwrap();
+        pool.allocate(1, None).unwrap();
+        assert_eq!(pool.free_addresses(), 0);
+        assert_adds_up(&pool);
+    }
+
+    #[test]
+    fn single_address() {
+        let pool = SubnetPool::new(IpCidr::from_str("10.20.0.1/32").unwrap());
+        assert_eq!(pool.free_addresses(), 1);
+        assert_adds_up(&pool);
+    }
+}
+
+mod capacity_for {
+    use super::*;
+
+    #[test]
+    fn empty_pool() {
+        let pool = new_test_pool();
+        assert_eq!(pool.capacity_for(16), 1);
+        assert_eq!(pool.capacity_for(8), 256);
+        assert_eq!(pool.capacity_for(17), 0);
+    }
+
+    #[test]
+    fn partially_allocated() {
+        let mut pool = new_test_pool();
+        pool.allocate(4, None).unwrap();
+        assert_eq!(pool.capacity_for(16), 0);
+        assert_eq!(pool.capacity_for(15), 1);
+        assert_eq!(pool.capacity_for(4), 4095);
+    }
+
+    #[test]
+    fn saturates() {
+        let pool = SubnetPool::new(IpCidr::from_str("::/0").unwrap());
+        assert_eq!(pool.capacity_for(0), u128::MAX);
+        assert_eq!(pool.capacity_for(1), 1 << 127);
+    }
+}
+
+mod empty_name {
+    use super::*;
+
+    fn assert_unnamed(pool: &SubnetPool, cidr: &IpCidr) {
+        assert_eq!(pool.named_count(), 0);
+        assert_eq!(pool.find_by_name(""), None);
+        assert_eq!(pool.records_within(cidr).next().unwrap().name, None);
+    }
+
+    #[test]
+    fn allocate() {
+        let mut pool = new_test_pool();
+        let cidr = pool.allocate(4, Some("")).unwrap();
+        assert_unnamed(&pool, &cidr);
+    }
+
+    #[test]
+    fn allocate_many() {
+        let mut pool = new_test_pool();
+        let cidrs = pool.allocate_many(4, &[Some(""), Some("")]).unwrap();
+        assert_eq!(cidrs.len(), 2);
+        assert_unnamed(&pool, &cidrs[0]);
+    }
+
+    #[test]
+    fn claim() {
+        let mut pool = new_test_pool();
+        let cidr = IpCidr::from_str("10.20.1.0/24").unwrap();
+        pool.claim(&cidr, Some("")).unwrap();
+        assert_unnamed(&pool, &cidr);
+    }
+
+    #[test]
+    fn rename() {
+        let mut pool = new_test_pool();
+        let cidr = pool.allocate(4, Some("a-name")).unwrap();
+        pool.rename(&cidr, Some("")).unwrap();
+        assert_unnamed(&pool, &cidr);
+        assert_eq!(pool.find_by_name("a-name"), None);
+    }
+
+    #[test]
+    fn deserialize() {
+        let cidr = IpCidr::from_str("10.20.1.0/24").unwrap();
+        let mut expected = new_test_pool();
+        expected.claim_record(&CidrRecord::new(cidr, None)).unwrap();
+        serde_test::assert_de_tokens(
+            &expected,
+            &[
+                serde_test::Token::Struct {
+                    name: "SubnetPool",
+                    len: 2,
+                },
+                serde_test::Token::Str("cidr"),
+                serde_test::Token::Str("10.20.0.0/16"),
+                serde_test::Token::Str("subnets"),
+                serde_test::Token::Seq { len: Some(1) },
+                serde_test::Token::Struct {
+                    name: "CidrRecord",
+                    len: 2,
+                },
+                serde_test::Token::Str("cidr"),
+                serde_test::Token::Str("10.20.1.0/24"),
+                serde_test::Token::Str("name"),
+                serde_test::Token::Str(""),
+                serde_test::Token::StructEnd,
+                serde_test::Token::SeqEnd,
+                serde_test::Token::StructEnd,
+            ],
+        );
+    }
+}
+
+mod allocate {
+    use super::*;
+
+    #[test]
+    fn sets_created_at() {
+        let mut pool = new_test_pool();
+        let cidr = pool.allocate(4, Some("a-name")).unwrap();
+        let record = pool.records().next().unwrap();
+        assert_eq!(record.cidr, cidr);
+        let created_at = record.created_at.as_deref().unwrap();
+        assert!(chrono::DateTime::parse_from_rfc3339(created_at).is_ok());
+    }
+
+    #[test]
+    fn allocate_at_time() {
+        let mut pool = new_test_pool();
+        let cidr = pool.allocate_at_time(4, None, TEST_TIME).unwrap();
+        assert_eq!(pool.records().next(), Some(&test_record(cidr, None)));
+    }
+
+    #[test]
+    fn zero_bits_ipv4() {
+        let mut pool = new_test_pool();
+        let first = pool.allocate(0, Some("a-name")).unwrap();
+        let second = pool.allocate(0, None).unwrap();
+        assert_eq!(first, IpCidr::from_str("10.20.0.0/32").unwrap());
+        assert_eq!(second, IpCidr::from_str("10.20.0.1/32").unwrap());
+        assert_eq!(pool.find_by_name("a-name"), Some(first));
+        assert_eq!(pool.max_available_bits(), 15);
+        assert_eq!(pool.validate(), Ok(()));
+
+        assert_eq!(pool.free(&first), Ok(()));
+        assert_eq!(pool.free(&second), Ok(()));
+        assert_eq!(pool, new_test_pool());
+    }
+
+    #[test]
+    fn zero_bits_ipv6() {
+        let mut pool = new_test_pool6();
+        let cidr = pool.allocate(0, Some("a-name")).unwrap();
+        assert_eq!(cidr, IpCidr::from_str("1:2:3:4:a:14::/128").unwrap());
+        assert_eq!(pool.allocated_count(), 1);
+        assert_eq!(pool.free_by_name("a-name"), Ok(()));
+        assert_eq!(pool, new_test_pool6());
+    }
+
+    #[test]
+    fn zero_bits_single_address_pool() {
+        let root = IpCidr::from_str("10.20.0.1/32").unwrap();
+        let mut pool = SubnetPool::new(root);
+        assert_eq!(pool.allocate(0, None), Ok(root));
+        assert_eq!(pool.allocate(0, None), Err(AllocateError::NoSpaceAvailable));
+        assert_eq!(pool.free(&root), Ok(()));
+        assert_eq!(pool.allocate(0, None), Ok(root));
+    }
+
+    #[test]
+    fn too_many_bits() {
+        let mut pool = new_test_pool();
+        let result = pool.allocate(17, None);
+        assert_eq!(result.err(), Some(AllocateError::NoSpaceAvailable));
+        assert_eq!(pool.allocated_count(), pool.cidrs().count());
+    }
+
+    #[test]
+    fn no_pool_available() {
+        let mut pool = new_test_pool();
+        pool.allocate(16, None).unwrap();
+        let result = pool.allocate(16, None);
+        assert_eq!(result.err(), Some(AllocateError::NoSpaceAvailable));
+        assert_eq!(pool.allocated_count(), pool.cidrs().count());
+    }
+
+    #[test]
+    fn allocate_name_already_exists() {
+        let mut pool = new_test_pool();
+        pool.allocate(4, Some("a-name")).unwrap();
+        let result = pool.allocate(4, Some("a-name"));
+        assert_eq!(result.err(), Some(AllocateError::DuplicateName));
+        assert_eq!(pool.allocated_count(), pool.cidrs().count());
+    }
+
+    #[test]
+    fn duplicate_name_leaves_pool_unchanged() {
+        let mut pool = new_test_pool();
+        pool.allocate(4, Some("web")).unwrap();
+        assert_eq!(
+            pool.allocate(4, Some("web")),
+            Err(AllocateError::DuplicateName)
+        );
+        assert_eq!(pool.allocated_count(), 1);
+        assert_eq!(pool.validate(), Ok(()));
+    }
+
+    #[test]
+    fn name_is_not_cidr_record() {
+        let mut pool = new_test_pool();
+        pool.allocate(4, Some("10.20.0.16/28")).unwrap();
+        let result = pool.allocate(4, None);
+        assert_eq!(result.err(), None);
+        assert_eq!(pool.allocated_count(), pool.cidrs().count());
+    }
+
+    #[test]
+    fn allocate_named() {
+        let mut pool = new_test_pool();
+        let result = pool.allocate(4, Some("a-name")).unwrap();
+        let looked_up = pool.find_by_name("a-name").unwrap();
+        assert_eq!(looked_up, result);
+        assert_eq!(pool.allocated_count(), pool.cidrs().count());
+    }
+
+    #[test]
+    fn allocate_success_v4() {
+        let mut pool = new_test_pool();
+        let result = pool.allocate(4, None).unwrap();
+        assert_eq!(
+            result,
+            IpCidr::V4(Ipv4Cidr::new(Ipv4Addr::new(10, 20, 0, 0), 28).unwrap())
+        );
+        assert_eq!(pool.allocated_count(), pool.cidrs().count());
+    }
+
+    #[test]
+    fn allocate_success_v6() {
+        let mut pool = new_test_pool6();
+        let result = pool.allocate(4, None).unwrap();
+        assert_eq!(
+            result,
+            IpCidr::V6(Ipv6Cidr::new(Ipv6Addr::new(1, 2, 3, 4, 10, 20, 0, 0), 124).unwrap())
+        );
+        assert_eq!(pool.allocated_count(), pool.cidrs().count());
+    }
+
+    #[test]
+    fn allocate_multi_sizes() {
+        let mut pool = new_test_pool();
+        let result1 = pool.allocate(4, None).unwrap();
+        assert_eq!(
+            result1,
+            IpCidr::V4(Ipv4Cidr::new(Ipv4Addr::new(10, 20, 0, 0), 28).unwrap())
+        );
+        let result2 = pool.allocate(8, None).unwrap();
+        assert_eq!(
+            result2,
+            IpCidr::V4(Ipv4Cidr::new(Ipv4Addr::new(10, 20, 1, 0), 24).unwrap())
+        );
+        assert_eq!(pool.allocated_count(), pool.cidrs().count());
+    }
+
+    #[test]
+    fn max_subnet_allocation() {
+        let mut pool = new_test_pool();
+        let result = pool.allocate(16, None).unwrap();
+        assert_eq!(
+            result,
+            IpCidr::V4(Ipv4Cidr::new(Ipv4Addr::new(10, 20, 0, 0), 16).unwrap())
+        );
+    }
+
+    #[test]
+    fn max_available_bits() {
+        let mut pool = new_test_pool();
+        assert_eq!(pool.max_available_bits(), 16);
+
+        // Use up all the bits!
+        let result = pool.allocate(16, None).unwrap();
+        assert_eq!(pool.max_available_bits(), 0);
+        pool.free(&result).unwrap();
+        assert_eq!(pool.max_available_bits(), 16);
+
+        let net1 = pool.allocate(14, None).unwrap();
+        assert_eq!(pool.max_available_bits(), 15);
+        let net2 = pool.allocate(15, None).unwrap();
+        assert_eq!(pool.max_available_bits(), 14);
+        let net3 = pool.allocate(13, None).unwrap();
+        assert_eq!(pool.max_available_bits(), 13);
+        pool.free(&net1).unwrap();
+        assert_eq!(pool.max_available_bits(), 14);
+        pool.free(&net3).unwrap();
+        assert_eq!(pool.max_available_bits(), 15);
+        pool.free(&net2).unwrap();
+        assert_eq!(pool.max_available_bits(), 16);
+    }
+}
+
+mod allocate_record {
+    use super::*;
+
+    #[test]
+    fn success() {
+        let mut pool = new_test_pool();
+        let record = pool.allocate_record(4, Some("a-name")).unwrap();
+        assert_eq!(record.name.as_deref(), Some("a-name"));
+        assert_eq!(record.cidr, IpCidr::from_str("10.20.0.0/28").unwrap());
+        assert!(record.created_at.is_some());
+        let cidr = record.cidr;
+        assert_eq!(pool.find_by_name("a-name"), Some(cidr));
+    }
+
+    #[test]
+    fn no_space_available() {
+        let mut pool = new_test_pool();
+        let result = pool.allocate_record(17, None);
+        assert_eq!(result, Err(AllocateError::NoSpaceAvailable));
+    }
+}
+
+mod allocate_many {
+    use super::*;
+
+    #[test]
+    fn success() {
+        let mut pool = new_test_pool();
+        let cidrs = pool
+            .allocate_many(8, &[Some("a-name"), None, Some("b-name")])
+            .unwrap();
+        assert_eq!(
+            cidrs.iter().map(|cidr| cidr.to_string()).collect_vec(),
+            vec!["10.20.0.0/24", "10.20.1.0/24", "10.20.2.0/24"]
+        );
+        assert_eq!(pool.find_by_name("a-name"), Some(cidrs[0]));
+        assert_eq!(pool.find_by_name("b-name"), Some(cidrs[2]));
+        assert_eq!(pool.allocated_count(), 3);
+    }
+
+    #[test]
+    fn no_space_rolls_back() {
+        let mut pool = new_test_pool();
+        pool.allocate(15, Some("existing")).unwrap();
+        let result = pool.allocate_many(14, &[Some("a-name"), Some("b-name"), Some("c-name")]);
+        assert_eq!(result, Err(AllocateError::NoSpaceAvailable));
+        assert_eq!(pool.allocated_count(), 1);
+        assert_eq!(pool.named_count(), 1);
+        assert_eq!(pool.find_by_name("a-name"), None);
+        assert_eq!(pool.max_available_bits(), 15);
+    }
+
+    #[test]
+    fn duplicate_name() {
+        let mut pool = new_test_pool();
+        pool.allocate(4, Some("existing")).unwrap();
+        assert_eq!(
+            pool.allocate_many(4, &[Some("a-name"), Some("existing")]),
+            Err(AllocateError::DuplicateName)
+        );
+        assert_eq!(
+            pool.allocate_many(4, &[Some("a-name"), Some("a-name")]),
+            Err(AllocateError::DuplicateName)
+        );
+        assert_eq!(pool.allocated_count(), 1);
+        assert_eq!(pool.named_count(), 1);
+    }
+}
+
+mod allocate_with {
+    use super::*;
+
+    #[test]
+    fn low_first() {
+        let mut pool = new_test_pool();
+        assert_eq!(
+            pool.allocate_with(4, None, AllocStrategy::LowFirst)
+                .unwrap(),
+            IpCidr::from_str("10.20.0.0/28").unwrap()
+        );
+        assert_eq!(AllocStrategy::default(), AllocStrategy::LowFirst);
+    }
+
+    #[test]
+    fn high_first() {
+        let mut pool = new_test_pool();
+        let cidrs = [
+            pool.allocate_with(4, Some("a-name"), AllocStrategy::HighFirst)
+                .unwrap(),
+            pool.allocate_with(8, None, AllocStrategy::HighFirst)
+                .unwrap(),
+            pool.allocate_with(4, None, AllocStrategy::HighFirst)
+                .unwrap(),
+            pool.allocate(4, None).unwrap(),
+        ];
+        assert_eq!(
+            cidrs.iter().map(|cidr| cidr.to_string()).collect_vec(),
+            vec![
+                "10.20.255.240/28",
+                "10.20.254.0/24",
+                "10.20.255.224/28",
+                "10.20.0.0/28"
+            ]
+        );
+        assert_eq!(pool.find_by_name("a-name"), Some(cidrs[0]));
+        assert_eq!(pool.validate(), Ok(()));
+    }
+
+    #[test]
+    fn high_first_ipv6() {
+        let mut pool = new_test_pool6();
+        assert_eq!(
+            pool.allocate_with(8, None, AllocStrategy::HighFirst)
+                .unwrap(),
+            IpCidr::from_str("1:2:3:4:a:14:0:ff00/120").unwrap()
+        );
+    }
+
+    #[test]
+    fn high_first_full() {
+        let mut pool = new_test_pool();
+        pool.allocate(15, None).unwrap();
+        pool.allocate_with(15, None, AllocStrategy::HighFirst)
+            .unwrap();
+        assert_eq!(
+            pool.allocate_with(0, None, AllocStrategy::HighFirst),
+            Err(AllocateError::NoSpaceAvailable)
+        );
+    }
+}
+
+mod allocate_spread {
+    use super::*;
+    use crate::errors::AllocateError;
+
+    #[test]
+    fn deterministic() {
+        let mut pool = new_test_pool();
+        pool.allocate(8, None).unwrap();
+        let mut other = new_test_pool();
+        other.allocate(8, None).unwrap();
+        for seed in 0..16 {
+            assert_eq!(
+                pool.allocate_spread(8, None, seed).unwrap(),
+                other.allocate_spread(8, None, seed).unwrap()
+            );
+        }
+        assert_eq!(pool.allocated_count(), 17);
+        assert_eq!(pool.validate(), Ok(()));
+    }
+
+    #[test]
+    fn spreads() {
+        let cidrs: HashSet<IpCidr> = (0..8)
+            .map(|seed| {
+                new_test_pool()
+                    .allocate_spread(8, Some("a-name"), seed)
+                    .unwrap()
+            })
+            .collect();
+        assert!(cidrs.len() > 1);
+        assert!(cidrs
+            .iter()
+            .all(|cidr| util::host_length(cidr) == 8 && util::cidr_contains(&TEST_CIDR4, cidr)));
+    }
+
+    #[test]
+    fn only_free_space() {
+        let mut pool = new_test_pool();
+        pool.allocate(15, None).unwrap();
+        let cidr = pool.allocate_spread(14, Some("a-name"), 7).unwrap();
+        assert!(util::cidr_contains(
+            &IpCidr::from_str("10.20.128.0/17").unwrap(),
+            &cidr
+        ));
+        assert_eq!(pool.find_by_name("a-name"), Some(cidr));
+        pool.allocate_spread(14, None, 7).unwrap();
+        assert_eq!(
+            pool.allocate_spread(14, None, 7),
+            Err(AllocateError::NoSpaceAvailable)
+        );
+    }
+
+    #[test]
+    fn too_large() {
+        let mut pool = new_test_pool();
+        assert_eq!(
+            pool.allocate_spread(17, None, 0),
+            Err(AllocateError::NoSpaceAvailable)
+        );
+    }
+
+    #[test]
+    fn duplicate_name() {
+        let mut pool = new_test_pool();
+        pool.allocate(4, Some("a-name")).unwrap();
+        assert_eq!(
+            pool.allocate_spread(4, Some("a-name"), 0),
+            Err(AllocateError::DuplicateName)
+        );
+        assert_eq!(pool.allocated_count(), 1);
+    }
+}
+
+mod allocate_within {
+    use super::*;
+    use crate::errors::AllocateError;
+
+    #[test]
+    fn empty_pool() {
+        let mut pool = new_test_pool();
+        let parent = IpCidr::from_str("10.20.128.0/17").unwrap();
+        let cidr = pool.allocate_within(8, Some("a-name"), &parent).unwrap();
+        assert_eq!(cidr, IpCidr::from_str("10.20.128.0/24").unwrap());
+        assert_eq!(pool.find_by_name("a-name"), Some(cidr));
+        let cidr = pool.allocate_within(8, None, &parent).unwrap();
+        assert_eq!(cidr, IpCidr::from_str("10.20.129.0/24").unwrap());
+        assert_eq!(pool.max_available_bits(), 15);
+        assert_eq!(pool.validate(), Ok(()));
+    }
+
+    #[test]
+    fn skips_allocations() {
+        let mut pool = new_test_pool();
+        pool.claim(&IpCidr::from_str("10.20.1.0/24").unwrap(), None)
+            .unwrap();
+        let parent = IpCidr::from_str("10.20.0.0/23").unwrap();
+        pool.allocate_within(8, None, &parent).unwrap();
+        let result = pool.allocate_within(8, None, &parent);
+        assert_eq!(result, Err(AllocateError::NoSpaceAvailable));
+        assert_eq!(pool.allocated_count(), 2);
+        assert_eq!(
+            pool.allocate_within(4, None, &TEST_CIDR4).unwrap(),
+            IpCidr::from_str("10.20.2.0/28").unwrap()
+        );
+    }
+
+    #[test]
+    fn parent_within_allocation() {
+        let mut pool = new_test_pool();
+        pool.allocate(8, None).unwrap();
+        let parent = IpCidr::from_str("10.20.0.0/25").unwrap();
+        let result = pool.allocate_within(4, None, &parent);
+        assert_eq!(result, Err(AllocateError::NoSpaceAvailable));
+    }
+
+    #[test]
+    fn too_large_for_parent() {
+        let mut pool = new_test_pool();
+        let parent = IpCidr::from_str("10.20.0.0/24").unwrap();
+        let result = pool.allocate_within(9, None, &parent);
+        assert_eq!(result, Err(AllocateError::NoSpaceAvailable));
+        assert_eq!(pool.max_available_bits(), 16);
+    }
+
+    #[test]
+    fn outside_pool() {
+        let mut pool = new_test_pool();
+        let parent = IpCidr::from_str("10.21.0.0/24").unwrap();
+        let result = pool.allocate_within(4, None, &parent);
+        assert_eq!(result, Err(AllocateError::NoSpaceAvailable));
+        let result = pool.allocate_within(4, None, &TEST_CIDR6);
+        assert_eq!(result, Err(AllocateError::FamilyMismatch));
+    }
+
+    #[test]
+    fn duplicate_name() {
+        let mut pool = new_test_pool();
+        pool.allocate(4, Some("a-name")).unwrap();
+        let result = pool.allocate_within(4, Some("a-name"), &TEST_CIDR4);
+        assert_eq!(result, Err(AllocateError::DuplicateName));
+        assert_eq!(pool.allocated_count(), 1);
+    }
+}
+
+mod allocate_prefix {
+    use super::*;
+    use crate::errors::AllocateError;
+
+    #[test]
+    fn valid() {
+        let mut pool = new_test_pool();
+        let cidr = pool.allocate_prefix(24, Some("a-name")).unwrap();
+        assert_eq!(cidr, IpCidr::from_str("10.20.0.0/24").unwrap());
+        assert_eq!(pool.find_by_name("a-name"), Some(cidr));
+        let cidr = pool.allocate_prefix(32, None).unwrap();
+        assert_eq!(cidr, IpCidr::from_str("10.20.1.0/32").unwrap());
+
+        let mut pool = new_test_pool6();
+        let cidr = pool.allocate_prefix(120, None).unwrap();
+        assert_eq!(cidr, IpCidr::from_str("1:2:3:4:a:14::/120").unwrap());
+        let cidr = pool.allocate_prefix(112, None);
+        assert_eq!(cidr, Err(AllocateError::NoSpaceAvailable));
+    }
+
+    #[test]
+    fn too_short() {
+        let mut pool = new_test_pool();
+        assert_eq!(
+            pool.allocate_prefix(15, None),
+            Err(AllocateError::PrefixTooShort)
+        );
+        let mut pool = new_test_pool6();
+        assert_eq!(
+            pool.allocate_prefix(64, None),
+            Err(AllocateError::PrefixTooShort)
+        );
+        assert_eq!(pool.allocated_count(), 0);
+    }
+
+    #[test]
+    fn too_long() {
+        let mut pool = new_test_pool();
+        assert_eq!(
+            pool.allocate_prefix(33, None),
+            Err(AllocateError::PrefixTooLong)
+        );
+        let mut pool = new_test_pool6();
+        assert_eq!(
+            pool.allocate_prefix(129, None),
+            Err(AllocateError::PrefixTooLong)
+        );
+        assert_eq!(pool.allocated_count(), 0);
+    }
+}
+
+mod allocate_unique_name {
+    use super::*;
+    use crate::errors::AllocateError;
+
+    #[test]
+    fn unused_name() {
+        let mut pool = new_test_pool();
+        let (cidr, name) = pool.allocate_unique_name(4, "web").unwrap();
+        assert_eq!(name, "web");
+        assert_eq!(pool.find_by_name("web"), Some(cidr));
+    }
+
+    #[test]
+    fn appends_suffix() {
+        let mut pool = new_test_pool();
+        pool.allocate(4, Some("web")).unwrap();
+        pool.allocate(4, Some("web-1")).unwrap();
+        let (cidr, name) = pool.allocate_unique_name(4, "web").unwrap();
+        assert_eq!(name, "web-2");
+        assert_eq!(pool.find_by_name("web-2"), Some(cidr));
+        assert_eq!(pool.allocated_count(), 3);
+    }
+
+    #[test]
+    fn no_space() {
+        let mut pool = new_test_pool();
+        pool.allocate(16, Some("web")).unwrap();
+        let result = pool.allocate_unique_name(4, "web");
+        assert_eq!(result, Err(AllocateError::NoSpaceAvailable));
+        assert_eq!(pool.find_by_name("web-1"), None);
+    }
+}
+
+mod allocate_largest_available {
+    use super::*;
+
+    #[test]
+    fn empty_pool() {
+        let mut pool = new_test_pool();
+        let cidr = pool.allocate_largest_available(Some("a-name")).unwrap();
+        assert_eq!(cidr, TEST_CIDR4);
+        assert_eq!(pool.find_by_name("a-name"), Some(TEST_CIDR4));
+    }
+
+    #[test]
+    fn partial_pool() {
+        let mut pool = new_test_pool();
+        pool.allocate(14, None).unwrap();
+        pool.allocate(15, None).unwrap();
+        let cidr = pool.allocate_largest_available(None).unwrap();
+        assert_eq!(cidr, IpCidr::from_str("10.20.64.0/18").unwrap());
+        assert_eq!(pool.max_available_bits(), 0);
+    }
+
+    #[test]
+    fn full_pool() {
+        let mut pool = new_test_pool();
+        pool.allocate(16, None).unwrap();
+        let result = pool.allocate_largest_available(None);
+        assert_eq!(result, Err(AllocateError::NoSpaceAvailable));
+        assert_eq!(pool.allocated_count(), 1);
+    }
+}
+
+mod assign_names_from_template {
+    use super::*;
+    use crate::errors::FormatError;
+
+    #[test]
+    fn names_in_order() {
+        let mut pool = new_test_pool();
+        pool.allocate(4, None).unwrap();
+        pool.allocate(4, Some("a-name")).unwrap();
+        pool.allocate(8, None).unwrap();
+        pool.allocate(4, None).unwrap();
+        assert_eq!(pool.assign_names_from_template("subnet-{index}"), Ok(3));
+        assert_eq!(
+            pool.records()
+                .map(|record| (record.cidr.to_string(), record.name.clone().unwrap()))
+                .collect_vec(),
+            vec![
+                ("10.20.0.0/28".to_string(), "subnet-0".to_string()),
+                ("10.20.0.16/28".to_string(), "a-name".to_string()),
+                ("10.20.0.32/28".to_string(), "subnet-1".to_string()),
+                ("10.20.1.0/24".to_string(), "subnet-2".to_string()),
+            ]
+        );
+        assert_eq!(pool.named_count(), 4);
+        assert_eq!(pool.validate(), Ok(()));
+    }
+
+    #[test]
+    fn record_fields() {
+        let mut pool = new_test_pool();
+        pool.allocate(8, None).unwrap();
+        assert_eq!(pool.assign_names_from_template("net-{first}"), Ok(1));
+        assert!(pool.find_by_name("net-10.20.0.0").is_some());
+    }
+
+    #[test]
+    fn skips_collisions() {
+        let mut pool = new_test_pool();
+        pool.allocate(4, Some("subnet-1")).unwrap();
+        pool.allocate(4, None).unwrap();
+        pool.allocate(4, None).unwrap();
+        pool.allocate(4, None).unwrap();
+        assert_eq!(pool.assign_names_from_template("subnet-{index}"), Ok(2));
+        assert_eq!(
+            pool.records()
+                .map(|record| record.name.as_deref())
+                .collect_vec(),
+            vec![Some("subnet-1"), Some("subnet-0"), None, Some("subnet-2")]
+        );
+    }
+
+    #[test]
+    fn unknown_field() {
+        let mut pool = new_test_pool();
+        pool.allocate(4, None).unwrap();
+        assert_eq!(
+            pool.assign_names_from_template("subnet-{position}"),
+            Err(FormatError::UnknownField("position".to_string()))
+        );
+        assert_eq!(pool.named_count(), 0);
+    }
+}
+
+mod compact {
+    use super::*;
+
+    /// Split every free subspace down to `depth` levels below `subspace`,
+    /// as a tree can be left when splits are not merged back.
+    fn split_down(subspace: &mut Subspace, depth: usize) {
+        if depth == 0 {
+            return;
+        }
+        if subspace.state == State::Free {
+            subspace.split();
+        }
+        if subspace.state == State::Unavailable {
+            split_down(subspace.low.as_deref_mut().unwrap(), depth - 1);
+            split_down(subspace.high.as_deref_mut().unwrap(), depth - 1);
+        }
+    }
+
+    #[test]
+    fn collapses_free_subtrees() {
+        let mut pool = new_test_pool();
+        let a = pool.allocate_at_time(4, Some("a-name"), TEST_TIME).unwrap();
+        let b = pool.allocate_at_time(8, Some("b-name"), TEST_TIME).unwrap();
+        pool.allocate_at_time(12, None, TEST_TIME).unwrap();
+        split_down(&mut pool.root, 6);
+        assert_eq!(pool.free(&a), Ok(()));
+        assert_eq!(pool.free(&b), Ok(()));
+
+        let mut expected = new_test_pool();
+        expected
+            .claim_record(&test_record(
+                IpCidr::from_str("10.20.16.0/20").unwrap(),
+                None,
+            ))
+            .unwrap();
+        assert_ne!(pool, expected);
+
+        pool.compact();
+        assert_eq!(pool, expected);
+        assert_eq!(pool.max_available_bits(), 15);
+        assert_eq!(pool.validate(), Ok(()));
+    }
+
+    #[test]
+    fn empty_pool() {
+        let mut pool = new_test_pool();
+        split_down(&mut pool.root, 3);
+        pool.compact();
+        assert_eq!(pool, new_test_pool());
+    }
+}
+
+mod free_all_within {
+    use super::*;
+
+    #[test]
+    fn frees_records() {
+        let mut pool = new_test_pool();
+        pool.allocate_at_time(4, Some("a-name"), TEST_TIME).unwrap();
+        pool.allocate_at_time(4, None, TEST_TIME).unwrap();
+        pool.allocate_at_time(8, Some("b-name"), TEST_TIME).unwrap();
+        pool.allocate_at_time(12, Some("c-name"), TEST_TIME)
+            .unwrap();
+
+        let freed = pool.free_all_within(&IpCidr::from_str("10.20.0.0/20").unwrap());
+        assert_eq!(
+            freed,
+            vec![
+                CidrRecord {
+                    created_at: Some(TEST_TIME.to_string()),
+                    ..CidrRecord::new(IpCidr::from_str("10.20.0.0/28").unwrap(), Some("a-name"))
+                },
+                CidrRecord {
+                    created_at: Some(TEST_TIME.to_string()),
+                    ..CidrRecord::new(IpCidr::from_str("10.20.0.16/28").unwrap(), None)
+                },
+                CidrRecord {
+                    created_at: Some(TEST_TIME.to_string()),
+                    ..CidrRecord::new(IpCidr::from_str("10.20.1.0/24").unwrap(), Some("b-name"))
+                },
+            ]
+        );
+        assert_eq!(pool.find_by_name("a-name"), None);
+        assert_eq!(pool.find_by_name("b-name"), None);
+        assert_eq!(
+            pool.find_by_name("c-name"),
+            IpCidr::from_str("10.20.16.0/20").ok()
+        );
+        assert_eq!(pool.allocated_count(), 1);
+        assert_eq!(pool.validate(), Ok(()));
+    }
+
+    #[test]
+    fn keeps_overlapping() {
+        let mut pool = new_test_pool();
+        pool.allocate(8, Some("a-name")).unwrap();
+        let freed = pool.free_all_within(&IpCidr::from_str("10.20.0.0/28").unwrap());
+        assert!(freed.is_empty());
+        assert_eq!(pool.allocated_count(), 1);
+    }
+
+    #[test]
+    fn whole_pool() {
+        let mut pool = new_test_pool();
+        pool.allocate(4, Some("a-name")).unwrap();
+        pool.allocate(8, None).unwrap();
+        let freed = pool.free_all_within(&IpCidr::from_str("10.0.0.0/8").unwrap());
+        assert_eq!(freed.len(), 2);
+        assert_eq!(pool, new_test_pool());
+    }
+}
+
+mod reclaim_unnamed_older_than {
+    use super::*;
+    use chrono::{DateTime, Utc};
+
+    static OLD_TIME: &str = "2024-01-01T00:00:00Z";
+    static NEW_TIME: &str = "2024-02-01T00:00:00Z";
+
+    fn cutoff() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(TEST_TIME)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn only_old_unnamed() {
+        let mut pool = new_test_pool();
+        let old_unnamed = pool.allocate_at_time(4, None, OLD_TIME).unwrap();
+        let old_named = pool.allocate_at_time(4, Some("old"), OLD_TIME).unwrap();
+        let new_unnamed = pool.allocate_at_time(4, None, NEW_TIME).unwrap();
+        let new_named = pool.allocate_at_time(4, Some("new"), NEW_TIME).unwrap();
+        let other_old_unnamed = pool.allocate_at_time(8, None, OLD_TIME).unwrap();
+
+        assert_eq!(
+            pool.reclaim_unnamed_older_than(&cutoff()),
+            vec![old_unnamed, other_old_unnamed]
+        );
+        assert_eq!(
+            pool.cidrs().copied().collect::<Vec<_>>(),
+            vec![old_named, new_unnamed, new_named]
+        );
+        assert_eq!(pool.find_by_name("old"), Some(old_named));
+    }
+
+    #[test]
+    fn keeps_untimed() {
+        let mut pool = new_test_pool();
+        let cidr = IpCidr::from_str("10.20.0.0/24").unwrap();
+        pool.claim_record(&CidrRecord::new(cidr, None)).unwrap();
+        assert_eq!(pool.reclaim_unnamed_older_than(&cutoff()), vec![]);
+        assert!(pool.contains(&cidr));
+    }
+
+    #[test]
+    fn other_time_zone() {
+        let mut pool = new_test_pool();
+        let cidr = pool
+            .allocate_at_time(4, None, "2024-01-26T13:30:00+02:00")
+            .unwrap();
+        assert_eq!(pool.reclaim_unnamed_older_than(&cutoff()), vec![cidr]);
+    }
+}
+
+mod free {
+    use super::*;
+
+    #[test]
+    fn out_of_range() {
+        let mut pool = new_test_pool();
+        assert_eq!(
+            pool.free(&IpCidr::from_str("20.20.0.0/16").unwrap()),
+            Err(AllocateError::NoSuchObject)
+        );
+        assert_eq!(pool.allocated_count(), pool.cidrs().count());
+    }
+
+    #[test]
+    fn family_mismatch() {
+        let mut pool = new_test_pool6();
+        pool.allocate(16, None).unwrap();
+        assert_eq!(pool.free(&TEST_CIDR4), Err(AllocateError::FamilyMismatch));
+        assert_eq!(pool.allocated_count(), 1);
+    }
+
+    #[test]
+    fn removes_name() {
+        let mut pool = new_test_pool();
+        let cidr = pool.allocate(4, Some("a-name")).unwrap();
+        assert_eq!(pool.free(&cidr), Ok(()));
+        assert_eq!(pool.find_by_name("a-name"), None);
+        assert_eq!(pool.named_count(), 0);
+        pool.allocate(4, Some("a-name")).unwrap();
+    }
+
+    #[test]
+    fn unallocated_keeps_names() {
+        let mut pool = new_test_pool();
+        let cidr = pool.allocate(4, Some("a-name")).unwrap();
+        let parent = util::parent(&cidr).unwrap();
+        assert_eq!(pool.free(&parent), Err(AllocateError::NoSuchObject));
+        assert_eq!(pool.find_by_name("a-name"), Some(cidr));
+    }
+
+    #[test]
+    fn free() {
+        let index_list = [0, 1, 2, 3];
+        for indices in index_list.iter().permutations(index_list.len()) {
+            let mut pool = new_test_pool();
+            let mut cidrs = vec![];
+            for _ in 0..index_list.len() {
+                cidrs.push(pool.allocate(14, None).unwrap());
+            }
+            fn free_test(pool: &mut SubnetPool, cidr: &IpCidr) {
+                assert_eq!(pool.free(cidr), Ok(()));
+                assert_eq!(pool.free(cidr), Err(AllocateError::NoSuchObject));
+                pool.claim(cidr, None).unwrap();
+            }
+            for index in indices {
+                let cidr = cidrs[*index];
+                free_test(&mut pool, &cidr);
+            }
+            assert_eq!(pool.allocated_count(), pool.cidrs().count());
+        }
+    }
+}
+
+mod free_by_name {
+    use super::*;
+
+    #[test]
+    fn unknown_name() {
+        let mut pool = new_test_pool();
+        pool.allocate(4, Some("a-name")).unwrap();
+        assert_eq!(
+            pool.free_by_name("b-name"),
+            Err(AllocateError::NoSuchObject)
+        );
+        assert_eq!(pool.allocated_count(), 1);
+        assert_eq!(pool.named_count(), 1);
+    }
+
+    #[test]
+    fn known_name() {
+        let mut pool = new_test_pool();
+        let cidr = pool.allocate(4, Some("a-name")).unwrap();
+        pool.allocate(4, Some("b-name")).unwrap();
+        assert_eq!(pool.free_by_name("a-name"), Ok(()));
+        assert!(!pool.contains(&cidr));
+        assert_eq!(pool.find_by_name("a-name"), None);
+        assert_eq!(pool.named_count(), 1);
+        assert_eq!(
+            pool.free_by_name("a-name"),
+            Err(AllocateError::NoSuchObject)
+        );
+    }
+}
+
+mod set_locked {
+    use super::*;
+    use crate::errors::RenameError;
+
+    fn new_locked_pool() -> (SubnetPool, IpCidr) {
+        let mut pool = new_test_pool();
+        let cidr = pool.allocate(4, Some("a-name")).unwrap();
+        pool.set_locked(&cidr, true).unwrap();
+        (pool, cidr)
+    }
+
+    #[test]
+    fn not_found() {
+        let mut pool = new_test_pool();
+        let cidr = IpCidr::from_str("10.20.0.0/28").unwrap();
+        assert_eq!(
+            pool.set_locked(&cidr, true),
+            Err(AllocateError::NoSuchObject)
+        );
+        assert_eq!(
+            pool.set_locked(&TEST_CIDR6, true),
+            Err(AllocateError::FamilyMismatch)
+        );
+    }
+
+    #[test]
+    fn free_locked() {
+        let (mut pool, cidr) = new_locked_pool();
+        assert_eq!(pool.free(&cidr), Err(AllocateError::Locked));
+        assert_eq!(pool.free_by_name("a-name"), Err(AllocateError::Locked));
+        assert_eq!(pool.free_all_within(&TEST_CIDR4), vec![]);
+        assert!(pool.contains(&cidr));
+        assert_eq!(pool.force_free(&cidr), Ok(()));
+        assert!(!pool.contains(&cidr));
+        assert_eq!(pool.find_by_name("a-name"), None);
+    }
+
+    #[test]
+    fn unlock() {
+        let (mut pool, cidr) = new_locked_pool();
+        pool.set_locked(&cidr, false).unwrap();
+        assert_eq!(pool.free(&cidr), Ok(()));
+    }
+
+    #[test]
+    fn force_free_family_mismatch() {
+        let (mut pool, _) = new_locked_pool();
+        assert_eq!(
+            pool.force_free(&TEST_CIDR6),
+            Err(AllocateError::FamilyMismatch)
+        );
+        assert_eq!(pool.allocated_count(), 1);
+    }
+
+    #[test]
+    fn rename_locked() {
+        let (mut pool, cidr) = new_locked_pool();
+        assert_eq!(pool.rename(&cidr, Some("b-name")), Err(RenameError::Locked));
+        assert_eq!(pool.rename(&cidr, Some("a-name")), Ok(()));
+        assert_eq!(
+            pool.update_metadata(&cidr, None, None, &[]),
+            Err(RenameError::Locked)
+        );
+        assert_eq!(pool.find_by_name("a-name"), Some(cidr));
+        assert_eq!(pool.force_rename(&cidr, Some("b-name")), Ok(()));
+        assert_eq!(pool.find_by_name("b-name"), Some(cidr));
+        assert!(pool.records().next().unwrap().locked);
+    }
+
+    #[test]
+    fn split_locked() {
+        let (mut pool, cidr) = new_locked_pool();
+        assert_eq!(pool.split(&cidr, 2), Err(AllocateError::Locked));
+        assert_eq!(pool.find_by_name("a-name"), Some(cidr));
+    }
+}
+
+mod split {
+    use super::*;
+
+    fn quarters() -> Vec<IpCidr> {
+        [
+            "10.20.0.0/26",
+            "10.20.0.64/26",
+            "10.20.0.128/26",
+            "10.20.0.192/26",
+        ]
+        .iter()
+        .map(|cidr| IpCidr::from_str(cidr).unwrap())
+        .collect()
+    }
+
+    #[test]
+    fn free_block() {
+        let mut pool = new_test_pool();
+        let parent = IpCidr::from_str("10.20.0.0/24").unwrap();
+        let children = pool.split(&parent, 6).unwrap();
+        assert_eq!(children, quarters());
+        assert_eq!(pool.cidrs().copied().collect::<Vec<_>>(), quarters());
+        assert_eq!(pool.allocated_count(), 4);
+    }
+
+    #[test]
+    fn allocated_block() {
+        let mut pool = new_test_pool();
+        let parent = pool.allocate(8, Some("parent")).unwrap();
+        let children = pool.split(&parent, 6).unwrap();
+        assert_eq!(children, quarters());
+        assert!(!pool.contains(&parent));
+        assert_eq!(pool.find_by_name("parent"), None);
+        assert_eq!(pool.allocated_count(), 4);
+    }
+
+    #[test]
+    fn overlaps_allocation() {
+        let mut pool = new_test_pool();
+        let existing = IpCidr::from_str("10.20.0.128/28").unwrap();
+        pool.claim(&existing, Some("existing")).unwrap();
+        let parent = IpCidr::from_str("10.20.0.0/24").unwrap();
+        assert_eq!(pool.split(&parent, 6), Err(AllocateError::NoSpaceAvailable));
+        assert_eq!(pool.cidrs().copied().collect::<Vec<_>>(), vec![existing]);
+        assert_eq!(pool.allocated_count(), 1);
+    }
+
+    #[test]
+    fn too_many_bits() {
+        let mut pool = new_test_pool();
+        let parent = IpCidr::from_str("10.20.0.0/24").unwrap();
+        assert_eq!(pool.split(&parent, 9), Err(AllocateError::NoSpaceAvailable));
+        assert_eq!(pool.allocated_count(), 0);
+    }
+
+    #[test]
+    fn out_of_range() {
+        let mut pool = new_test_pool();
+        let parent = IpCidr::from_str("10.21.0.0/24").unwrap();
+        assert_eq!(pool.split(&parent, 6), Err(AllocateError::NoSpaceAvailable));
+    }
+}
+
+mod coalesce {
+    use super::*;
+
+    #[test]
+    fn siblings() {
+        let mut pool = new_test_pool();
+        let low = pool.allocate(7, Some("low")).unwrap();
+        let high = pool.allocate(7, Some("high")).unwrap();
+        let parent = pool.coalesce(&high, &low, Some("parent")).unwrap();
+        assert_eq!(parent, IpCidr::from_str("10.20.0.0/24").unwrap());
+        assert!(pool.contains(&parent));
+        assert_eq!(pool.find_by_name("parent"), Some(parent));
+        assert_eq!(pool.find_by_name("low"), None);
+        assert_eq!(pool.find_by_name("high"), None);
+        assert_eq!(pool.allocated_count(), 1);
+        assert_eq!(pool.named_count(), 1);
+    }
+
+    #[test]
+    fn reuse_sibling_name() {
+        let mut pool = new_test_pool();
+        let low = pool.allocate(7, Some("low")).unwrap();
+        let high = pool.allocate(7, None).unwrap();
+        let parent = pool.coalesce(&low, &high, Some("low")).unwrap();
+        assert_eq!(pool.find_by_name("low"), Some(parent));
+    }
+
+    #[test]
+    fn not_adjacent() {
+        let mut pool = new_test_pool();
+        pool.allocate(7, None).unwrap();
+        let second = pool.allocate(7, None).unwrap();
+        let third = pool.allocate(7, None).unwrap();
+        assert_eq!(
+            pool.coalesce(&second, &third, None),
+            Err(AllocateError::NotSiblings)
+        );
+        assert_eq!(pool.allocated_count(), 3);
+    }
+
+    #[test]
+    fn different_sizes() {
+        let mut pool = new_test_pool();
+        let low = pool.allocate(7, None).unwrap();
+        let high = pool.allocate(6, None).unwrap();
+        assert_eq!(
+            pool.coalesce(&low, &high, None),
+            Err(AllocateError::NotSiblings)
+        );
+    }
+
+    #[test]
+    fn not_allocated() {
+        let mut pool = new_test_pool();
+        let low = pool.allocate(7, None).unwrap();
+        let high = IpCidr::from_str("10.20.0.128/25").unwrap();
+        assert_eq!(
+            pool.coalesce(&low, &high, None),
+            Err(AllocateError::NoSuchObject)
+        );
+        assert!(pool.contains(&low));
+    }
+
+    #[test]
+    fn duplicate_name() {
+        let mut pool = new_test_pool();
+        let low = pool.allocate(7, None).unwrap();
+        let high = pool.allocate(7, None).unwrap();
+        pool.allocate(4, Some("taken")).unwrap();
+        assert_eq!(
+            pool.coalesce(&low, &high, Some("taken")),
+            Err(AllocateError::DuplicateName)
+        );
+        assert!(pool.contains(&low));
+        assert!(pool.contains(&high));
+    }
+}
+
+mod claim_batch {
+    use super::*;
+
+    fn items() -> Vec<(IpCidr, Option<String>)> {
+        vec![
+            (
+                IpCidr::from_str("10.20.0.0/24").unwrap(),
+                Some("a-name".to_string()),
+            ),
+            (IpCidr::from_str("10.20.1.0/25").unwrap(), None),
+            (
+                IpCidr::from_str("10.20.2.0/24").unwrap(),
+                Some("b-name".to_string()),
+            ),
+        ]
+    }
+
+    fn new_conflict_pool() -> SubnetPool {
+        let mut pool = new_test_pool();
+        pool.claim(&IpCidr::from_str("10.20.1.0/24").unwrap(), Some("taken"))
+            .unwrap();
+        pool
+    }
+
+    #[test]
+    fn success() {
+        let mut pool = new_test_pool();
+        let outcomes = pool.claim_batch(&items(), true);
+        assert_eq!(
+            outcomes,
+            items()
+                .into_iter()
+                .map(|(cidr, _)| (cidr, Ok(())))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(pool.allocated_count(), 3);
+        assert_eq!(pool.named_count(), 2);
+    }
+
+    #[test]
+    fn continue_past_conflict() {
+        let mut pool = new_conflict_pool();
+        let outcomes = pool.claim_batch(&items(), false);
+        assert_eq!(
+            outcomes,
+            vec![
+                (IpCidr::from_str("10.20.0.0/24").unwrap(), Ok(())),
+                (
+                    IpCidr::from_str("10.20.1.0/25").unwrap(),
+                    Err(AllocateError::NoSpaceAvailable)
+                ),
+                (IpCidr::from_str("10.20.2.0/24").unwrap(), Ok(())),
+            ]
+        );
+        assert_eq!(pool.allocated_count(), 3);
+        assert!(pool.find_by_name("a-name").is_some());
+        assert!(pool.find_by_name("b-name").is_some());
+    }
+
+    #[test]
+    fn stop_on_conflict() {
+        let mut pool = new_conflict_pool();
+        let before = pool.clone();
+        let outcomes = pool.claim_batch(&items(), true);
+        assert_eq!(
+            outcomes,
+            vec![
+                (IpCidr::from_str("10.20.0.0/24").unwrap(), Ok(())),
+                (
+                    IpCidr::from_str("10.20.1.0/25").unwrap(),
+                    Err(AllocateError::NoSpaceAvailable)
+                ),
+            ]
+        );
+        assert_eq!(pool, before);
+        assert_eq!(pool.find_by_name("a-name"), None);
+    }
+
+    #[test]
+    fn duplicate_name_in_batch() {
+        let mut pool = new_test_pool();
+        let mut items = items();
+        items[2].1 = Some("a-name".to_string());
+        let outcomes = pool.claim_batch(&items, false);
+        assert_eq!(outcomes[2].1, Err(AllocateError::DuplicateName));
+        assert_eq!(pool.allocated_count(), 2);
+    }
+}
+
+mod claim_idempotent {
+    use super::*;
+
+    #[test]
+    fn first_claim() {
+        let mut pool = new_test_pool();
+        let cidr = IpCidr::from_str("10.20.1.0/24").unwrap();
+        assert_eq!(pool.claim_idempotent(&cidr, Some("a-name")), Ok(true));
+        assert_eq!(pool.find_by_name("a-name"), Some(cidr));
+    }
+
+    #[test]
+    fn repeat_same_name() {
+        let mut pool = new_test_pool();
+        let cidr = IpCidr::from_str("10.20.1.0/24").unwrap();
+        pool.claim_idempotent(&cidr, Some("a-name")).unwrap();
+        assert_eq!(pool.claim_idempotent(&cidr, Some("a-name")), Ok(false));
+        assert_eq!(pool.allocated_count(), 1);
+        assert_eq!(pool.find_by_name("a-name"), Some(cidr));
+    }
+
+    #[test]
+    fn repeat_unnamed() {
+        let mut pool = new_test_pool();
+        let cidr = IpCidr::from_str("10.20.1.0/24").unwrap();
+        pool.claim_idempotent(&cidr, None).unwrap();
+        assert_eq!(pool.claim_idempotent(&cidr, None), Ok(false));
+        assert_eq!(pool.allocated_count(), 1);
+    }
+
+    #[test]
+    fn repeat_empty_name() {
+        let mut pool = new_test_pool();
+        let cidr = IpCidr::from_str("10.20.1.0/24").unwrap();
+        pool.claim_idempotent(&cidr, None).unwrap();
+        assert_eq!(pool.claim_idempotent(&cidr, Some("")), Ok(false));
+        assert_eq!(pool.allocated_count(), 1);
+        assert_eq!(pool.names().count(), 0);
+    }
+
+    #[test]
+    fn repeat_different_name() {
+        let mut pool = new_test_pool();
+        let cidr = IpCidr::from_str("10.20.1.0/24").unwrap();
+        pool.claim_idempotent(&cidr, Some("a-name")).unwrap();
+        assert_eq!(
+            pool.claim_idempotent(&cidr, Some("b-name")),
+            Err(AllocateError::NoSpaceAvailable)
+        );
+        assert_eq!(
+            pool.claim_idempotent(&cidr, None),
+            Err(AllocateError::NoSpaceAvailable)
+        );
+        assert_eq!(pool.find_by_name("a-name"), Some(cidr));
+        assert_eq!(pool.find_by_name("b-name"), None);
+    }
+
+    #[test]
+    fn overlap() {
+        let mut pool = new_test_pool();
+        pool.claim_idempotent(&IpCidr::from_str("10.20.1.0/24").unwrap(), Some("a-name"))
+            .unwrap();
+        assert_eq!(
+            pool.claim_idempotent(&IpCidr::from_str("10.20.0.0/23").unwrap(), Some("b-name")),
+            Err(AllocateError::NoSpaceAvailable)
+        );
+        assert_eq!(
+            pool.claim_idempotent(&IpCidr::from_str("10.20.1.0/25").unwrap(), Some("b-name")),
+            Err(AllocateError::NoSpaceAvailable)
+        );
+    }
+
+    #[test]
+    fn name_taken_elsewhere() {
+        let mut pool = new_test_pool();
+        pool.claim_idempotent(&IpCidr::from_str("10.20.1.0/24").unwrap(), Some("a-name"))
+            .unwrap();
+        assert_eq!(
+            pool.claim_idempotent(&IpCidr::from_str("10.20.2.0/24").unwrap(), Some("a-name")),
+            Err(AllocateError::DuplicateName)
+        );
+    }
+}
+
+mod claim {
+    use super::*;
+    use crate::errors::AllocateError;
+
+    #[test]
+    fn out_of_range() {
+        let mut pool = new_test_pool();
+        let cidr = IpCidr::V4(Ipv4Cidr::new(Ipv4Addr::new(10, 21, 0, 0), 28).unwrap());
+        let result = pool.claim(&cidr, None);
+        assert_eq!(result, Err(AllocateError::NoSpaceAvailable));
+        assert_eq!(pool.allocated_count(), pool.cidrs().count());
+    }
+
+    #[test]
+    fn family_mismatch() {
+        let mut pool = new_test_pool();
+        let cidr = IpCidr::from_str("1:2:3:4::/112").unwrap();
+        let result = pool.claim(&cidr, Some("a-name"));
+        assert_eq!(result, Err(AllocateError::FamilyMismatch));
+        assert_eq!(pool.allocated_count(), 0);
+        assert_eq!(pool.find_by_name("a-name"), None);
+
+        let mut pool = new_test_pool6();
+        let result = pool.claim(&TEST_CIDR4, None);
+        assert_eq!(result, Err(AllocateError::FamilyMismatch));
+        assert_eq!(pool.allocated_count(), 0);
+    }
+
+    #[test]
+    fn already_claimed() {
+        let mut pool = new_test_pool();
+        let cidr = IpCidr::V4(Ipv4Cidr::new(Ipv4Addr::new(10, 20, 0, 0), 28).unwrap());
+        pool.claim(&cidr, None).unwrap();
+        let result = pool.claim(&cidr, None);
+        assert_eq!(result, Err(AllocateError::NoSpaceAvailable));
+        assert_eq!(pool.allocated_count(), pool.cidrs().count());
+    }
+
+    #[test]
+    fn failed_claim_keeps_name_free() {
+        let mut pool = new_test_pool();
+        let cidr = IpCidr::from_str("10.20.0.0/28").unwrap();
+        pool.claim(&cidr, None).unwrap();
+        let result = pool.claim(&cidr, Some("a-name"));
+        assert_eq!(result, Err(AllocateError::NoSpaceAvailable));
+        assert_eq!(pool.find_by_name("a-name"), None);
+        assert_eq!(pool.named_count(), 0);
+    }
+
+    #[test]
+    fn already_allocated() {
+        let mut pool = new_test_pool();
+        let cidr = IpCidr::V4(Ipv4Cidr::new(Ipv4Addr::new(10, 20, 0, 0), 28).unwrap());
+        pool.allocate(16, None).unwrap();
+        let result = pool.claim(&cidr, None);
+        assert_eq!(result, Err(AllocateError::NoSpaceAvailable));
+        assert_eq!(pool.allocated_count(), pool.cidrs().count());
+    }
+
+    #[test]
+    fn already_named() {
+        let mut pool = new_test_pool();
+        let cidr = IpCidr::V4(Ipv4Cidr::new(Ipv4Addr::new(10, 20, 0, 16), 28).unwrap());
+        pool.allocate(4, Some("a-name")).unwrap();
+        let result = pool.claim(&cidr, Some("a-name"));
+        assert_eq!(result, Err(AllocateError::DuplicateName));
+        assert_eq!(pool.allocated_count(), pool.cidrs().count());
+    }
+
+    #[test]
+    fn unnamed() {
+        let mut pool = new_test_pool();
+        let cidr = IpCidr::V4(Ipv4Cidr::new(Ipv4Addr::new(10, 20, 0, 0), 28).unwrap());
+        let result = pool.claim(&cidr, None);
+        assert_eq!(result, Ok(()));
+        assert_eq!(pool.allocated_count(), pool.cidrs().count());
+    }
+
+    #[test]
+    fn named() {
+        let mut pool = new_test_pool();
+        let cidr = IpCidr::V4(Ipv4Cidr::new(Ipv4Addr::new(10, 20, 0, 0), 28).unwrap());
+        let result = pool.claim(&cidr, Some("a-name"));
+        assert_eq!(result, Ok(()));
+        let looked_up = pool.find_by_name("a-name").unwrap();
+        assert_eq!(looked_up, cidr);
+        assert_eq!(pool.allocated_count(), pool.cidrs().count());
+    }
+}
+
+mod claim_full {
+    use super::*;
+    use crate::errors::AllocateError;
+
+    #[test]
+    fn success() {
+        let mut pool = new_test_pool();
+        let cidr = IpCidr::from_str("10.20.1.0/24").unwrap();
+        pool.claim_full(&cidr, Some("a-name"), Some("Lab network"), &["lab", "west"])
+            .unwrap();
+        let record = pool.records().next().unwrap();
+        assert_eq!(record.cidr, cidr);
+        assert_eq!(record.name.as_deref(), Some("a-name"));
+        assert_eq!(record.description.as_deref(), Some("Lab network"));
+        assert_eq!(record.tags, vec!["lab".to_string(), "west".to_string()]);
+        assert!(record.created_at.is_some());
+        assert_eq!(pool.find_by_name("a-name"), Some(cidr));
+    }
+
+    #[test]
+    fn already_allocated() {
+        let mut pool = new_test_pool();
+        let cidr = IpCidr::from_str("10.20.1.0/24").unwrap();
+        pool.claim(&cidr, None).unwrap();
+        let result = pool.claim_full(&cidr, None, Some("Lab network"), &["lab"]);
+        assert_eq!(result, Err(AllocateError::NoSpaceAvailable));
+        assert_eq!(pool.records().next().unwrap().description, None);
+    }
+
+    #[test]
+    fn cleared_by_free() {
+        let mut pool = new_test_pool();
+        let cidr = IpCidr::from_str("10.20.1.0/24").unwrap();
+        pool.claim_full(&cidr, None, Some("Lab network"), &["lab"])
+            .unwrap();
+        assert_eq!(pool.free(&cidr), Ok(()));
+        pool.claim(&cidr, None).unwrap();
+        let record = pool.records().next().unwrap();
+        assert_eq!(record.description, None);
+        assert!(record.tags.is_empty());
+    }
+}
+
+mod claim_free_within {
+    use super::*;
+
+    fn cidr(text: &str) -> IpCidr {
+        IpCidr::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn hole_in_middle() {
+        let mut pool = new_test_pool();
+        pool.claim(&cidr("10.20.0.112/28"), Some("hole")).unwrap();
+        let claimed = pool
+            .claim_free_within(&cidr("10.20.0.0/24"), |block| {
+                Some(format!("free-{}", block.first_address()))
+            })
+            .unwrap();
+        assert_eq!(
+            claimed.iter().map(|block| block.to_string()).collect_vec(),
+            vec![
+                "10.20.0.0/26",
+                "10.20.0.64/27",
+                "10.20.0.96/28",
+                "10.20.0.128/25"
+            ]
+        );
+        assert_eq!(pool.allocated_count(), 5);
+        assert_eq!(pool.find_by_name("hole"), Some(cidr("10.20.0.112/28")));
+        assert_eq!(
+            pool.find_by_name("free-10.20.0.96"),
+            Some(cidr("10.20.0.96/28"))
+        );
+    }
+
+    #[test]
+    fn within_free_block() {
+        let mut pool = new_test_pool();
+        let claimed = pool
+            .claim_free_within(&cidr("10.20.1.0/24"), |_| None)
+            .unwrap();
+        assert_eq!(claimed, vec![cidr("10.20.1.0/24")]);
+        assert!(pool.contains(&cidr("10.20.1.0/24")));
+    }
+
+    #[test]
+    fn fully_allocated() {
+        let mut pool = new_test_pool();
+        pool.claim(&cidr("10.20.1.0/24"), None).unwrap();
+        let claimed = pool
+            .claim_free_within(&cidr("10.20.1.0/25"), |_| None)
+            .unwrap();
+        assert_eq!(claimed, vec![]);
+    }
+
+    #[test]
+    fn out_of_range() {
+        let mut pool = new_test_pool();
+        assert_eq!(
+            pool.claim_free_within(&cidr("10.21.0.0/24"), |_| None),
+            Err(AllocateError::NoSpaceAvailable)
+        );
+        assert_eq!(
+            pool.claim_free_within(&TEST_CIDR6, |_| None),
+            Err(AllocateError::FamilyMismatch)
+        );
+    }
+
+    #[test]
+    fn duplicate_name_rolls_back() {
+        let mut pool = new_test_pool();
+        pool.claim(&cidr("10.20.0.112/28"), Some("taken")).unwrap();
+        let result = pool.claim_free_within(&cidr("10.20.0.0/24"), |block| {
+            match block.first_address().to_string().as_str() {
+                "10.20.0.96" => Some("taken".to_string()),
+                _ => None,
+            }
+        });
+        assert_eq!(result, Err(AllocateError::DuplicateName));
+        assert_eq!(pool.allocated_count(), 1);
+        assert_eq!(pool.max_available_bits(), 15);
+    }
+}
+
+mod rename {
+    use super::*;
+    use crate::errors::RenameError;
+
+    #[test]
+    fn not_found() {
+        let mut pool = new_test_pool();
+        let cidr = IpCidr::V4(Ipv4Cidr::new(Ipv4Addr::new(10, 21, 0, 0), 28).unwrap());
+        let result = pool.rename(&cidr, Some("a-name"));
+        assert_eq!(result, Err(RenameError::NoSuchObject));
+        assert_eq!(pool.find_by_name("a-name"), None);
+    }
+    #[test]
+    fn family_mismatch() {
+        let mut pool = new_test_pool();
+        let result = pool.rename(&TEST_CIDR6, Some("a-name"));
+        assert_eq!(result, Err(RenameError::FamilyMismatch));
+        assert_eq!(pool.find_by_name("a-name"), None);
+    }
+    #[test]
+    fn already_not_set() {
+        let mut pool = new_test_pool();
+        let cidr = IpCidr::V4(Ipv4Cidr::new(Ipv4Addr::new(10, 20, 0, 0), 28).unwrap());
+        pool.claim(&cidr, None).unwrap();
+        let result = pool.rename(&cidr, None);
+        assert_eq!(result, Ok(()));
+    }
+    #[test]
+    fn same_name() {
+        let mut pool = new_test_pool();
+        let cidr = IpCidr::V4(Ipv4Cidr::new(Ipv4Addr::new(10, 20, 0, 0), 28).unwrap());
+        pool.claim(&cidr, Some("same-name")).unwrap();
+        let result = pool.rename(&cidr, Some("same-name"));
+        assert_eq!(result, Ok(()));
+        assert_eq!(pool.find_by_name("same-name").unwrap(), cidr);
+    }
+    #[test]
+    fn already_exists() {
+        let mut pool = new_test_pool();
+        let cidr = IpCidr::V4(Ipv4Cidr::new(Ipv4Addr::new(10, 20, 0, 16), 28).unwrap());
+        let existing_cidr = pool.allocate(4, Some("already-exists")).unwrap();
+        pool.claim(&cidr, Some("old-name")).unwrap();
+        let result = pool.rename(&cidr, Some("already-exists"));
+        assert_eq!(result, Err(RenameError::DuplicateName));
+        assert_eq!(pool.find_by_name("already-exists").unwrap(), existing_cidr);
+        assert_eq!(pool.find_by_name("old-name").unwrap(), cidr);
+    }
+
+    #[test]
+    fn success() {
+        let mut pool = new_test_pool();
+        let cidr = pool.allocate(4, Some("old-name")).unwrap();
+        let result = pool.rename(&cidr, Some("new-name"));
+        assert_eq!(result, Ok(()));
+        assert_eq!(pool.find_by_name("new-name").unwrap(), cidr);
+        assert_eq!(pool.find_by_name("old-name"), None);
+    }
+}
+
+mod update_metadata {
+    use super::*;
+    use crate::errors::RenameError;
+    use crate::pool::MetadataUpdate;
+
+    fn new_update_pool() -> (SubnetPool, IpCidr) {
+        let mut pool = new_test_pool();
+        let cidr = IpCidr::from_str("10.20.1.0/24").unwrap();
+        pool.claim_full(&cidr, Some("a-name"), Some("Lab network"), &["lab"])
+            .unwrap();
+        (pool, cidr)
+    }
+
+    #[test]
+    fn no_change() {
+        let (mut pool, cidr) = new_update_pool();
+        let update = pool
+            .update_metadata(&cidr, Some("a-name"), Some("Lab network"), &["lab"])
+            .unwrap();
+        assert_eq!(update, MetadataUpdate::default());
+        assert!(!update.changed());
+        assert_eq!(pool.find_by_name("a-name"), Some(cidr));
+    }
+
+    #[test]
+    fn name_only() {
+        let (mut pool, cidr) = new_update_pool();
+        let update = pool
+            .update_metadata(&cidr, Some("b-name"), Some("Lab network"), &["lab"])
+            .unwrap();
+        assert_eq!(
+            update,
+            MetadataUpdate {
+                name: true,
+                ..MetadataUpdate::default()
+            }
+        );
+        assert_eq!(pool.find_by_name("a-name"), None);
+        assert_eq!(pool.find_by_name("b-name"), Some(cidr));
+    }
+
+    #[test]
+    fn description_only() {
+        let (mut pool, cidr) = new_update_pool();
+        let update = pool
+            .update_metadata(&cidr, Some("a-name"), Some("Other lab"), &["lab"])
+            .unwrap();
+        assert_eq!(
+            update,
+            MetadataUpdate {
+                description: true,
+                ..MetadataUpdate::default()
+            }
+        );
+        assert!(update.changed());
+        let record = pool.records().next().unwrap();
+        assert_eq!(record.description.as_deref(), Some("Other lab"));
+        assert_eq!(pool.find_by_name("a-name"), Some(cidr));
+    }
+
+    #[test]
+    fn tags_only() {
+        let (mut pool, cidr) = new_update_pool();
+        let update = pool
+            .update_metadata(&cidr, Some("a-name"), Some("Lab network"), &[])
+            .unwrap();
+        assert_eq!(
+            update,
+            MetadataUpdate {
+                tags: true,
+                ..MetadataUpdate::default()
+            }
+        );
+        assert!(pool.records().next().unwrap().tags.is_empty());
+    }
+
+    #[test]
+    fn duplicate_name() {
+        let (mut pool, cidr) = new_update_pool();
+        pool.allocate(4, Some("b-name")).unwrap();
+        let result = pool.update_metadata(&cidr, Some("b-name"), None, &[]);
+        assert_eq!(result, Err(RenameError::DuplicateName));
+        let record = pool.records_within(&cidr).next().unwrap();
+        assert_eq!(record.name.as_deref(), Some("a-name"));
+        assert_eq!(record.description.as_deref(), Some("Lab network"));
+    }
+
+    #[test]
+    fn not_allocated() {
+        let (mut pool, _) = new_update_pool();
+        let free = IpCidr::from_str("10.20.2.0/24").unwrap();
+        let result = pool.update_metadata(&free, None, Some("Free"), &[]);
+        assert_eq!(result, Err(RenameError::NoSuchObject));
+    }
+}
+
+mod swap_names {
+    use super::*;
+    use crate::errors::RenameError;
+
+    #[test]
+    fn not_found() {
+        let mut pool = new_test_pool();
+        let cidr = pool.allocate(4, Some("a-name")).unwrap();
+        let missing = IpCidr::from_str("10.20.1.0/28").unwrap();
+        assert_eq!(
+            pool.swap_names(&cidr, &missing),
+            Err(RenameError::NoSuchObject)
+        );
+        assert_eq!(
+            pool.swap_names(&missing, &cidr),
+            Err(RenameError::NoSuchObject)
+        );
+        assert_eq!(pool.find_by_name("a-name").unwrap(), cidr);
+    }
+
+    #[test]
+    fn same_cidr() {
+        let mut pool = new_test_pool();
+        let cidr = pool.allocate(4, Some("a-name")).unwrap();
+        assert_eq!(pool.swap_names(&cidr, &cidr), Ok(()));
+        assert_eq!(pool.find_by_name("a-name").unwrap(), cidr);
+    }
+
+    #[test]
+    fn one_unnamed() {
+        let mut pool = new_test_pool();
+        let cidr_a = pool.allocate(4, Some("a-name")).unwrap();
+        let cidr_b = pool.allocate(4, None).unwrap();
+        assert_eq!(pool.swap_names(&cidr_a, &cidr_b), Ok(()));
+        assert_eq!(pool.find_by_name("a-name").unwrap(), cidr_b);
+        assert_eq!(pool.named_count(), 1);
+        assert_eq!(pool.root.find_record(&cidr_a).unwrap().record.name, None);
+    }
+
+    #[test]
+    fn success() {
+        let mut pool = new_test_pool();
+        let cidr_a = pool.allocate(4, Some("a-name")).unwrap();
+        let cidr_b = pool.allocate(6, Some("b-name")).unwrap();
+        assert_eq!(pool.swap_names(&cidr_a, &cidr_b), Ok(()));
+        assert_eq!(pool.find_by_name("a-name").unwrap(), cidr_b);
+        assert_eq!(pool.find_by_name("b-name").unwrap(), cidr_a);
+        assert_eq!(
+            pool.root
+                .find_record(&cidr_a)
+                .unwrap()
+                .record
+                .name
+                .as_deref(),
+            Some("b-name")
+        );
+        assert_eq!(
+            pool.root
+                .find_record(&cidr_b)
+                .unwrap()
+                .record
+                .name
+                .as_deref(),
+            Some("a-name")
+        );
+        assert_eq!(pool.named_count(), 2);
+    }
+}
+
+mod enclosing {
+    use super::*;
+
+    #[test]
+    fn empty() {
+        assert_eq!(SubnetPool::enclosing(&[]), Err(AllocateError::NoSuchObject));
+    }
+
+    #[test]
+    fn single() {
+        let cidr = IpCidr::from_str("10.20.1.0/24").unwrap();
+        let pool = SubnetPool::enclosing(&[test_record(cidr, Some("a-name"))]).unwrap();
+        assert_eq!(pool.cidr(), &cidr);
+        assert_eq!(pool.find_by_name("a-name"), Some(cidr));
+    }
+
+    #[test]
+    fn success() {
+        let records = [
+            "10.20.0.0/24",
+            "10.20.1.0/24",
+            "10.20.2.0/24",
+            "10.20.3.0/24",
+        ]
+        .iter()
+        .enumerate()
+        .map(|(index, cidr)| {
+            test_record(
+                IpCidr::from_str(cidr).unwrap(),
+                Some(&format!("name-{index}")),
+            )
+        })
+        .collect_vec();
+        let pool = SubnetPool::enclosing(&records).unwrap();
+        assert_eq!(pool.cidr(), &IpCidr::from_str("10.20.0.0/22").unwrap());
+        assert_eq!(pool.records().cloned().collect_vec(), records);
+        assert_eq!(pool.max_available_bits(), 0);
+        assert_eq!(
+            pool.find_by_name("name-3"),
+            IpCidr::from_str("10.20.3.0/24").ok()
+        );
+    }
+
+    #[test]
+    fn duplicate_name() {
+        let records = [
+            test_record(IpCidr::from_str("10.20.0.0/24").unwrap(), Some("a-name")),
+            test_record(IpCidr::from_str("10.20.1.0/24").unwrap(), Some("a-name")),
+        ];
+        assert_eq!(
+            SubnetPool::enclosing(&records),
+            Err(AllocateError::DuplicateName)
+        );
+    }
+
+    #[test]
+    fn mixed_families() {
+        let records = [
+            test_record(IpCidr::from_str("10.20.0.0/24").unwrap(), None),
+            test_record(IpCidr::from_str("1:2:3:4::/64").unwrap(), None),
+        ];
+        assert_eq!(
+            SubnetPool::enclosing(&records),
+            Err(AllocateError::FamilyMismatch)
+        );
+    }
+}
+
+mod renumber {
+    use super::*;
+
+    #[test]
+    fn success() {
+        let mut pool = new_test_pool();
+        pool.allocate(4, Some("a-name")).unwrap();
+        pool.allocate(8, None).unwrap();
+        pool.claim(&IpCidr::from_str("10.20.128.0/17").unwrap(), Some("c-name"))
+            .unwrap();
+        let renumbered = pool
+            .renumber(IpCidr::from_str("172.16.0.0/16").unwrap())
+            .unwrap();
+        assert_eq!(
+            renumbered.cidr(),
+            &IpCidr::from_str("172.16.0.0/16").unwrap()
+        );
+        let records: Vec<(String, Option<String>)> = renumbered
+            .records()
+            .map(|r| (r.cidr.to_string(), r.name.clone()))
+            .collect();
+        assert!(renumbered.records().all(|r| r.created_at.is_some()));
+        assert_eq!(
+            records,
+            vec![
+                ("172.16.0.0/28".to_string(), Some("a-name".to_string())),
+                ("172.16.1.0/24".to_string(), None),
+                ("172.16.128.0/17".to_string(), Some("c-name".to_string())),
+            ]
+        );
+        assert_eq!(
+            renumbered.find_by_name("c-name"),
+            IpCidr::from_str("172.16.128.0/17").ok()
+        );
+        assert_eq!(renumbered.named_count(), 2);
+    }
+
+    #[test]
+    fn keeps_pool_metadata() {
+        let mut pool = new_test_pool();
+        pool.set_description(Some("Lab networks"));
+        pool.set_metadata("owner", "alice");
+        let renumbered = pool
+            .renumber(IpCidr::from_str("172.16.0.0/16").unwrap())
+            .unwrap();
+        assert_eq!(renumbered.description(), Some("Lab networks"));
+        assert_eq!(renumbered.metadata(), pool.metadata());
+    }
+
+    #[test]
+    fn different_prefix() {
+        let pool = new_test_pool();
+        assert_eq!(
+            pool.renumber(IpCidr::from_str("172.16.0.0/15").unwrap()),
+            Err(AllocateError::SizeMismatch)
+        );
+    }
+
+    #[test]
+    fn different_family() {
+        let pool = new_test_pool();
+        assert_eq!(
+            pool.renumber(IpCidr::from_str("fc00::/16").unwrap()),
+            Err(AllocateError::SizeMismatch)
+        );
+    }
+}
+
+mod diff {
+    use super::*;
+
+    fn cidr(text: &str) -> IpCidr {
+        IpCidr::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn identical() {
+        let mut pool = new_test_pool();
+        pool.allocate_at_time(4, Some("a-name"), TEST_TIME).unwrap();
+        let other = pool.renumber(TEST_CIDR4).unwrap();
+        assert_eq!(pool.diff(&other), PoolDiff::default());
+    }
+
+    #[test]
+    fn added_removed_renamed() {
+        let mut pool = new_test_pool();
+        pool.claim(&cidr("10.20.0.0/24"), Some("kept")).unwrap();
+        pool.claim(&cidr("10.20.1.0/24"), Some("old-name")).unwrap();
+        pool.claim(&cidr("10.20.2.0/24"), None).unwrap();
+        pool.claim(&cidr("10.20.4.0/24"), Some("removed")).unwrap();
+
+        let mut other = new_test_pool();
+        other.claim(&cidr("10.20.0.0/24"), Some("kept")).unwrap();
+        other
+            .claim(&cidr("10.20.1.0/24"), Some("new-name"))
+            .unwrap();
+        other.claim(&cidr("10.20.2.0/24"), Some("named")).unwrap();
+        other.claim(&cidr("10.20.3.0/24"), Some("added")).unwrap();
+        other.claim(&cidr("10.20.5.0/25"), None).unwrap();
+
+        let diff = pool.diff(&other);
+        assert_eq!(
+            diff.added
+                .iter()
+                .map(|record| (record.cidr, record.name.as_deref()))
+                .collect_vec(),
+            vec![
+                (cidr("10.20.3.0/24"), Some("added")),
+                (cidr("10.20.5.0/25"), None)
+            ]
+        );
+        assert_eq!(
+            diff.removed
+                .iter()
+                .map(|record| (record.cidr, record.name.as_deref()))
+                .collect_vec(),
+            vec![(cidr("10.20.4.0/24"), Some("removed"))]
+        );
+        assert_eq!(
+            diff.renamed,
+            vec![
+                (
+                    cidr("10.20.1.0/24"),
+                    Some("old-name".to_string()),
+                    Some("new-name".to_string())
+                ),
+                (cidr("10.20.2.0/24"), None, Some("named".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn resized() {
+        let mut pool = new_test_pool();
+        pool.claim(&cidr("10.20.0.0/24"), None).unwrap();
+        let mut other = new_test_pool();
+        other.claim(&cidr("10.20.0.0/25"), None).unwrap();
+        let diff = pool.diff(&other);
+        assert_eq!(diff.removed[0].cidr, cidr("10.20.0.0/24"));
+        assert_eq!(diff.added[0].cidr, cidr("10.20.0.0/25"));
+        assert_eq!(diff.renamed, vec![]);
+    }
+}
+
+mod validate {
+    use super::*;
+    use crate::errors::ValidationError;
+
+    // Pools are loaded through `claim_record`, which refuses overlapping and
+    // doubly named subnets, so inconsistencies are introduced after loading.
+    fn new_validate_pool() -> SubnetPool {
+        let mut pool = new_test_pool();
+        pool.allocate(8, Some("a-name")).unwrap();
+        pool.allocate(8, None).unwrap();
+        pool
+    }
+
+    #[test]
+    fn valid() {
+        assert_eq!(new_validate_pool().validate(), Ok(()));
+        assert_eq!(new_test_pool().validate(), Ok(()));
+    }
+
+    #[test]
+    fn name_desync() {
+        let mut pool = new_validate_pool();
+        let a = IpCidr::from_str("10.20.0.0/24").unwrap();
+        let b = IpCidr::from_str("10.20.1.0/24").unwrap();
+        pool.names.remove("a-name");
+        pool.names.insert("b-name".to_string(), b);
+        assert_eq!(
+            pool.validate(),
+            Err(vec![
+                ValidationError::UnindexedName {
+                    name: "a-name".to_string(),
+                    cidr: a
+                },
+                ValidationError::StaleName {
+                    name: "b-name".to_string(),
+                    cidr: b
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn name_of_free_subnet() {
+        let mut pool = new_validate_pool();
+        let free = IpCidr::from_str("10.20.2.0/24").unwrap();
+        pool.names.insert("c-name".to_string(), free);
+        assert_eq!(
+            pool.validate(),
+            Err(vec![ValidationError::StaleName {
+                name: "c-name".to_string(),
+                cidr: free
+            }])
+        );
+    }
+
+    #[test]
+    fn count_mismatch() {
+        let mut pool = new_validate_pool();
+        pool.root.allocated_count = 3;
+        assert_eq!(
+            pool.validate(),
+            Err(vec![ValidationError::CountMismatch {
+                counted: 3,
+                found: 2
+            }])
+        );
+    }
+
+    #[test]
+    fn out_of_root() {
+        let mut pool = new_validate_pool();
+        let b = IpCidr::from_str("10.20.1.0/24").unwrap();
+        let outside = IpCidr::from_str("10.21.0.0/24").unwrap();
+        pool.root.find_record_mut(&b).unwrap().record.cidr = outside;
+        assert_eq!(
+            pool.validate(),
+            Err(vec![ValidationError::OutOfRoot(outside)])
+        );
+    }
+
+    #[test]
+    fn overlap() {
+        let mut pool = new_validate_pool();
+        let a = IpCidr::from_str("10.20.0.0/24").unwrap();
+        let b = IpCidr::from_str("10.20.1.0/24").unwrap();
+        let overlapping = IpCidr::from_str("10.20.0.0/23").unwrap();
+        pool.root.find_record_mut(&a).unwrap().record.cidr = overlapping;
+        pool.names.insert("a-name".to_string(), overlapping);
+        assert_eq!(
+            pool.validate(),
+            Err(vec![
+                ValidationError::Overlap(overlapping, b),
+                ValidationError::StaleName {
+                    name: "a-name".to_string(),
+                    cidr: overlapping
+                },
+            ])
+        );
+    }
+}
+
+mod cidr_for_name {
+    use super::*;
+
+    #[test]
+    fn matches_find_by_name() {
+        let mut pool = new_test_pool();
+        pool.allocate(4, Some("a-name")).unwrap();
+        pool.allocate(4, None).unwrap();
+        for name in ["a-name", "b-name", ""] {
+            assert_eq!(pool.cidr_for_name(name), pool.find_by_name(name));
+            assert_eq!(pool.contains_name_conflict(name), pool.find_by_name(name));
+        }
+        assert_eq!(
+            pool.contains_name_conflict("a-name"),
+            Some(IpCidr::from_str("10.20.0.0/28").unwrap())
+        );
+    }
+
+    #[test]
+    fn conflict_predicts_duplicate_name() {
+        let mut pool = new_test_pool();
+        let cidr = pool.allocate(4, Some("a-name")).unwrap();
+        assert_eq!(pool.contains_name_conflict("a-name"), Some(cidr));
+        assert_eq!(
+            pool.allocate(4, Some("a-name")),
+            Err(AllocateError::DuplicateName)
+        );
+        pool.rename(&cidr, Some("b-name")).unwrap();
+        assert_eq!(pool.contains_name_conflict("a-name"), None);
+        assert!(pool.allocate(4, Some("a-name")).is_ok());
+    }
+}
+
+mod find_record_by_name {
+    use super::*;
+
+    #[test]
+    fn found() {
+        let mut pool = new_test_pool();
+        pool.allocate(4, None).unwrap();
+        let cidr = IpCidr::from_str("10.20.1.0/24").unwrap();
+        pool.claim_full(&cidr, Some("a-name"), Some("Lab network"), &["lab", "west"])
+            .unwrap();
+        let record = pool.find_record_by_name("a-name").unwrap();
+        assert_eq!(record.cidr, cidr);
+        assert_eq!(record.name.as_deref(), Some("a-name"));
+        assert_eq!(record.description.as_deref(), Some("Lab network"));
+        assert_eq!(record.tags, vec!["lab", "west"]);
+        assert!(record.created_at.is_some());
+    }
+
+    #[test]
+    fn not_found() {
+        let mut pool = new_test_pool();
+        let cidr = pool.allocate(4, Some("a-name")).unwrap();
+        assert_eq!(pool.find_record_by_name("b-name"), None);
+        pool.free(&cidr).unwrap();
+        assert_eq!(pool.find_record_by_name("a-name"), None);
+    }
+}
+
+mod names {
+    use super::*;
+    #[test]
+    fn success() {
+        let mut pool = new_test_pool();
+        pool.allocate(4, Some("a-name")).unwrap();
+        pool.allocate(4, Some("b-name")).unwrap();
+        pool.allocate(4, None).unwrap();
+        let mut names: Vec<String> = pool.names().collect();
+        names.sort();
+        assert_eq!(names.len(), 2);
+        assert_eq!(names[0], "a-name");
+        assert_eq!(names[1], "b-name");
+    }
+}
+
+mod names_with_cidrs {
+    use super::*;
+
+    #[test]
+    fn empty() {
+        let pool = new_test_pool();
+        assert_eq!(pool.names_with_cidrs(), vec![]);
+    }
+
+    #[test]
+    fn sorted() {
+        let mut pool = new_test_pool();
+        let c_cidr = pool.allocate(4, Some("c-name")).unwrap();
+        let a_cidr = pool.allocate(4, Some("a-name")).unwrap();
+        pool.allocate(4, None).unwrap();
+        let b_cidr = pool.allocate(4, Some("b-name")).unwrap();
+        assert_eq!(
+            pool.names_with_cidrs(),
+            vec![
+                ("a-name".to_string(), a_cidr),
+                ("b-name".to_string(), b_cidr),
+                ("c-name".to_string(), c_cidr),
+            ]
+        );
+    }
+}
+
+mod names_starting_with {
+    use super::*;
+
+    fn new_naming_pool() -> SubnetPool {
+        let mut pool = new_test_pool();
+        pool.allocate(4, Some("prod-web")).unwrap();
+        pool.allocate(4, Some("prod-db")).unwrap();
+        pool.allocate(4, Some("production")).unwrap();
+        pool.allocate(4, Some("dev-web")).unwrap();
+        pool.allocate(4, Some("web-prod-")).unwrap();
+        pool.allocate(4, None).unwrap();
+        pool
+    }
+
+    #[test]
+    fn count() {
+        let pool = new_naming_pool();
+        assert_eq!(pool.names_starting_with("prod-"), 2);
+        assert_eq!(pool.names_starting_with("prod"), 3);
+        assert_eq!(pool.names_starting_with("test-"), 0);
+        assert_eq!(pool.names_starting_with(""), 5);
+    }
+
+    #[test]
+    fn matching() {
+        let pool = new_naming_pool();
+        let mut names: Vec<&str> = pool.names_matching_prefix("prod-").collect();
+        names.sort();
+        assert_eq!(names, vec!["prod-db", "prod-web"]);
+    }
+
+    #[test]
+    fn case_sensitive() {
+        let pool = new_naming_pool();
+        assert_eq!(pool.names_starting_with("PROD-"), 0);
+    }
+}
+
+mod cidrs {
+    use super::*;
+    #[test]
+    fn no_cidrs() {
+        let pool = new_test_pool();
+        let cidrs = pool.cidrs();
+        assert_eq!(cidrs.count(), 0);
+    }
+
+    #[test]
+    fn some() {
+        let mut pool = new_test_pool();
+        for bits in [4, 5, 5, 4, 4, 4].iter() {
+            pool.allocate(*bits, None).unwrap();
+        }
+        let mut cidrs = pool.cidrs();
+        assert_eq!(
+            &IpCidr::V4(Ipv4Cidr::new(Ipv4Addr::new(10, 20, 0, 0), 28).unwrap()),
+            cidrs.next().unwrap(),
+        );
+        assert_eq!(
+            &IpCidr::V4(Ipv4Cidr::new(Ipv4Addr::new(10, 20, 0, 16), 28).unwrap()),
+            cidrs.next().unwrap(),
+        );
+        assert_eq!(
+            &IpCidr::V4(Ipv4Cidr::new(Ipv4Addr::new(10, 20, 0, 32), 27).unwrap()),
+            cidrs.next().unwrap(),
+        );
+        assert_eq!(
+            &IpCidr::V4(Ipv4Cidr::new(Ipv4Addr::new(10, 20, 0, 64), 27).unwrap()),
+            cidrs.next().unwrap(),
+        );
+        assert_eq!(
+            &IpCidr::V4(Ipv4Cidr::new(Ipv4Addr::new(10, 20, 0, 96), 28).unwrap()),
+            cidrs.next().unwrap(),
+        );
+        assert_eq!(
+            &IpCidr::V4(Ipv4Cidr::new(Ipv4Addr::new(10, 20, 0, 112), 28).unwrap()),
+            cidrs.next().unwrap(),
+        );
+        assert_eq!(cidrs.count(), 0);
+    }
+
+    #[test]
+    fn ascending() {
+        let mut pool = new_test_pool();
+        for cidr in [
+            "10.20.128.0/24",
+            "10.20.0.0/24",
+            "10.20.64.0/18",
+            "10.20.1.0/28",
+        ] {
+            pool.claim(&IpCidr::from_str(cidr).unwrap(), None).unwrap();
+        }
+        assert_eq!(
+            pool.cidrs().map(|cidr| cidr.to_string()).collect_vec(),
+            vec![
+                "10.20.0.0/24",
+                "10.20.1.0/28",
+                "10.20.64.0/18",
+                "10.20.128.0/24"
+            ]
+        );
+        assert!(pool
+            .records()
+            .map(|record| record.cidr)
+            .eq(pool.cidrs().copied()));
+    }
+}
+
+mod pretty_tree_string {
+    use super::*;
+
+    #[test]
+    fn empty() {
+        let pool = new_test_pool();
+        assert_eq!(pool.pretty_tree_string(), "10.20.0.0/16 (free)\n");
+    }
+
+    #[test]
+    fn allocated() {
+        let mut pool = SubnetPool::new(IpCidr::from_str("10.20.0.0/24").unwrap());
+        pool.allocate(6, Some("a-name")).unwrap();
+        pool.allocate(5, None).unwrap();
+        assert_eq!(
+            pool.pretty_tree_string(),
+            "10.20.0.0/24\n\
+             ├── 10.20.0.0/25\n\
+             │   ├── 10.20.0.0/26 a-name\n\
+             │   └── 10.20.0.64/26\n\
+             │       ├── 10.20.0.64/27 -\n\
+             │       └── 10.20.0.96/27 (free)\n\
+             └── 10.20.0.128/25 (free)\n"
+        );
+    }
+
+    #[test]
+    fn fully_allocated() {
+        let mut pool = SubnetPool::new(IpCidr::from_str("1:2:3:4::/127").unwrap());
+        pool.allocate(0, Some("low")).unwrap();
+        pool.allocate(0, Some("high")).unwrap();
+        assert_eq!(
+            pool.pretty_tree_string(),
+            "1:2:3:4::/127\n\
+             ├── 1:2:3:4:: low\n\
+             └── 1:2:3:4::1 high\n"
+        );
+    }
+}
+
+mod records {
+    use super::*;
+    use crate::CidrRecord;
+
+    #[test]
+    fn success() {
+        let mut pool = new_test_pool();
+        pool.allocate_at_time(4, Some("a-name"), TEST_TIME).unwrap();
+        pool.allocate_at_time(4, Some("b-name"), TEST_TIME).unwrap();
+        pool.allocate_at_time(4, None, TEST_TIME).unwrap();
+        let mut entries: Vec<&CidrRecord> = pool.records().collect();
+        entries.sort();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(
+            entries[0],
+            &test_record(
+                IpCidr::V4(Ipv4Cidr::new(Ipv4Addr::new(10, 20, 0, 0), 28).unwrap()),
+                Some("a-name")
+            )
+        );
+        assert_eq!(
+            entries[1],
+            &test_record(
+                IpCidr::V4(Ipv4Cidr::new(Ipv4Addr::new(10, 20, 0, 16), 28).unwrap()),
+                Some("b-name")
+            )
+        );
+        assert_eq!(
+            entries[2],
+            &test_record(
+                IpCidr::V4(Ipv4Cidr::new(Ipv4Addr::new(10, 20, 0, 32), 28).unwrap()),
+                None
+            )
+        );
+    }
+}
+
+mod allocate_flexible {
+    use super::*;
+
     #[test]
-    fn already_not_set() {
+    fn preferred_fits() {
         let mut pool = new_test_pool();
-        let cidr = IpCidr::V4(Ipv4Cidr::new(Ipv4Addr::new(10, 20, 0, 0), 28).unwrap());
-        pool.claim(&cidr, None).unwrap();
-        let result = pool.rename(&cidr, None);
-        assert_eq!(result, Ok(()));
+        assert_eq!(
+            pool.allocate_flexible(8, 6, Some("a-name")),
+            Ok(IpCidr::from_str("10.20.0.0/24").unwrap())
+        );
+        assert_eq!(
+            pool.find_by_name("a-name"),
+            Some(IpCidr::from_str("10.20.0.0/24").unwrap())
+        );
     }
+
     #[test]
-    fn same_name() {
+    fn falls_back() {
         let mut pool = new_test_pool();
-        let cidr = IpCidr::V4(Ipv4Cidr::new(Ipv4Addr::new(10, 20, 0, 0), 28).unwrap());
-        pool.claim(&cidr, Some("same-name")).unwrap();
-        let result = pool.rename(&cidr, Some("same-name"));
-        assert_eq!(result, Ok(()));
-        assert_eq!(pool.find_by_name("same-name").unwrap(), cidr);
+        pool.allocate(15, None).unwrap();
+        pool.allocate(14, None).unwrap();
+        pool.allocate(13, None).unwrap();
+        pool.allocate(12, None).unwrap();
+        pool.allocate(11, None).unwrap();
+        pool.allocate(10, None).unwrap();
+        pool.allocate(9, None).unwrap();
+        pool.allocate(8, None).unwrap();
+        pool.allocate(7, None).unwrap();
+        assert_eq!(pool.max_available_bits(), 7);
+        assert_eq!(
+            pool.allocate_flexible(8, 6, Some("a-name")),
+            Ok(IpCidr::from_str("10.20.255.128/25").unwrap())
+        );
     }
+
     #[test]
-    fn already_exists() {
+    fn no_space_available() {
         let mut pool = new_test_pool();
-        let cidr = IpCidr::V4(Ipv4Cidr::new(Ipv4Addr::new(10, 20, 0, 16), 28).unwrap());
-        let existing_cidr = pool.allocate(4, Some("already-exists")).unwrap();
-        pool.claim(&cidr, Some("old-name")).unwrap();
-        let result = pool.rename(&cidr, Some("already-exists"));
-        assert_eq!(result, Err(RenameError::DuplicateName));
-        assert_eq!(pool.find_by_name("already-exists").unwrap(), existing_cidr);
-        assert_eq!(pool.find_by_name("old-name").unwrap(), cidr);
+        pool.allocate(15, None).unwrap();
+        pool.allocate(14, None).unwrap();
+        pool.allocate(13, None).unwrap();
+        pool.allocate(12, None).unwrap();
+        pool.allocate(11, None).unwrap();
+        pool.allocate(10, None).unwrap();
+        pool.allocate(9, None).unwrap();
+        pool.allocate(8, None).unwrap();
+        assert_eq!(
+            pool.allocate_flexible(10, 9, None),
+            Err(AllocateError::NoSpaceAvailable)
+        );
+        assert_eq!(pool.allocated_count(), 8);
     }
 
     #[test]
-    fn success() {
+    fn duplicate_name() {
         let mut pool = new_test_pool();
-        let cidr = pool.allocate(4, Some("old-name")).unwrap();
-        let result = pool.rename(&cidr, Some("new-name"));
-        assert_eq!(result, Ok(()));
-        assert_eq!(pool.find_by_name("new-name").unwrap(), cidr);
-        assert_eq!(pool.find_by_name("old-name"), None);
+        pool.allocate(4, Some("a-name")).unwrap();
+        assert_eq!(
+            pool.allocate_flexible(8, 6, Some("a-name")),
+            Err(AllocateError::DuplicateName)
+        );
+        assert_eq!(pool.allocated_count(), 1);
+        assert_eq!(pool.validate(), Ok(()));
     }
-}
 
-mod names {
-    use super::*;
     #[test]
-    fn success() {
+    fn min_larger_than_preferred() {
         let mut pool = new_test_pool();
-        pool.allocate(4, Some("a-name")).unwrap();
-        pool.allocate(4, Some("b-name")).unwrap();
-        pool.allocate(4, None).unwrap();
-        let mut names: Vec<String> = pool.names().collect();
-        names.sort();
-        assert_eq!(names.len(), 2);
-        assert_eq!(names[0], "a-name");
-        assert_eq!(names[1], "b-name");
+        assert_eq!(
+            pool.allocate_flexible(6, 8, None),
+            Err(AllocateError::NoSpaceAvailable)
+        );
+        assert_eq!(pool.allocated_count(), 0);
     }
 }
 
-mod cidrs {
+mod plan_vlsm {
     use super::*;
+
+    fn new_vlsm_pool() -> SubnetPool {
+        SubnetPool::new(IpCidr::from_str("10.20.0.0/22").unwrap())
+    }
+
+    fn requirements(hosts: &[u128]) -> Vec<(u128, Option<String>)> {
+        hosts
+            .iter()
+            .map(|hosts| (*hosts, Some(format!("hosts-{hosts}"))))
+            .collect()
+    }
+
+    fn cidrs(cidrs: &[&str]) -> Vec<IpCidr> {
+        cidrs
+            .iter()
+            .map(|cidr| IpCidr::from_str(cidr).unwrap())
+            .collect()
+    }
+
     #[test]
-    fn no_cidrs() {
-        let pool = new_test_pool();
-        let cidrs = pool.cidrs();
-        assert_eq!(cidrs.count(), 0);
+    fn largest_first() {
+        let mut pool = new_vlsm_pool();
+        assert_eq!(
+            pool.plan_vlsm(&requirements(&[500, 250, 100, 50])),
+            Ok(cidrs(&[
+                "10.20.0.0/23",
+                "10.20.2.0/24",
+                "10.20.3.0/25",
+                "10.20.3.128/26"
+            ]))
+        );
+        assert_eq!(pool.allocated_count(), 4);
+        assert_eq!(
+            pool.find_by_name("hosts-250"),
+            Some(IpCidr::from_str("10.20.2.0/24").unwrap())
+        );
+        assert_eq!(pool.free_addresses(), 64);
     }
 
     #[test]
-    fn some() {
-        let mut pool = new_test_pool();
-        for bits in [4, 5, 5, 4, 4, 4].iter() {
-            pool.allocate(*bits, None).unwrap();
+    fn requirement_order() {
+        let mut pool = new_vlsm_pool();
+        assert_eq!(
+            pool.plan_vlsm(&requirements(&[50, 100, 250, 500])),
+            Ok(cidrs(&[
+                "10.20.3.128/26",
+                "10.20.3.0/25",
+                "10.20.2.0/24",
+                "10.20.0.0/23"
+            ]))
+        );
+    }
+
+    #[test]
+    fn sizes_fit_hosts() {
+        let mut pool = new_vlsm_pool();
+        let hosts = [254, 255, 2, 1];
+        let planned = pool.plan_vlsm(&requirements(&hosts)).unwrap();
+        for (hosts, cidr) in hosts.iter().zip(&planned) {
+            assert!(util::usable_hosts(cidr) >= *hosts);
         }
-        let mut cidrs = pool.cidrs();
         assert_eq!(
-            &IpCidr::V4(Ipv4Cidr::new(Ipv4Addr::new(10, 20, 0, 0), 28).unwrap()),
-            cidrs.next().unwrap(),
+            planned
+                .iter()
+                .map(|cidr| cidr.network_length())
+                .collect::<Vec<_>>(),
+            vec![24, 23, 31, 32]
         );
+    }
+
+    #[test]
+    fn rolls_back() {
+        let mut pool = new_vlsm_pool();
+        pool.allocate(4, Some("existing")).unwrap();
         assert_eq!(
-            &IpCidr::V4(Ipv4Cidr::new(Ipv4Addr::new(10, 20, 0, 16), 28).unwrap()),
-            cidrs.next().unwrap(),
+            pool.plan_vlsm(&requirements(&[500, 400, 100])),
+            Err(AllocateError::NoSpaceAvailable)
         );
+        assert_eq!(pool.allocated_count(), 1);
+        assert_eq!(pool.find_by_name("hosts-500"), None);
+    }
+
+    #[test]
+    fn too_many_hosts() {
+        let mut pool = new_vlsm_pool();
         assert_eq!(
-            &IpCidr::V4(Ipv4Cidr::new(Ipv4Addr::new(10, 20, 0, 32), 27).unwrap()),
-            cidrs.next().unwrap(),
+            pool.plan_vlsm(&requirements(&[1 << 32])),
+            Err(AllocateError::NoSpaceAvailable)
         );
+    }
+
+    #[test]
+    fn duplicate_name() {
+        let mut pool = new_vlsm_pool();
+        pool.allocate(4, Some("hosts-50")).unwrap();
         assert_eq!(
-            &IpCidr::V4(Ipv4Cidr::new(Ipv4Addr::new(10, 20, 0, 64), 27).unwrap()),
-            cidrs.next().unwrap(),
+            pool.plan_vlsm(&requirements(&[100, 50])),
+            Err(AllocateError::DuplicateName)
         );
         assert_eq!(
-            &IpCidr::V4(Ipv4Cidr::new(Ipv4Addr::new(10, 20, 0, 96), 28).unwrap()),
-            cidrs.next().unwrap(),
+            pool.plan_vlsm(&[(100, Some("a".to_string())), (50, Some("a".to_string()))]),
+            Err(AllocateError::DuplicateName)
         );
+        assert_eq!(pool.allocated_count(), 1);
+    }
+
+    #[test]
+    fn unnamed() {
+        let mut pool = new_vlsm_pool();
         assert_eq!(
-            &IpCidr::V4(Ipv4Cidr::new(Ipv4Addr::new(10, 20, 0, 112), 28).unwrap()),
-            cidrs.next().unwrap(),
+            pool.plan_vlsm(&[(100, None), (100, None)]),
+            Ok(cidrs(&["10.20.0.0/25", "10.20.0.128/25"]))
         );
-        assert_eq!(cidrs.count(), 0);
+        assert_eq!(pool.named_count(), 0);
     }
 }
 
-mod records {
+mod records_mut {
     use super::*;
-    use crate::CidrRecord;
 
     #[test]
-    fn success() {
+    fn set_tag() {
         let mut pool = new_test_pool();
         pool.allocate(4, Some("a-name")).unwrap();
+        pool.allocate(8, None).unwrap();
         pool.allocate(4, Some("b-name")).unwrap();
+        for mut record in pool.records_mut() {
+            record.tags_mut().push("lab".to_string());
+        }
+        assert_eq!(pool.records().count(), 3);
+        assert!(pool.records().all(|record| record.tags == ["lab"]));
+    }
+
+    #[test]
+    fn set_description() {
+        let mut pool = new_test_pool();
+        pool.allocate(4, Some("a-name")).unwrap();
         pool.allocate(4, None).unwrap();
-        let mut entries: Vec<&CidrRecord> = pool.records().collect();
-        entries.sort();
-        assert_eq!(entries.len(), 3);
-        assert_eq!(
-            entries[0],
-            &CidrRecord::new(
-                IpCidr::V4(Ipv4Cidr::new(Ipv4Addr::new(10, 20, 0, 0), 28).unwrap()),
-                Some("a-name")
-            )
-        );
+        for mut record in pool.records_mut() {
+            if record.name().is_none() {
+                record.set_description(Some("Unnamed"));
+            }
+        }
         assert_eq!(
-            entries[1],
-            &CidrRecord::new(
-                IpCidr::V4(Ipv4Cidr::new(Ipv4Addr::new(10, 20, 0, 16), 28).unwrap()),
-                Some("b-name")
-            )
+            pool.find_record_by_name("a-name").unwrap().description,
+            None
         );
+        let cidr = IpCidr::from_str("10.20.0.16/28").unwrap();
         assert_eq!(
-            entries[2],
-            &CidrRecord::new(
-                IpCidr::V4(Ipv4Cidr::new(Ipv4Addr::new(10, 20, 0, 32), 28).unwrap()),
-                None
-            )
+            pool.records_within(&cidr)
+                .next()
+                .unwrap()
+                .description
+                .as_deref(),
+            Some("Unnamed")
         );
     }
+
+    #[test]
+    fn in_order() {
+        let mut pool = new_test_pool();
+        pool.allocate(4, None).unwrap();
+        pool.allocate(8, None).unwrap();
+        pool.allocate(4, None).unwrap();
+        let cidrs: Vec<IpCidr> = pool.records_mut().map(|record| *record.cidr()).collect();
+        assert_eq!(cidrs, pool.cidrs().copied().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn empty() {
+        let mut pool = new_test_pool();
+        assert_eq!(pool.records_mut().count(), 0);
+    }
+}
+
+mod scale {
+    use super::*;
+
+    #[test]
+    fn allocate_and_list_many() {
+        let mut pool = new_test_pool();
+        for _ in 0..50_000 {
+            pool.allocate_at_time(0, None, TEST_TIME).unwrap();
+        }
+        assert_eq!(pool.allocated_count(), 50_000);
+        assert_eq!(pool.records().count(), 50_000);
+        assert_eq!(pool.capacity_for(0), 65_536 - 50_000);
+        let last = pool.records().last().unwrap();
+        assert_eq!(last.cidr, IpCidr::from_str("10.20.195.79/32").unwrap());
+    }
 }
 
 mod records_within {
@@ -440,9 +3503,9 @@ mod records_within {
     #[test]
     fn no_overlap() {
         let mut pool = new_test_pool();
-        pool.allocate(4, Some("a-name")).unwrap();
-        pool.allocate(4, Some("b-name")).unwrap();
-        pool.allocate(4, None).unwrap();
+        pool.allocate_at_time(4, Some("a-name"), TEST_TIME).unwrap();
+        pool.allocate_at_time(4, Some("b-name"), TEST_TIME).unwrap();
+        pool.allocate_at_time(4, None, TEST_TIME).unwrap();
         let entries: Vec<&CidrRecord> = pool
             .records_within(&IpCidr::from_str("20.20.0.0/16").unwrap())
             .collect();
@@ -452,9 +3515,9 @@ mod records_within {
     #[test]
     fn superset() {
         let mut pool = new_test_pool();
-        pool.allocate(4, Some("a-name")).unwrap();
-        pool.allocate(4, Some("b-name")).unwrap();
-        pool.allocate(4, None).unwrap();
+        pool.allocate_at_time(4, Some("a-name"), TEST_TIME).unwrap();
+        pool.allocate_at_time(4, Some("b-name"), TEST_TIME).unwrap();
+        pool.allocate_at_time(4, None, TEST_TIME).unwrap();
         let mut entries: Vec<&CidrRecord> = pool
             .records_within(&IpCidr::from_str("10.0.0.0/8").unwrap())
             .collect();
@@ -462,21 +3525,21 @@ mod records_within {
         assert_eq!(entries.len(), 3);
         assert_eq!(
             entries[0],
-            &CidrRecord::new(
+            &test_record(
                 IpCidr::V4(Ipv4Cidr::new(Ipv4Addr::new(10, 20, 0, 0), 28).unwrap()),
                 Some("a-name")
             )
         );
         assert_eq!(
             entries[1],
-            &CidrRecord::new(
+            &test_record(
                 IpCidr::V4(Ipv4Cidr::new(Ipv4Addr::new(10, 20, 0, 16), 28).unwrap()),
                 Some("b-name")
             )
         );
         assert_eq!(
             entries[2],
-            &CidrRecord::new(
+            &test_record(
                 IpCidr::V4(Ipv4Cidr::new(Ipv4Addr::new(10, 20, 0, 32), 28).unwrap()),
                 None
             )
@@ -486,9 +3549,9 @@ mod records_within {
     #[test]
     fn subset() {
         let mut pool = new_test_pool();
-        pool.allocate(4, Some("a-name")).unwrap();
-        pool.allocate(4, Some("b-name")).unwrap();
-        pool.allocate(4, None).unwrap();
+        pool.allocate_at_time(4, Some("a-name"), TEST_TIME).unwrap();
+        pool.allocate_at_time(4, Some("b-name"), TEST_TIME).unwrap();
+        pool.allocate_at_time(4, None, TEST_TIME).unwrap();
         let mut entries: Vec<&CidrRecord> = pool
             .records_within(&IpCidr::from_str("10.20.0.0/27").unwrap())
             .collect();
@@ -496,14 +3559,14 @@ mod records_within {
         assert_eq!(entries.len(), 2);
         assert_eq!(
             entries[0],
-            &CidrRecord::new(
+            &test_record(
                 IpCidr::V4(Ipv4Cidr::new(Ipv4Addr::new(10, 20, 0, 0), 28).unwrap()),
                 Some("a-name")
             )
         );
         assert_eq!(
             entries[1],
-            &CidrRecord::new(
+            &test_record(
                 IpCidr::V4(Ipv4Cidr::new(Ipv4Addr::new(10, 20, 0, 16), 28).unwrap()),
                 Some("b-name")
             )
@@ -513,7 +3576,7 @@ mod records_within {
 
 mod serialize {
     use super::*;
-    use serde_test::{assert_de_tokens_error, assert_tokens};
+    use serde_test::{assert_de_tokens, assert_de_tokens_error, assert_tokens, Configure};
 
     #[test]
     fn parse_bad_network() {
@@ -531,6 +3594,48 @@ mod serialize {
         );
     }
 
+    #[test]
+    fn duplicate_cidr() {
+        assert_de_tokens_error::<SubnetPool>(
+            &[
+                serde_test::Token::Struct {
+                    name: "SubnetPool",
+                    len: 2,
+                },
+                serde_test::Token::Str("cidr"),
+                serde_test::Token::Str("10.20.0.0/16"),
+                serde_test::Token::Str("subnets"),
+                serde_test::Token::Seq { len: Some(3) },
+                serde_test::Token::Struct {
+                    name: "CidrRecord",
+                    len: 1,
+                },
+                serde_test::Token::Str("cidr"),
+                serde_test::Token::Str("10.20.0.0/24"),
+                serde_test::Token::StructEnd,
+                serde_test::Token::Struct {
+                    name: "CidrRecord",
+                    len: 1,
+                },
+                serde_test::Token::Str("cidr"),
+                serde_test::Token::Str("10.20.1.0/24"),
+                serde_test::Token::StructEnd,
+                serde_test::Token::Struct {
+                    name: "CidrRecord",
+                    len: 2,
+                },
+                serde_test::Token::Str("cidr"),
+                serde_test::Token::Str("10.20.0.0/24"),
+                serde_test::Token::Str("name"),
+                serde_test::Token::Str("a-name"),
+                serde_test::Token::StructEnd,
+                serde_test::Token::SeqEnd,
+                serde_test::Token::StructEnd,
+            ],
+            "Duplicate CIDR: 10.20.0.0/24",
+        );
+    }
+
     #[test]
     fn allocation_error() {
         assert_de_tokens_error::<SubnetPool>(
@@ -578,16 +3683,16 @@ mod serialize {
     #[test]
     fn success() {
         let mut pool = SubnetPool::new(TEST_CIDR4);
-        pool.allocate(4, Some("a-name")).unwrap();
-        pool.allocate(4, Some("b-name")).unwrap();
-        pool.allocate(4, None).unwrap();
+        pool.allocate_at_time(4, Some("a-name"), TEST_TIME).unwrap();
+        pool.allocate_at_time(4, Some("b-name"), TEST_TIME).unwrap();
+        pool.allocate_at_time(4, None, TEST_TIME).unwrap();
 
         assert_tokens(
-            &pool,
+            &pool.readable(),
             &[
                 serde_test::Token::Struct {
                     name: "SubnetPool",
-                    len: 2,
+                    len: 4,
                 },
                 serde_test::Token::Str("cidr"),
                 serde_test::Token::Str("10.20.0.0/16"),
@@ -595,32 +3700,162 @@ mod serialize {
                 serde_test::Token::Seq { len: Some(3) },
                 serde_test::Token::Struct {
                     name: "CidrRecord",
-                    len: 2,
+                    len: 6,
                 },
                 serde_test::Token::Str("cidr"),
                 serde_test::Token::Str("10.20.0.0/28"),
                 serde_test::Token::Str("name"),
                 serde_test::Token::Str("a-name"),
+                serde_test::Token::Str("created_at"),
+                serde_test::Token::Str(TEST_TIME),
                 serde_test::Token::StructEnd,
                 serde_test::Token::Struct {
                     name: "CidrRecord",
-                    len: 2,
+                    len: 6,
                 },
                 serde_test::Token::Str("cidr"),
                 serde_test::Token::Str("10.20.0.16/28"),
                 serde_test::Token::Str("name"),
                 serde_test::Token::Str("b-name"),
+                serde_test::Token::Str("created_at"),
+                serde_test::Token::Str(TEST_TIME),
                 serde_test::Token::StructEnd,
                 serde_test::Token::Struct {
                     name: "CidrRecord",
-                    len: 2,
+                    len: 6,
                 },
                 serde_test::Token::Str("cidr"),
                 serde_test::Token::Str("10.20.0.32/28"),
+                serde_test::Token::Str("created_at"),
+                serde_test::Token::Str(TEST_TIME),
+                serde_test::Token::StructEnd,
+                serde_test::Token::SeqEnd,
                 serde_test::Token::StructEnd,
+            ],
+        );
+    }
+
+    #[test]
+    fn pool_metadata() {
+        let mut pool = SubnetPool::new(TEST_CIDR4);
+        pool.set_description(Some("Lab networks"));
+        pool.set_metadata("region", "eu-west");
+        pool.set_metadata("owner", "alice");
+
+        assert_tokens(
+            &pool.readable(),
+            &[
+                serde_test::Token::Struct {
+                    name: "SubnetPool",
+                    len: 4,
+                },
+                serde_test::Token::Str("cidr"),
+                serde_test::Token::Str("10.20.0.0/16"),
+                serde_test::Token::Str("description"),
+                serde_test::Token::Str("Lab networks"),
+                serde_test::Token::Str("metadata"),
+                serde_test::Token::Map { len: Some(2) },
+                serde_test::Token::Str("owner"),
+                serde_test::Token::Str("alice"),
+                serde_test::Token::Str("region"),
+                serde_test::Token::Str("eu-west"),
+                serde_test::Token::MapEnd,
+                serde_test::Token::Str("subnets"),
+                serde_test::Token::Seq { len: Some(0) },
                 serde_test::Token::SeqEnd,
                 serde_test::Token::StructEnd,
             ],
         );
     }
+
+    #[test]
+    fn postcard_round_trip() {
+        let mut pool = new_test_pool();
+        pool.set_description(Some("Lab networks"));
+        pool.set_metadata("owner", "alice");
+        pool.allocate_at_time(8, Some("a-name"), TEST_TIME).unwrap();
+        pool.allocate(4, None).unwrap();
+        pool.claim_full(
+            &IpCidr::from_str("10.20.128.0/24").unwrap(),
+            None,
+            Some("Reserved"),
+            &["excluded"],
+        )
+        .unwrap();
+        let bytes = postcard::to_extend(&pool, Vec::new()).unwrap();
+        assert_eq!(postcard::from_bytes::<SubnetPool>(&bytes).unwrap(), pool);
+    }
+
+    #[test]
+    fn sequence_without_pool_metadata() {
+        assert_de_tokens(
+            &SubnetPool::new(TEST_CIDR4),
+            &[
+                serde_test::Token::Seq { len: Some(2) },
+                serde_test::Token::BorrowedStr("10.20.0.0/16"),
+                serde_test::Token::Seq { len: Some(0) },
+                serde_test::Token::SeqEnd,
+                serde_test::Token::SeqEnd,
+            ],
+        );
+    }
+}
+
+mod lenient_pool {
+    use super::*;
+    use crate::errors::StorageError;
+    use crate::storage::{self, Format};
+
+    static OVERLAPPING: &str = "cidr: 10.20.0.0/16\n\
+                                subnets:\n\
+                                - {cidr: 10.20.0.0/24, name: a-name}\n\
+                                - {cidr: 10.20.0.0/25, name: b-name}\n\
+                                - {cidr: 10.20.1.0/24}\n\
+                                - {cidr: 10.20.0.0/23}\n\
+                                - {cidr: 10.20.1.0/24}\n\
+                                - {cidr: 10.20.2.0/24, name: c-name}\n";
+
+    #[test]
+    fn strict() {
+        assert_eq!(
+            storage::read::<SubnetPool>(OVERLAPPING.as_bytes(), Format::Yaml),
+            Err(StorageError::Parse(
+                "Duplicate CIDR: 10.20.1.0/24".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn skips_overlapping() {
+        let loaded: LenientPool = storage::read(OVERLAPPING.as_bytes(), Format::Yaml).unwrap();
+        assert_eq!(
+            loaded
+                .pool
+                .cidrs()
+                .map(|cidr| cidr.to_string())
+                .collect_vec(),
+            vec!["10.20.0.0/24", "10.20.1.0/24", "10.20.2.0/24"]
+        );
+        assert_eq!(loaded.pool.find_by_name("b-name"), None);
+        assert_eq!(
+            loaded
+                .skipped
+                .iter()
+                .map(|record| record.cidr.to_string())
+                .collect_vec(),
+            vec!["10.20.0.0/25", "10.20.0.0/23", "10.20.1.0/24"]
+        );
+        assert_eq!(loaded.skipped[0].name.as_deref(), Some("b-name"));
+    }
+
+    #[test]
+    fn consistent_pool() {
+        let mut pool = new_test_pool();
+        pool.allocate(8, Some("a-name")).unwrap();
+        let mut content = Vec::new();
+        storage::write(&mut content, &pool, Format::Json, false).unwrap();
+        let loaded: LenientPool = storage::read(content.as_slice(), Format::Json).unwrap();
+        assert_eq!(loaded.pool, pool);
+        assert!(loaded.skipped.is_empty());
+    }
 }
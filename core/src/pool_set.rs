@@ -0,0 +1,233 @@
+// Copyright 2024 The Milton Hirsch Institute, B.V.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::errors::{CreateError, DeleteError};
+use crate::pool::{LenientPool, SubnetPool};
+use crate::{CreateResult, DeleteResult};
+use std::collections::BTreeMap;
+
+/// Named subnet pools kept together in one file, serialized as
+/// `{"pools": {"<name>": <pool>, ...}}` with the pools in name order.
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct PoolSet {
+    pools: BTreeMap<String, SubnetPool>,
+}
+
+/// The pools of a pool set file read leniently, as `LenientPool` reads a
+/// single pool.
+#[derive(Debug, PartialEq, serde::Deserialize)]
+pub struct LenientPoolSet {
+    pub pools: BTreeMap<String, LenientPool>,
+}
+
+impl PoolSet {
+    pub fn new() -> Self {
+        PoolSet::default()
+    }
+
+    /// Add a pool under a name that is not yet in use.
+    pub fn create(&mut self, name: &str, pool: SubnetPool) -> CreateResult<()> {
+        if self.pools.contains_key(name) {
+            return Err(CreateError::DuplicateObject);
+        }
+        self.pools.insert(name.to_string(), pool);
+        Ok(())
+    }
+
+    /// Put a pool under a name, returning the pool it replaces, if any.
+    pub fn replace(&mut self, name: &str, pool: SubnetPool) -> Option<SubnetPool> {
+        self.pools.insert(name.to_string(), pool)
+    }
+
+    pub fn remove(&mut self, name: &str) -> DeleteResult<SubnetPool> {
+        self.pools.remove(name).ok_or(DeleteError::NoSuchObject)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&SubnetPool> {
+        self.pools.get(name)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut SubnetPool> {
+        self.pools.get_mut(name)
+    }
+
+    /// Names of the pools in order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.pools.keys().map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.pools.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pools.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cidr::IpCidr;
+    use serde_test::{assert_de_tokens_error, assert_tokens, Configure, Token};
+    use std::str::FromStr;
+
+    static TEST_TIME: &str = "2024-01-26T12:00:00Z";
+
+    fn new_test_set() -> PoolSet {
+        let mut set = PoolSet::new();
+        let mut prod = SubnetPool::new(IpCidr::from_str("10.20.0.0/16").unwrap());
+        prod.allocate(8, Some("web")).unwrap();
+        set.create("prod", prod).unwrap();
+        set.create(
+            "dev",
+            SubnetPool::new(IpCidr::from_str("1:2:3:4:a:14::/112").unwrap()),
+        )
+        .unwrap();
+        set
+    }
+
+    #[test]
+    fn create() {
+        let mut set = new_test_set();
+        assert_eq!(set.len(), 2);
+        assert_eq!(
+            set.create(
+                "prod",
+                SubnetPool::new(IpCidr::from_str("10.30.0.0/16").unwrap())
+            ),
+            Err(CreateError::DuplicateObject)
+        );
+        assert_eq!(
+            set.get("prod").unwrap().cidr(),
+            &IpCidr::from_str("10.20.0.0/16").unwrap()
+        );
+    }
+
+    #[test]
+    fn replace() {
+        let mut set = new_test_set();
+        let pool = SubnetPool::new(IpCidr::from_str("10.30.0.0/16").unwrap());
+        let replaced = set.replace("prod", pool.clone()).unwrap();
+        assert!(replaced.find_by_name("web").is_some());
+        assert_eq!(set.get("prod"), Some(&pool));
+        assert_eq!(set.replace("test", pool), None);
+        assert_eq!(set.len(), 3);
+    }
+
+    #[test]
+    fn remove() {
+        let mut set = new_test_set();
+        assert!(set.remove("dev").is_ok());
+        assert_eq!(set.remove("dev"), Err(DeleteError::NoSuchObject));
+        assert_eq!(set.names().collect::<Vec<_>>(), vec!["prod"]);
+    }
+
+    #[test]
+    fn get_mut() {
+        let mut set = new_test_set();
+        set.get_mut("dev").unwrap().allocate(4, Some("db")).unwrap();
+        assert!(set.get("dev").unwrap().find_by_name("db").is_some());
+        assert!(set.get_mut("test").is_none());
+    }
+
+    #[test]
+    fn names() {
+        let set = new_test_set();
+        assert_eq!(set.names().collect::<Vec<_>>(), vec!["dev", "prod"]);
+        assert!(PoolSet::new().is_empty());
+    }
+
+    #[test]
+    fn serialize() {
+        let mut set = PoolSet::new();
+        let mut prod = SubnetPool::new(IpCidr::from_str("10.20.0.0/16").unwrap());
+        prod.allocate_at_time(8, Some("web"), TEST_TIME).unwrap();
+        set.create("prod", prod).unwrap();
+        set.create(
+            "dev",
+            SubnetPool::new(IpCidr::from_str("1:2:3:4:a:14::/112").unwrap()),
+        )
+        .unwrap();
+
+        assert_tokens(
+            &set.readable(),
+            &[
+                Token::Struct {
+                    name: "PoolSet",
+                    len: 1,
+                },
+                Token::Str("pools"),
+                Token::Map { len: Some(2) },
+                Token::Str("dev"),
+                Token::Struct {
+                    name: "SubnetPool",
+                    len: 4,
+                },
+                Token::Str("cidr"),
+                Token::Str("1:2:3:4:a:14::/112"),
+                Token::Str("subnets"),
+                Token::Seq { len: Some(0) },
+                Token::SeqEnd,
+                Token::StructEnd,
+                Token::Str("prod"),
+                Token::Struct {
+                    name: "SubnetPool",
+                    len: 4,
+                },
+                Token::Str("cidr"),
+                Token::Str("10.20.0.0/16"),
+                Token::Str("subnets"),
+                Token::Seq { len: Some(1) },
+                Token::Struct {
+                    name: "CidrRecord",
+                    len: 6,
+                },
+                Token::Str("cidr"),
+                Token::Str("10.20.0.0/24"),
+                Token::Str("name"),
+                Token::Str("web"),
+                Token::Str("created_at"),
+                Token::Str(TEST_TIME),
+                Token::StructEnd,
+                Token::SeqEnd,
+                Token::StructEnd,
+                Token::MapEnd,
+                Token::StructEnd,
+            ],
+        );
+    }
+
+    #[test]
+    fn deserialize_lenient() {
+        let set: LenientPoolSet = serde_json::from_str(
+            r#"{"pools": {"prod": {"cidr": "10.20.0.0/16", "subnets": [
+                {"cidr": "10.20.0.0/24"}, {"cidr": "10.20.0.0/25"}
+            ]}}}"#,
+        )
+        .unwrap();
+        let prod = &set.pools["prod"];
+        assert_eq!(prod.pool.allocated_count(), 1);
+        assert_eq!(
+            prod.skipped
+                .iter()
+                .map(|record| record.cidr.to_string())
+                .collect::<Vec<_>>(),
+            vec!["10.20.0.0/25"]
+        );
+    }
+
+    #[test]
+    fn deserialize_without_pools() {
+        assert_de_tokens_error::<PoolSet>(
+            &[
+                Token::Struct {
+                    name: "PoolSet",
+                    len: 0,
+                },
+                Token::StructEnd,
+            ],
+            "missing field `pools`",
+        );
+    }
+}
@@ -0,0 +1,204 @@
+// Copyright 2024 The Milton Hirsch Institute, B.V.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Reading and writing pools, or anything else serializable, in the text
+//! formats of pool files.
+
+use crate::errors::StorageError;
+use crate::StorageResult;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt;
+use std::io::{Read, Write};
+use std::str::FromStr;
+
+/// Format of a pool file, named as its file extension is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl Format {
+    /// Guess the format of pool file content for files whose extension does
+    /// not give it away. JSON pools start with `{` or `[`; anything else is
+    /// YAML.
+    pub fn sniff(content: &[u8]) -> Format {
+        match content.iter().find(|byte| !byte.is_ascii_whitespace()) {
+            Some(b'{' | b'[') => Format::Json,
+            _ => Format::Yaml,
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = format!("{self:?}");
+        write!(f, "{}", s.to_lowercase())
+    }
+}
+
+impl TryFrom<&str> for Format {
+    type Error = String;
+
+    fn try_from(name: &str) -> Result<Self, Self::Error> {
+        match name {
+            "json" => Ok(Format::Json),
+            "toml" => Ok(Format::Toml),
+            "yaml" | "yml" => Ok(Format::Yaml),
+            _ => Err(format!("Unknown pool format: {name}")),
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Format::try_from(name)
+    }
+}
+
+/// Read a value in `format` from `reader`.
+pub fn read<T: DeserializeOwned>(mut reader: impl Read, format: Format) -> StorageResult<T> {
+    let mut content = Vec::new();
+    reader
+        .read_to_end(&mut content)
+        .map_err(|err| StorageError::Io(err.to_string()))?;
+    let parse_error = |err: &dyn std::error::Error| StorageError::Parse(err.to_string());
+    match format {
+        Format::Json => serde_json::from_slice(&content).map_err(|err| parse_error(&err)),
+        Format::Toml => toml::from_slice(&content).map_err(|err| parse_error(&err)),
+        Format::Yaml => serde_yaml::from_slice(&content).map_err(|err| parse_error(&err)),
+    }
+}
+
+/// Write `value` in `format` to `writer`. JSON is pretty printed unless
+/// `compact` is set, which writes it on a single line; the other formats
+/// are always written the same way.
+pub fn write<T: Serialize>(
+    mut writer: impl Write,
+    value: &T,
+    format: Format,
+    compact: bool,
+) -> StorageResult<()> {
+    let content = match format {
+        Format::Json if compact => serde_json::to_vec(value).map_err(serialize_error)?,
+        Format::Json => serde_json::to_vec_pretty(value).map_err(serialize_error)?,
+        // Turning the value into a TOML table first puts the keys in order
+        // with the plain values before the tables, as TOML requires.
+        Format::Toml => toml::Value::try_from(value)
+            .and_then(|value| toml::to_string(&value))
+            .map_err(serialize_error)?
+            .into_bytes(),
+        Format::Yaml => serde_yaml::to_string(value)
+            .map_err(serialize_error)?
+            .into_bytes(),
+    };
+    writer
+        .write_all(&content)
+        .map_err(|err| StorageError::Io(err.to_string()))
+}
+
+fn serialize_error(err: impl std::error::Error) -> StorageError {
+    StorageError::Serialize(err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pool::SubnetPool;
+    use crate::pool_set::PoolSet;
+    use cidr::IpCidr;
+
+    fn new_test_pool() -> SubnetPool {
+        let mut pool = SubnetPool::new(IpCidr::from_str("10.20.0.0/16").unwrap());
+        pool.set_description(Some("Test pool"));
+        pool.set_metadata("owner", "alice");
+        pool.allocate(8, Some("web")).unwrap();
+        pool.allocate(4, None).unwrap();
+        pool
+    }
+
+    fn round_trip(format: Format, compact: bool) -> Vec<u8> {
+        let pool = new_test_pool();
+        let mut content = Vec::new();
+        write(&mut content, &pool, format, compact).unwrap();
+        assert_eq!(read::<SubnetPool>(content.as_slice(), format), Ok(pool));
+        content
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(Format::try_from("json"), Ok(Format::Json));
+        assert_eq!(Format::try_from("toml"), Ok(Format::Toml));
+        assert_eq!(Format::try_from("yaml"), Ok(Format::Yaml));
+        assert_eq!(Format::try_from("yml"), Ok(Format::Yaml));
+        assert_eq!("yml".parse::<Format>(), Ok(Format::Yaml));
+        assert_eq!(Format::Toml.to_string(), "toml");
+    }
+
+    #[test]
+    fn unknown_name() {
+        assert_eq!(
+            Format::try_from("xml"),
+            Err("Unknown pool format: xml".to_string())
+        );
+        assert_eq!(
+            "JSON".parse::<Format>(),
+            Err("Unknown pool format: JSON".to_string())
+        );
+    }
+
+    #[test]
+    fn sniff() {
+        assert_eq!(
+            Format::sniff(b"  {\"cidr\": \"10.20.0.0/16\"}"),
+            Format::Json
+        );
+        assert_eq!(Format::sniff(b"[]"), Format::Json);
+        assert_eq!(Format::sniff(b"cidr: 10.20.0.0/16"), Format::Yaml);
+        assert_eq!(Format::sniff(b""), Format::Yaml);
+    }
+
+    #[test]
+    fn json() {
+        let content = round_trip(Format::Json, false);
+        assert!(content.starts_with(b"{\n  \"cidr\": \"10.20.0.0/16\""));
+    }
+
+    #[test]
+    fn compact_json() {
+        let content = round_trip(Format::Json, true);
+        assert!(content.starts_with(b"{\"cidr\":\"10.20.0.0/16\""));
+        assert!(!content.contains(&b'\n'));
+    }
+
+    #[test]
+    fn toml() {
+        let content = round_trip(Format::Toml, false);
+        assert!(content.starts_with(b"cidr = \"10.20.0.0/16\"\n"));
+    }
+
+    #[test]
+    fn yaml() {
+        let content = round_trip(Format::Yaml, false);
+        assert!(content.starts_with(b"cidr: 10.20.0.0/16\n"));
+    }
+
+    #[test]
+    fn pool_set() {
+        let mut set = PoolSet::new();
+        set.create("prod", new_test_pool()).unwrap();
+        let mut content = Vec::new();
+        write(&mut content, &set, Format::Yaml, false).unwrap();
+        assert_eq!(read::<PoolSet>(content.as_slice(), Format::Yaml), Ok(set));
+    }
+
+    #[test]
+    fn parse_error() {
+        let result = read::<SubnetPool>(b"{\"cidr\": \"bad\"}".as_slice(), Format::Json);
+        assert!(matches!(result, Err(StorageError::Parse(_))));
+    }
+}
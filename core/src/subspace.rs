@@ -1,21 +1,21 @@
 // Copyright 2023-2024 The Milton Hirsch Institute, B.V.
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::pool::AllocStrategy;
 use crate::util::host_length;
 use crate::Bits;
 use crate::{util, CidrRecord};
 use cidr::IpCidr;
-use cidr_utils::separator;
 use std::cmp;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub(crate) enum State {
     Allocated,
     Free,
     Unavailable,
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
 pub(crate) struct Subspace {
     pub(crate) record: CidrRecord,
     pub(crate) high: Option<Box<Self>>,
@@ -23,6 +23,10 @@ pub(crate) struct Subspace {
     pub(crate) state: State,
     pub(crate) allocated_count: usize,
     pub(crate) max_available_bits: Bits,
+    /// No free space remains anywhere below this subspace. Lets allocation
+    /// skip exhausted branches, which `max_available_bits` alone cannot tell
+    /// apart from branches with a single free address.
+    pub(crate) full: bool,
 }
 
 impl Subspace {
@@ -34,6 +38,7 @@ impl Subspace {
             state: State::Free,
             allocated_count: 0,
             max_available_bits: util::host_length(&cidr),
+            full: false,
         }
     }
 
@@ -42,16 +47,19 @@ impl Subspace {
             State::Allocated => {
                 self.allocated_count = 1;
                 self.max_available_bits = 0;
+                self.full = true;
             }
             State::Free => {
                 self.allocated_count = 0;
                 self.max_available_bits = host_length(&self.record.cidr);
+                self.full = false;
             }
             State::Unavailable => {
                 let low = self.low.as_deref_mut().unwrap();
                 let high = self.high.as_deref_mut().unwrap();
                 self.allocated_count = low.allocated_count + high.allocated_count;
                 self.max_available_bits = cmp::max(low.max_available_bits, high.max_available_bits);
+                self.full = low.full && high.full;
             }
         }
     }
@@ -63,17 +71,8 @@ impl Subspace {
     pub(crate) fn split(&mut self) {
         self.state = State::Unavailable;
         let new_network_length = self.record.cidr.network_length() + 1;
-        let cidrs: Vec<_> = (match self.record.cidr {
-            IpCidr::V4(cidr) => {
-                separator::Ipv4CidrSeparator::sub_networks(&cidr, new_network_length)
-                    .map(|vs| vs.into_iter().map(IpCidr::V4).collect())
-            }
-            IpCidr::V6(cidr) => {
-                separator::Ipv6CidrSeparator::sub_networks(&cidr, new_network_length)
-                    .map(|vs| vs.into_iter().map(IpCidr::V6).collect())
-            }
-        })
-        .expect("Vec of length 2");
+        let cidrs =
+            util::sub_networks(&self.record.cidr, new_network_length).expect("Vec of length 2");
         self.low = Some(Box::new(Subspace::new(cidrs[0])));
         self.high = Some(Box::new(Subspace::new(cidrs[1])));
     }
@@ -82,8 +81,10 @@ impl Subspace {
         &mut self,
         host_length: Bits,
         name: Option<&str>,
+        created_at: &str,
+        strategy: AllocStrategy,
     ) -> Option<IpCidr> {
-        if host_length > self.max_available_bits {
+        if self.full || host_length > self.max_available_bits {
             return None;
         }
         if self.state == State::Free {
@@ -91,30 +92,37 @@ impl Subspace {
                 self.state = State::Allocated;
                 self.update_info();
                 self.record.name = name.map(|s| s.to_string());
+                self.record.created_at = Some(created_at.to_string());
                 return Some(self.record.cidr);
             } else {
                 self.split();
             }
         }
         if self.state == State::Unavailable {
-            let found_low = self
-                .low
-                .as_deref_mut()?
-                .allocate_free_space(host_length, name);
-            return match found_low {
+            let (first, second) = match strategy {
+                AllocStrategy::LowFirst => (&mut self.low, &mut self.high),
+                AllocStrategy::HighFirst => (&mut self.high, &mut self.low),
+            };
+            let found_first =
+                first
+                    .as_deref_mut()?
+                    .allocate_free_space(host_length, name, created_at, strategy);
+            return match found_first {
                 Some(_) => {
                     self.update_info();
-                    found_low
+                    found_first
                 }
                 None => {
-                    let found_high = self
-                        .high
-                        .as_deref_mut()?
-                        .allocate_free_space(host_length, name);
-                    match found_high {
+                    let found_second = second.as_deref_mut()?.allocate_free_space(
+                        host_length,
+                        name,
+                        created_at,
+                        strategy,
+                    );
+                    match found_second {
                         Some(_) => {
                             self.update_info();
-                            found_high
+                            found_second
                         }
                         None => None,
                     }
@@ -123,6 +131,55 @@ impl Subspace {
         }
         None
     }
+
+    /// Allocate free space as `allocate_free_space` does, but only inside
+    /// `parent`.
+    pub(crate) fn allocate_free_space_within(
+        &mut self,
+        parent: &IpCidr,
+        host_length: Bits,
+        name: Option<&str>,
+        created_at: &str,
+    ) -> Option<IpCidr> {
+        if util::cidr_contains(parent, &self.record.cidr) {
+            return self.allocate_free_space(
+                host_length,
+                name,
+                created_at,
+                AllocStrategy::LowFirst,
+            );
+        }
+        if !util::cidr_contains(&self.record.cidr, parent)
+            || host_length > util::host_length(parent)
+            || self.full
+            || host_length > self.max_available_bits
+        {
+            return None;
+        }
+        if self.state == State::Free {
+            self.split();
+        }
+        if self.state != State::Unavailable {
+            return None;
+        }
+        let found = match self.low.as_deref_mut()?.allocate_free_space_within(
+            parent,
+            host_length,
+            name,
+            created_at,
+        ) {
+            Some(cidr) => Some(cidr),
+            None => self.high.as_deref_mut()?.allocate_free_space_within(
+                parent,
+                host_length,
+                name,
+                created_at,
+            ),
+        };
+        self.update_info();
+        found
+    }
+
     pub(crate) fn free(&mut self, cidr: &IpCidr) -> bool {
         if !util::cidr_contains(&self.record.cidr, cidr) {
             return false;
@@ -132,7 +189,7 @@ impl Subspace {
             State::Allocated => match self.record.cidr == *cidr {
                 true => {
                     self.state = State::Free;
-                    self.record.name = None;
+                    self.record = CidrRecord::new(self.record.cidr, None);
                     self.update_info();
                     true
                 }
@@ -157,7 +214,26 @@ impl Subspace {
         }
     }
 
-    pub(crate) fn claim(&mut self, cidr: &IpCidr, name: Option<&str>) -> bool {
+    /// Collapse every entirely free subtree below this subspace into a single
+    /// free subspace.
+    pub(crate) fn compact(&mut self) {
+        if self.state != State::Unavailable {
+            return;
+        }
+        let low = self.low.as_deref_mut().unwrap();
+        let high = self.high.as_deref_mut().unwrap();
+        low.compact();
+        high.compact();
+        if low.state == State::Free && high.state == State::Free {
+            self.low = None;
+            self.high = None;
+            self.state = State::Free;
+        }
+        self.update_info();
+    }
+
+    pub(crate) fn claim(&mut self, record: &CidrRecord) -> bool {
+        let cidr = &record.cidr;
         if !util::cidr_contains(&self.record.cidr, cidr) {
             return false;
         }
@@ -168,7 +244,7 @@ impl Subspace {
                 if self.record.cidr == *cidr {
                     self.state = State::Allocated;
                     self.update_info();
-                    self.record.name = name.map(|name| name.to_string());
+                    self.record = record.clone();
                     return true;
                 }
                 self.split();
@@ -176,8 +252,8 @@ impl Subspace {
             State::Unavailable => {}
         }
 
-        if self.low.as_deref_mut().unwrap().claim(cidr, name)
-            || self.high.as_deref_mut().unwrap().claim(cidr, name)
+        if self.low.as_deref_mut().unwrap().claim(record)
+            || self.high.as_deref_mut().unwrap().claim(record)
         {
             self.update_info();
             return true;
@@ -193,10 +269,29 @@ impl Subspace {
             return Some(self);
         }
         let found_low = self.low.as_deref()?.find_record(cidr);
-        return match found_low {
+        match found_low {
             Some(_) => found_low,
             None => self.high.as_deref()?.find_record(cidr),
+        }
+    }
+
+    /// The smallest subspace containing `cidr`.
+    pub(crate) fn find_containing(&self, cidr: &IpCidr) -> Option<&Self> {
+        if !util::cidr_contains(&self.record.cidr, cidr) {
+            return None;
+        }
+        let found_child = match self
+            .low
+            .as_deref()
+            .and_then(|low| low.find_containing(cidr))
+        {
+            Some(low) => Some(low),
+            None => self
+                .high
+                .as_deref()
+                .and_then(|high| high.find_containing(cidr)),
         };
+        Some(found_child.unwrap_or(self))
     }
 
     pub(crate) fn find_record_mut(&mut self, cidr: &IpCidr) -> Option<&mut Self> {
@@ -207,9 +302,9 @@ impl Subspace {
             return Some(self);
         }
         let found_low = self.low.as_deref_mut()?.find_record_mut(cidr);
-        return match found_low {
+        match found_low {
             Some(_) => found_low,
             None => self.high.as_deref_mut()?.find_record_mut(cidr),
-        };
+        }
     }
 }
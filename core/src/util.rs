@@ -1,8 +1,15 @@
 // Copyright 2023 The Milton Hirsch Institute, B.V.
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::Bits;
-use cidr::IpCidr;
+use crate::{AddressFamily, Bits};
+use cidr::{IpCidr, IpInet};
+use cidr_utils::separator;
+use std::cmp;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+pub(crate) fn now() -> String {
+    chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+}
 
 #[inline(always)]
 pub fn max_bits(cidr: &IpCidr) -> Bits {
@@ -23,6 +30,123 @@ pub fn cidr_contains(outer: &IpCidr, inner: &IpCidr) -> bool {
     outer.contains(&first) && outer.contains(&last)
 }
 
+pub(crate) fn parent(cidr: &IpCidr) -> Option<IpCidr> {
+    match cidr.network_length() {
+        0 => None,
+        length => Some(
+            IpInet::new(cidr.first_address(), length - 1)
+                .expect("shorter network length")
+                .network(),
+        ),
+    }
+}
+
+/// Smallest network containing both `a` and `b`, or `None` when their address
+/// families differ.
+pub(crate) fn supernet(a: &IpCidr, b: &IpCidr) -> Option<IpCidr> {
+    let common_bits = match (a.first_address(), b.first_address()) {
+        (IpAddr::V4(a), IpAddr::V4(b)) => (u32::from(a) ^ u32::from(b)).leading_zeros(),
+        (IpAddr::V6(a), IpAddr::V6(b)) => (u128::from(a) ^ u128::from(b)).leading_zeros(),
+        _ => return None,
+    };
+    let length = cmp::min(
+        common_bits as Bits,
+        cmp::min(a.network_length(), b.network_length()),
+    );
+    Some(
+        IpInet::new(a.first_address(), length)
+            .expect("length within address size")
+            .network(),
+    )
+}
+
+/// Move `cidr` from within `from` to the same relative position within `to`.
+pub(crate) fn rebase(cidr: &IpCidr, from: &IpCidr, to: &IpCidr) -> IpCidr {
+    let address = match (
+        cidr.first_address(),
+        from.first_address(),
+        to.first_address(),
+    ) {
+        (IpAddr::V4(address), IpAddr::V4(from), IpAddr::V4(to)) => IpAddr::V4(Ipv4Addr::from(
+            u32::from(address) - u32::from(from) + u32::from(to),
+        )),
+        (IpAddr::V6(address), IpAddr::V6(from), IpAddr::V6(to)) => IpAddr::V6(Ipv6Addr::from(
+            u128::from(address) - u128::from(from) + u128::from(to),
+        )),
+        _ => panic!("Mismatched address families"),
+    };
+    IpCidr::new(address, cidr.network_length()).expect("rebased network is aligned")
+}
+
+/// The `index`th subnet with `host_length` host bits within `cidr`, counting
+/// from its first address.
+pub(crate) fn nth_subnet(cidr: &IpCidr, host_length: Bits, index: u128) -> IpCidr {
+    let offset = index.checked_shl(host_length as u32).unwrap_or(0);
+    let address = match cidr.first_address() {
+        IpAddr::V4(address) => IpAddr::V4(Ipv4Addr::from(u32::from(address) + offset as u32)),
+        IpAddr::V6(address) => IpAddr::V6(Ipv6Addr::from(u128::from(address) + offset)),
+    };
+    IpCidr::new(address, max_bits(cidr) - host_length).expect("subnet is aligned")
+}
+
+/// Spread `seed` over all 128 bits with SplitMix64, which unlike the
+/// standard library hashers gives the same result on every platform and
+/// release.
+pub(crate) fn seed_hash(seed: u64) -> u128 {
+    fn split_mix(state: u64) -> u64 {
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+    let first = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let second = first.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    ((split_mix(first) as u128) << 64) | split_mix(second) as u128
+}
+
+pub(crate) fn sub_networks(cidr: &IpCidr, network_length: Bits) -> Option<Vec<IpCidr>> {
+    match cidr {
+        IpCidr::V4(cidr) => separator::Ipv4CidrSeparator::sub_networks(cidr, network_length)
+            .map(|vs| vs.into_iter().map(IpCidr::V4).collect()),
+        IpCidr::V6(cidr) => separator::Ipv6CidrSeparator::sub_networks(cidr, network_length)
+            .map(|vs| vs.into_iter().map(IpCidr::V6).collect()),
+    }
+}
+
+pub fn host_range(cidr: &IpCidr) -> (IpAddr, IpAddr) {
+    (cidr.first_address(), cidr.last_address())
+}
+
+/// Number of addresses usable by hosts.
+///
+/// IPv4 networks larger than a /31 lose the network and broadcast addresses.
+/// A /31 (RFC 3021), a /32 and all IPv6 networks count every address. Counts
+/// that do not fit in a `u128` (an IPv6 /0) saturate.
+pub fn usable_hosts(cidr: &IpCidr) -> u128 {
+    usable_hosts_with_bits(AddressFamily::from(cidr), host_length(cidr))
+}
+
+/// Number of addresses usable by hosts in a subnet of the given family with
+/// `bits` host bits, counted as `usable_hosts` does.
+fn usable_hosts_with_bits(family: AddressFamily, bits: Bits) -> u128 {
+    let count = 1u128.checked_shl(bits as u32).unwrap_or(u128::MAX);
+    match family {
+        AddressFamily::Ipv4 if bits > 1 => count - 2,
+        _ => count,
+    }
+}
+
+/// Fewest host bits of a subnet of the given family with room for `hosts`
+/// usable hosts, counted as `usable_hosts` does, or `None` if no subnet of
+/// the family is large enough.
+pub(crate) fn host_bits_for(family: AddressFamily, hosts: u128) -> Option<Bits> {
+    let max_bits = match family {
+        AddressFamily::Ipv4 => 32,
+        AddressFamily::Ipv6 => 128,
+    };
+    (0..=max_bits).find(|bits| usable_hosts_with_bits(family, *bits) >= hosts)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -56,6 +180,243 @@ mod tests {
         }
     }
 
+    mod supernet {
+        use super::*;
+        use std::str::FromStr;
+
+        fn cidr(text: &str) -> IpCidr {
+            IpCidr::from_str(text).unwrap()
+        }
+
+        #[test]
+        fn siblings() {
+            assert_eq!(
+                supernet(&cidr("10.20.0.0/24"), &cidr("10.20.1.0/24")),
+                Some(cidr("10.20.0.0/23"))
+            );
+        }
+
+        #[test]
+        fn distant() {
+            assert_eq!(
+                supernet(&cidr("10.20.0.0/24"), &cidr("10.20.3.128/25")),
+                Some(cidr("10.20.0.0/22"))
+            );
+        }
+
+        #[test]
+        fn nested() {
+            assert_eq!(
+                supernet(&cidr("10.20.0.0/16"), &cidr("10.20.3.0/24")),
+                Some(cidr("10.20.0.0/16"))
+            );
+        }
+
+        #[test]
+        fn v6() {
+            assert_eq!(
+                supernet(&cidr("1:2:3:4::/64"), &cidr("1:2:3:5::/64")),
+                Some(cidr("1:2:3:4::/63"))
+            );
+            assert_eq!(
+                supernet(&cidr("::/1"), &cidr("8000::/1")),
+                Some(cidr("::/0"))
+            );
+        }
+
+        #[test]
+        fn mixed_families() {
+            assert_eq!(supernet(&cidr("10.20.0.0/24"), &cidr("1:2:3:4::/64")), None);
+        }
+    }
+
+    mod parent {
+        use super::*;
+        use std::str::FromStr;
+
+        #[test]
+        fn low_half() {
+            let cidr = IpCidr::from_str("10.20.30.0/25").unwrap();
+            assert_eq!(parent(&cidr), IpCidr::from_str("10.20.30.0/24").ok());
+        }
+
+        #[test]
+        fn high_half() {
+            let cidr = IpCidr::from_str("10.20.30.128/25").unwrap();
+            assert_eq!(parent(&cidr), IpCidr::from_str("10.20.30.0/24").ok());
+        }
+
+        #[test]
+        fn no_parent() {
+            let cidr = IpCidr::from_str("::/0").unwrap();
+            assert_eq!(parent(&cidr), None);
+        }
+    }
+
+    mod rebase {
+        use super::*;
+        use std::str::FromStr;
+
+        #[test]
+        fn v4() {
+            let cidr = IpCidr::from_str("10.10.3.128/25").unwrap();
+            let from = IpCidr::from_str("10.10.0.0/16").unwrap();
+            let to = IpCidr::from_str("10.20.0.0/16").unwrap();
+            assert_eq!(
+                rebase(&cidr, &from, &to),
+                IpCidr::from_str("10.20.3.128/25").unwrap()
+            );
+        }
+
+        #[test]
+        fn v6() {
+            let cidr = IpCidr::from_str("fc00::1:0/112").unwrap();
+            let from = IpCidr::from_str("fc00::/96").unwrap();
+            let to = IpCidr::from_str("fd00::/96").unwrap();
+            assert_eq!(
+                rebase(&cidr, &from, &to),
+                IpCidr::from_str("fd00::1:0/112").unwrap()
+            );
+        }
+    }
+
+    mod nth_subnet {
+        use super::*;
+        use std::str::FromStr;
+
+        #[test]
+        fn v4() {
+            let cidr = IpCidr::from_str("10.10.0.0/16").unwrap();
+            assert_eq!(
+                nth_subnet(&cidr, 8, 0),
+                IpCidr::from_str("10.10.0.0/24").unwrap()
+            );
+            assert_eq!(
+                nth_subnet(&cidr, 8, 255),
+                IpCidr::from_str("10.10.255.0/24").unwrap()
+            );
+            assert_eq!(nth_subnet(&cidr, 16, 0), cidr);
+        }
+
+        #[test]
+        fn v6() {
+            let cidr = IpCidr::from_str("fc00::/96").unwrap();
+            assert_eq!(
+                nth_subnet(&cidr, 16, 3),
+                IpCidr::from_str("fc00::3:0/112").unwrap()
+            );
+            let all = IpCidr::from_str("::/0").unwrap();
+            assert_eq!(nth_subnet(&all, 128, 0), all);
+        }
+    }
+
+    mod seed_hash {
+        use super::*;
+
+        #[test]
+        fn stable() {
+            assert_eq!(seed_hash(0), seed_hash(0));
+            assert_ne!(seed_hash(0), seed_hash(1));
+            assert_eq!(seed_hash(0) >> 64, 0xe220_a839_7b1d_cdaf);
+        }
+    }
+
+    mod host_range {
+        use super::*;
+        use std::str::FromStr;
+
+        #[test]
+        fn v4() {
+            let cidr = IpCidr::from_str("10.20.30.0/24").unwrap();
+            assert_eq!(
+                host_range(&cidr),
+                (
+                    IpAddr::from_str("10.20.30.0").unwrap(),
+                    IpAddr::from_str("10.20.30.255").unwrap()
+                )
+            );
+        }
+
+        #[test]
+        fn v6() {
+            assert_eq!(
+                host_range(&TEST_CIDR6),
+                (
+                    IpAddr::from_str("1:2:3:4:a:14::").unwrap(),
+                    IpAddr::from_str("1:2:3:4:a:14:0:ffff").unwrap()
+                )
+            );
+        }
+    }
+
+    mod usable_hosts {
+        use super::*;
+        use std::str::FromStr;
+
+        fn usable(cidr: &str) -> u128 {
+            usable_hosts(&IpCidr::from_str(cidr).unwrap())
+        }
+
+        #[test]
+        fn v4_24() {
+            assert_eq!(usable("10.20.30.0/24"), 254);
+        }
+
+        #[test]
+        fn v4_30() {
+            assert_eq!(usable("10.20.30.0/30"), 2);
+        }
+
+        #[test]
+        fn v4_0() {
+            assert_eq!(usable("0.0.0.0/0"), (1 << 32) - 2);
+        }
+
+        #[test]
+        fn v4_31() {
+            assert_eq!(usable("10.20.30.0/31"), 2);
+        }
+
+        #[test]
+        fn v4_32() {
+            assert_eq!(usable("10.20.30.0/32"), 1);
+        }
+
+        #[test]
+        fn v6_64() {
+            assert_eq!(usable("1:2:3:4::/64"), 1 << 64);
+        }
+
+        #[test]
+        fn v6_0() {
+            assert_eq!(usable("::/0"), u128::MAX);
+        }
+    }
+
+    mod host_bits_for {
+        use super::*;
+
+        #[test]
+        fn v4() {
+            assert_eq!(host_bits_for(AddressFamily::Ipv4, 0), Some(0));
+            assert_eq!(host_bits_for(AddressFamily::Ipv4, 1), Some(0));
+            assert_eq!(host_bits_for(AddressFamily::Ipv4, 2), Some(1));
+            assert_eq!(host_bits_for(AddressFamily::Ipv4, 3), Some(3));
+            assert_eq!(host_bits_for(AddressFamily::Ipv4, 254), Some(8));
+            assert_eq!(host_bits_for(AddressFamily::Ipv4, 255), Some(9));
+            assert_eq!(host_bits_for(AddressFamily::Ipv4, (1 << 32) - 2), Some(32));
+            assert_eq!(host_bits_for(AddressFamily::Ipv4, (1 << 32) - 1), None);
+        }
+
+        #[test]
+        fn v6() {
+            assert_eq!(host_bits_for(AddressFamily::Ipv6, 2), Some(1));
+            assert_eq!(host_bits_for(AddressFamily::Ipv6, 256), Some(8));
+            assert_eq!(host_bits_for(AddressFamily::Ipv6, 257), Some(9));
+            assert_eq!(host_bits_for(AddressFamily::Ipv6, u128::MAX), Some(128));
+        }
+    }
+
     mod cidr_contains {
         use super::*;
         use cidr_utils::separator;